@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+
+use crate::dex::PokedexView;
+
+/// What a trainer has to do to earn a badge.
+#[derive(Clone, Debug)]
+pub enum Rule {
+    /// At least this many species in the Pokedex.
+    Caught(usize),
+    AllStarters,
+    /// Every loaded species with this (lowercase) type.
+    AllOfType(String),
+    /// Every non-legendary species caught, so legendaries start appearing.
+    LegendaryUnlock,
+    /// Logged in on this many days in a row.
+    Streak(u32),
+}
+
+#[derive(Clone, Debug)]
+pub struct Badge {
+    /// Stable key stored in the `achievements` table.
+    pub id: String,
+    pub title: String,
+    pub description: String,
+    pub rule: Rule,
+}
+
+/// What the rules are checked against.
+pub struct Progress<'a> {
+    pub pokedex: &'a HashSet<String>,
+    pub view: &'a PokedexView,
+    pub streak: u32,
+}
+
+const MILESTONES: &[(usize, &str, &str)] = &[
+    (1, "first-catch", "First Catch"),
+    (50, "caught-50", "Collector"),
+    (100, "caught-100", "Researcher"),
+    (151, "caught-151", "Pokemon Master"),
+];
+
+const STREAK_DAYS: u32 = 7;
+
+/// Every badge that can be earned with the loaded dex: the fixed badges plus
+/// one per type.
+pub fn catalog(view: &PokedexView) -> Vec<Badge> {
+    let mut badges: Vec<Badge> = MILESTONES
+        .iter()
+        .map(|&(count, id, title)| Badge {
+            id: id.to_string(),
+            title: title.to_string(),
+            description: if count == 1 {
+                "Catch your first Pokemon".to_string()
+            } else {
+                format!("Catch {count} different Pokemon")
+            },
+            rule: Rule::Caught(count),
+        })
+        .collect();
+    badges.push(Badge {
+        id: "all-starters".to_string(),
+        title: "Starter Set".to_string(),
+        description: "Catch every starter Pokemon".to_string(),
+        rule: Rule::AllStarters,
+    });
+    badges.push(Badge {
+        id: "legendary-unlock".to_string(),
+        title: "Legend Seeker".to_string(),
+        description: "Catch every non-legendary Pokemon".to_string(),
+        rule: Rule::LegendaryUnlock,
+    });
+    badges.push(Badge {
+        id: format!("streak-{STREAK_DAYS}"),
+        title: "Dedicated Trainer".to_string(),
+        description: format!("Play {STREAK_DAYS} days in a row"),
+        rule: Rule::Streak(STREAK_DAYS),
+    });
+    let mut types: Vec<&String> = view.types_by_name.values().flatten().collect();
+    types.sort();
+    types.dedup();
+    for kind in types {
+        let mut title = kind.clone();
+        if let Some(first) = title.get_mut(0..1) {
+            first.make_ascii_uppercase();
+        }
+        badges.push(Badge {
+            id: format!("type-{kind}"),
+            title: format!("{title} Specialist"),
+            description: format!("Catch every {title}-type Pokemon"),
+            rule: Rule::AllOfType(kind.clone()),
+        });
+    }
+    badges
+}
+
+pub fn earned(rule: &Rule, progress: &Progress) -> bool {
+    let view = progress.view;
+    let in_dex = || view.names.iter().filter(|name| !name.is_empty());
+    match rule {
+        Rule::Caught(count) => {
+            in_dex()
+                .filter(|name| progress.pokedex.contains(*name))
+                .count()
+                >= *count
+        }
+        Rule::AllStarters => {
+            let mut starters = in_dex().filter(|name| view.is_starter(name)).peekable();
+            starters.peek().is_some() && starters.all(|name| progress.pokedex.contains(name))
+        }
+        Rule::AllOfType(kind) => {
+            let mut of_type = in_dex()
+                .filter(|name| {
+                    view.types_by_name
+                        .get(*name)
+                        .is_some_and(|types| types.contains(kind))
+                })
+                .peekable();
+            of_type.peek().is_some() && of_type.all(|name| progress.pokedex.contains(name))
+        }
+        Rule::LegendaryUnlock => crate::legendaries_unlocked(progress.pokedex, view),
+        Rule::Streak(days) => progress.streak >= *days,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn set(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// Bulbasaur, Charmander and Squirtle as starters, a gap, and Mewtwo as
+    /// the only legendary.
+    fn view() -> PokedexView {
+        let types: HashMap<String, Vec<String>> = [
+            ("bulbasaur", &["grass", "poison"][..]),
+            ("charmander", &["fire"]),
+            ("squirtle", &["water"]),
+            ("mewtwo", &["psychic"]),
+        ]
+        .into_iter()
+        .map(|(name, kinds)| {
+            let kinds = kinds.iter().map(|kind| kind.to_string()).collect();
+            (name.to_string(), kinds)
+        })
+        .collect();
+        PokedexView {
+            names: ["bulbasaur", "charmander", "", "squirtle", "mewtwo"]
+                .map(String::from)
+                .to_vec(),
+            totals_by_name: HashMap::new(),
+            types_by_name: types,
+            stats_by_name: HashMap::new(),
+            regions: Vec::new(),
+            legendaries: set(&["mewtwo"]),
+            starters: set(&["bulbasaur", "charmander", "squirtle"]),
+            evolutions: HashMap::new(),
+            info: HashMap::new(),
+        }
+    }
+
+    fn check(rule: Rule, caught: &[&str], view: &PokedexView) -> bool {
+        let pokedex = set(caught);
+        let progress = Progress {
+            pokedex: &pokedex,
+            view,
+            streak: 0,
+        };
+        earned(&rule, &progress)
+    }
+
+    #[test]
+    fn catalog_has_fixed_badges_and_one_per_type() {
+        let badges = catalog(&view());
+        let ids: Vec<&str> = badges.iter().map(|badge| badge.id.as_str()).collect();
+        assert_eq!(
+            ids,
+            [
+                "first-catch",
+                "caught-50",
+                "caught-100",
+                "caught-151",
+                "all-starters",
+                "legendary-unlock",
+                "streak-7",
+                "type-fire",
+                "type-grass",
+                "type-poison",
+                "type-psychic",
+                "type-water",
+            ]
+        );
+        assert_eq!(badges[8].title, "Grass Specialist");
+        assert_eq!(badges[0].description, "Catch your first Pokemon");
+        assert_eq!(badges[1].description, "Catch 50 different Pokemon");
+    }
+
+    #[test]
+    fn caught_counts_only_species_in_the_loaded_dex() {
+        let view = view();
+        assert!(!check(Rule::Caught(1), &[], &view));
+        assert!(check(Rule::Caught(1), &["squirtle"], &view));
+        assert!(!check(Rule::Caught(2), &["squirtle", "pikachu"], &view));
+        assert!(check(Rule::Caught(2), &["squirtle", "mewtwo"], &view));
+    }
+
+    #[test]
+    fn all_starters_needs_every_starter() {
+        let mut view = view();
+        assert!(!check(
+            Rule::AllStarters,
+            &["bulbasaur", "charmander"],
+            &view
+        ));
+        let all = ["bulbasaur", "charmander", "squirtle"];
+        assert!(check(Rule::AllStarters, &all, &view));
+        view.starters.clear();
+        assert!(!check(Rule::AllStarters, &all, &view));
+    }
+
+    #[test]
+    fn all_of_type_needs_a_species_of_that_type() {
+        let view = view();
+        assert!(check(
+            Rule::AllOfType("grass".into()),
+            &["bulbasaur"],
+            &view
+        ));
+        assert!(check(
+            Rule::AllOfType("poison".into()),
+            &["bulbasaur"],
+            &view
+        ));
+        assert!(!check(
+            Rule::AllOfType("fire".into()),
+            &["bulbasaur"],
+            &view
+        ));
+        assert!(!check(
+            Rule::AllOfType("dragon".into()),
+            &["bulbasaur"],
+            &view
+        ));
+    }
+
+    #[test]
+    fn legendary_unlock_ignores_legendaries() {
+        let view = view();
+        let starters = ["bulbasaur", "charmander", "squirtle"];
+        assert!(check(Rule::LegendaryUnlock, &starters, &view));
+        assert!(!check(
+            Rule::LegendaryUnlock,
+            &["bulbasaur", "mewtwo"],
+            &view
+        ));
+    }
+
+    #[test]
+    fn streak_needs_enough_days() {
+        let view = view();
+        let pokedex = HashSet::new();
+        let progress = |streak| Progress {
+            pokedex: &pokedex,
+            view: &view,
+            streak,
+        };
+        assert!(!earned(&Rule::Streak(STREAK_DAYS), &progress(6)));
+        assert!(earned(&Rule::Streak(STREAK_DAYS), &progress(7)));
+        assert!(earned(&Rule::Streak(STREAK_DAYS), &progress(30)));
+    }
+}
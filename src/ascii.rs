@@ -1,6 +1,16 @@
 use image::codecs::gif::GifDecoder;
 use image::imageops::FilterType;
-use image::{AnimationDecoder, DynamicImage, Frame, RgbImage};
+use image::{AnimationDecoder, DynamicImage, Frame, RgbImage, RgbaImage};
+
+pub const IMG_CHARSET: &str =
+    ".'`^\",:;Il!i><~+_-?][}{1)(|\\/*tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$Ñ";
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum ColorMode {
+    Truecolor,
+    Ansi256,
+    Mono,
+}
 
 pub struct AsciiImage {
     pub width: usize,
@@ -9,20 +19,111 @@ pub struct AsciiImage {
     pub colors: Vec<(u8, u8, u8)>,
 }
 
-pub fn load_ascii_image(path: &str, width: usize, height: usize, charset: &str) -> AsciiImage {
-    let img = image::open(path)
-        .unwrap_or_else(|_| panic!("failed to load image: {path}"))
-        .resize_exact(width as u32, height as u32, FilterType::Nearest)
-        .to_rgb8();
-    ascii_from_rgb(img, charset)
+impl AsciiImage {
+    /// Copy of the image with every color rotated around the hue wheel, used for
+    /// shiny variants that have no sprite of their own. Grays are left alone.
+    pub fn hue_shifted(&self, degrees: f32) -> AsciiImage {
+        AsciiImage {
+            width: self.width,
+            height: self.height,
+            chars: self.chars.clone(),
+            colors: self
+                .colors
+                .iter()
+                .map(|&(r, g, b)| shift_hue(r, g, b, degrees))
+                .collect(),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ResizeFilter {
+    Nearest,
+    Triangle,
+    Lanczos,
+    Area,
+}
+
+impl ResizeFilter {
+    pub fn parse(raw: &str) -> Option<Self> {
+        match raw.trim().to_lowercase().as_str() {
+            "nearest" => Some(Self::Nearest),
+            "triangle" | "bilinear" => Some(Self::Triangle),
+            "lanczos" | "lanczos3" => Some(Self::Lanczos),
+            "area" | "box" => Some(Self::Area),
+            _ => None,
+        }
+    }
 }
 
-pub fn load_ascii_animation(
-    path: &str,
-    width: usize,
-    height: usize,
-    charset: &str,
-) -> Vec<AsciiImage> {
+/// How a source image is mapped onto the target cell grid.
+#[derive(Clone, Debug)]
+pub struct ConvertConfig {
+    pub width: usize,
+    pub height: usize,
+    pub filter: ResizeFilter,
+    pub preserve_aspect: bool,
+    /// Height of a terminal cell divided by its width.
+    pub cell_aspect: f32,
+    /// Pad an aspect-fitted image back out to `width` x `height`.
+    pub letterbox: bool,
+}
+
+impl ConvertConfig {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            filter: ResizeFilter::Nearest,
+            preserve_aspect: false,
+            cell_aspect: 2.0,
+            letterbox: true,
+        }
+    }
+
+    pub fn filter(mut self, filter: ResizeFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn preserve_aspect(mut self, preserve: bool) -> Self {
+        self.preserve_aspect = preserve;
+        self
+    }
+
+    pub fn cell_aspect(mut self, cell_aspect: f32) -> Self {
+        if cell_aspect.is_finite() && cell_aspect > 0.0 {
+            self.cell_aspect = cell_aspect;
+        }
+        self
+    }
+
+    pub fn letterbox(mut self, letterbox: bool) -> Self {
+        self.letterbox = letterbox;
+        self
+    }
+
+    fn fitted_size(&self, src_w: u32, src_h: u32) -> (usize, usize) {
+        let width = self.width.max(1);
+        let height = self.height.max(1);
+        if !self.preserve_aspect || src_w == 0 || src_h == 0 {
+            return (width, height);
+        }
+        let src_w = src_w as f32;
+        let src_h = src_h as f32 / self.cell_aspect;
+        let scale = (width as f32 / src_w).min(height as f32 / src_h);
+        let fit_w = ((src_w * scale).round() as usize).clamp(1, width);
+        let fit_h = ((src_h * scale).round() as usize).clamp(1, height);
+        (fit_w, fit_h)
+    }
+}
+
+pub fn load_ascii_image(path: &str, config: &ConvertConfig, charset: &str) -> AsciiImage {
+    let img = image::open(path).unwrap_or_else(|_| panic!("failed to load image: {path}"));
+    convert_image(img.to_rgba8(), config, charset)
+}
+
+pub fn load_ascii_animation(path: &str, config: &ConvertConfig, charset: &str) -> Vec<AsciiImage> {
     let file =
         std::fs::File::open(path).unwrap_or_else(|_| panic!("failed to load animation: {path}"));
     let reader = std::io::BufReader::new(file);
@@ -36,14 +137,93 @@ pub fn load_ascii_animation(
     let mut out = Vec::with_capacity(frames.len());
     for frame in frames.into_iter() {
         let frame: Frame = frame;
-        let img = DynamicImage::ImageRgba8(frame.into_buffer())
-            .resize_exact(width as u32, height as u32, FilterType::Nearest)
-            .to_rgb8();
-        out.push(ascii_from_rgb(img, charset));
+        out.push(convert_image(frame.into_buffer(), config, charset));
     }
     out
 }
 
+fn convert_image(img: RgbaImage, config: &ConvertConfig, charset: &str) -> AsciiImage {
+    let (fit_w, fit_h) = config.fitted_size(img.width(), img.height());
+    let resized = resize_rgb(img, fit_w as u32, fit_h as u32, config.filter);
+    let ascii = ascii_from_rgb(resized, charset);
+    if config.preserve_aspect && config.letterbox {
+        letterbox(ascii, config.width.max(1), config.height.max(1))
+    } else {
+        ascii
+    }
+}
+
+fn resize_rgb(img: RgbaImage, width: u32, height: u32, filter: ResizeFilter) -> RgbImage {
+    let img = DynamicImage::ImageRgba8(img);
+    let filter_type = match filter {
+        ResizeFilter::Nearest => FilterType::Nearest,
+        ResizeFilter::Triangle => FilterType::Triangle,
+        ResizeFilter::Lanczos => FilterType::Lanczos3,
+        ResizeFilter::Area => return area_resize(&img.to_rgb8(), width, height),
+    };
+    img.resize_exact(width, height, filter_type).to_rgb8()
+}
+
+// Box filter: every output pixel is the mean of the source pixels it covers.
+fn area_resize(src: &RgbImage, width: u32, height: u32) -> RgbImage {
+    let (src_w, src_h) = src.dimensions();
+    let mut out = RgbImage::new(width, height);
+    for y in 0..height {
+        let y0 = (y as u64 * src_h as u64 / height as u64) as u32;
+        let y1 = (((y as u64 + 1) * src_h as u64).div_ceil(height as u64) as u32).max(y0 + 1);
+        for x in 0..width {
+            let x0 = (x as u64 * src_w as u64 / width as u64) as u32;
+            let x1 = (((x as u64 + 1) * src_w as u64).div_ceil(width as u64) as u32).max(x0 + 1);
+            let mut sum = [0u64; 3];
+            let mut count = 0u64;
+            for sy in y0..y1.min(src_h) {
+                for sx in x0..x1.min(src_w) {
+                    let p = src.get_pixel(sx, sy).0;
+                    sum[0] += p[0] as u64;
+                    sum[1] += p[1] as u64;
+                    sum[2] += p[2] as u64;
+                    count += 1;
+                }
+            }
+            let count = count.max(1);
+            out.put_pixel(
+                x,
+                y,
+                image::Rgb([
+                    (sum[0] / count) as u8,
+                    (sum[1] / count) as u8,
+                    (sum[2] / count) as u8,
+                ]),
+            );
+        }
+    }
+    out
+}
+
+fn letterbox(image: AsciiImage, width: usize, height: usize) -> AsciiImage {
+    if image.width == width && image.height == height {
+        return image;
+    }
+    let offset_x = width.saturating_sub(image.width) / 2;
+    let offset_y = height.saturating_sub(image.height) / 2;
+    let mut chars = vec![' '; width * height];
+    let mut colors = vec![(0, 0, 0); width * height];
+    for y in 0..image.height.min(height) {
+        for x in 0..image.width.min(width) {
+            let src_idx = x + y * image.width;
+            let dst_idx = (x + offset_x) + (y + offset_y) * width;
+            chars[dst_idx] = image.chars[src_idx];
+            colors[dst_idx] = image.colors[src_idx];
+        }
+    }
+    AsciiImage {
+        width,
+        height,
+        chars,
+        colors,
+    }
+}
+
 fn ascii_from_rgb(img: RgbImage, charset: &str) -> AsciiImage {
     let charset: Vec<char> = charset.chars().collect();
     let width = img.width() as usize;
@@ -178,3 +358,129 @@ fn apply_color_boost(r: u8, g: u8, b: u8, shade: f32) -> (u8, u8, u8) {
     let b = (bf * 255.0).clamp(0.0, 255.0) as u8;
     (r, g, b)
 }
+
+fn shift_hue(r: u8, g: u8, b: u8, degrees: f32) -> (u8, u8, u8) {
+    let rf = r as f32 / 255.0;
+    let gf = g as f32 / 255.0;
+    let bf = b as f32 / 255.0;
+    let max = rf.max(gf).max(bf);
+    let min = rf.min(gf).min(bf);
+    let delta = max - min;
+    if delta < 0.04 {
+        return (r, g, b);
+    }
+    let hue = if max == rf {
+        60.0 * ((gf - bf) / delta).rem_euclid(6.0)
+    } else if max == gf {
+        60.0 * ((bf - rf) / delta + 2.0)
+    } else {
+        60.0 * ((rf - gf) / delta + 4.0)
+    };
+    let hue = (hue + degrees).rem_euclid(360.0);
+    let x = delta * (1.0 - ((hue / 60.0).rem_euclid(2.0) - 1.0).abs());
+    let (rf, gf, bf) = match (hue / 60.0) as u32 {
+        0 => (delta, x, 0.0),
+        1 => (x, delta, 0.0),
+        2 => (0.0, delta, x),
+        3 => (0.0, x, delta),
+        4 => (x, 0.0, delta),
+        _ => (delta, 0.0, x),
+    };
+    let to_u8 = |v: f32| ((v + min) * 255.0).round().clamp(0.0, 255.0) as u8;
+    (to_u8(rf), to_u8(gf), to_u8(bf))
+}
+
+pub fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
+    // 16-231: 6x6x6 color cube, 232-255: grayscale
+    let r = r as u16;
+    let g = g as u16;
+    let b = b as u16;
+    let gray = (r + g + b) / 3;
+    if gray > 8
+        && gray < 248
+        && (r as i16 - g as i16).abs() < 12
+        && (r as i16 - b as i16).abs() < 12
+    {
+        let gray_index = ((gray - 8) * 24 / 247) as u8;
+        return 232 + gray_index;
+    }
+    let rc = (r * 5 / 255) as u8;
+    let gc = (g * 5 / 255) as u8;
+    let bc = (b * 5 / 255) as u8;
+    16 + 36 * rc + 6 * gc + bc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stretching_fills_the_target() {
+        let config = ConvertConfig::new(40, 20);
+        assert_eq!(config.fitted_size(200, 100), (40, 20));
+        assert_eq!(ConvertConfig::new(0, 0).fitted_size(10, 10), (1, 1));
+    }
+
+    #[test]
+    fn aspect_fitting_accounts_for_tall_cells() {
+        let config = ConvertConfig::new(40, 20).preserve_aspect(true);
+        // Cells are twice as tall as wide, so a square source fills 40x20.
+        assert_eq!(config.fitted_size(100, 100), (40, 20));
+        assert_eq!(config.fitted_size(200, 100), (40, 10));
+        assert_eq!(config.fitted_size(100, 200), (20, 20));
+        assert_eq!(config.fitted_size(0, 100), (40, 20));
+        let square_cells = config.cell_aspect(1.0);
+        assert_eq!(square_cells.fitted_size(100, 100), (20, 20));
+    }
+
+    #[test]
+    fn area_resize_averages_covered_pixels() {
+        let mut src = RgbImage::new(4, 2);
+        for (x, _, pixel) in src.enumerate_pixels_mut() {
+            *pixel = if x < 2 {
+                image::Rgb([255, 0, 0])
+            } else {
+                image::Rgb([0, 0, 200])
+            };
+        }
+        let out = area_resize(&src, 2, 1);
+        assert_eq!(out.dimensions(), (2, 1));
+        assert_eq!(out.get_pixel(0, 0).0, [255, 0, 0]);
+        assert_eq!(out.get_pixel(1, 0).0, [0, 0, 200]);
+        let out = area_resize(&src, 1, 1);
+        assert_eq!(out.get_pixel(0, 0).0, [127, 0, 100]);
+        assert_eq!(area_resize(&src, 8, 4).dimensions(), (8, 4));
+    }
+
+    #[test]
+    fn letterbox_centers_the_image() {
+        let image = AsciiImage {
+            width: 2,
+            height: 1,
+            chars: vec!['a', 'b'],
+            colors: vec![(1, 1, 1), (2, 2, 2)],
+        };
+        let boxed = letterbox(image, 4, 3);
+        assert_eq!((boxed.width, boxed.height), (4, 3));
+        let rows: Vec<String> = boxed
+            .chars
+            .chunks(4)
+            .map(|row| row.iter().collect())
+            .collect();
+        assert_eq!(rows, ["    ", " ab ", "    "]);
+        assert_eq!(boxed.colors[5], (1, 1, 1));
+    }
+
+    #[test]
+    fn converted_images_keep_the_target_size() {
+        let img = RgbaImage::from_pixel(30, 10, image::Rgba([200, 50, 50, 255]));
+        let fitted = ConvertConfig::new(10, 10)
+            .filter(ResizeFilter::Area)
+            .preserve_aspect(true);
+        let ascii = convert_image(img.clone(), &fitted, IMG_CHARSET);
+        assert_eq!((ascii.width, ascii.height), (10, 10));
+        assert_eq!(ascii.chars.len(), 100);
+        let unboxed = convert_image(img, &fitted.letterbox(false), IMG_CHARSET);
+        assert_eq!((unboxed.width, unboxed.height), (10, 2));
+    }
+}
@@ -0,0 +1,477 @@
+use crate::dex::BaseStats;
+
+const LOG_LINES: usize = 3;
+
+#[derive(Clone, Debug)]
+pub struct Move {
+    pub name: &'static str,
+    pub kind: &'static str,
+    pub power: u16,
+    pub priority: i8,
+}
+
+const TACKLE: Move = Move {
+    name: "Tackle",
+    kind: "normal",
+    power: 40,
+    priority: 0,
+};
+
+const QUICK_ATTACK: Move = Move {
+    name: "Quick Attack",
+    kind: "normal",
+    power: 40,
+    priority: 1,
+};
+
+/// Signature move for each type, used to fill out a species' move set.
+fn type_move(kind: &str) -> Option<Move> {
+    let (name, kind) = match kind {
+        "normal" => ("Headbutt", "normal"),
+        "fire" => ("Ember", "fire"),
+        "water" => ("Water Gun", "water"),
+        "grass" => ("Vine Whip", "grass"),
+        "electric" => ("Thunder Shock", "electric"),
+        "ice" => ("Aurora Beam", "ice"),
+        "fighting" => ("Karate Chop", "fighting"),
+        "poison" => ("Sludge", "poison"),
+        "ground" => ("Bulldoze", "ground"),
+        "flying" => ("Wing Attack", "flying"),
+        "psychic" => ("Confusion", "psychic"),
+        "bug" => ("Bug Bite", "bug"),
+        "rock" => ("Rock Throw", "rock"),
+        "ghost" => ("Shadow Punch", "ghost"),
+        "dragon" => ("Dragon Breath", "dragon"),
+        "dark" => ("Bite", "dark"),
+        "steel" => ("Metal Claw", "steel"),
+        "fairy" => ("Fairy Wind", "fairy"),
+        _ => return None,
+    };
+    Some(Move {
+        name,
+        kind,
+        power: 60,
+        priority: 0,
+    })
+}
+
+/// Types whose moves hit with Sp. Atk against Sp. Def (the pre-split rule).
+fn is_special(kind: &str) -> bool {
+    matches!(
+        kind,
+        "fire" | "water" | "grass" | "electric" | "ice" | "psychic" | "dragon" | "dark"
+    )
+}
+
+/// Damage multiplier for an attacking type against one defending type.
+fn type_multiplier(attack: &str, defend: &str) -> f32 {
+    let (strong, weak, immune): (&[&str], &[&str], &[&str]) = match attack {
+        "normal" => (&[], &["rock", "steel"], &["ghost"]),
+        "fire" => (
+            &["grass", "ice", "bug", "steel"],
+            &["fire", "water", "rock", "dragon"],
+            &[],
+        ),
+        "water" => (
+            &["fire", "ground", "rock"],
+            &["water", "grass", "dragon"],
+            &[],
+        ),
+        "electric" => (
+            &["water", "flying"],
+            &["electric", "grass", "dragon"],
+            &["ground"],
+        ),
+        "grass" => (
+            &["water", "ground", "rock"],
+            &[
+                "fire", "grass", "poison", "flying", "bug", "dragon", "steel",
+            ],
+            &[],
+        ),
+        "ice" => (
+            &["grass", "ground", "flying", "dragon"],
+            &["fire", "water", "ice", "steel"],
+            &[],
+        ),
+        "fighting" => (
+            &["normal", "ice", "rock", "dark", "steel"],
+            &["poison", "flying", "psychic", "bug", "fairy"],
+            &["ghost"],
+        ),
+        "poison" => (
+            &["grass", "fairy"],
+            &["poison", "ground", "rock", "ghost"],
+            &["steel"],
+        ),
+        "ground" => (
+            &["fire", "electric", "poison", "rock", "steel"],
+            &["grass", "bug"],
+            &["flying"],
+        ),
+        "flying" => (
+            &["grass", "fighting", "bug"],
+            &["electric", "rock", "steel"],
+            &[],
+        ),
+        "psychic" => (&["fighting", "poison"], &["psychic", "steel"], &["dark"]),
+        "bug" => (
+            &["grass", "psychic", "dark"],
+            &[
+                "fire", "fighting", "poison", "flying", "ghost", "steel", "fairy",
+            ],
+            &[],
+        ),
+        "rock" => (
+            &["fire", "ice", "flying", "bug"],
+            &["fighting", "ground", "steel"],
+            &[],
+        ),
+        "ghost" => (&["psychic", "ghost"], &["dark"], &["normal"]),
+        "dragon" => (&["dragon"], &["steel"], &["fairy"]),
+        "dark" => (&["psychic", "ghost"], &["fighting", "dark", "fairy"], &[]),
+        "steel" => (
+            &["ice", "rock", "fairy"],
+            &["fire", "water", "electric", "steel"],
+            &[],
+        ),
+        "fairy" => (
+            &["fighting", "dragon", "dark"],
+            &["fire", "poison", "steel"],
+            &[],
+        ),
+        _ => (&[], &[], &[]),
+    };
+    if immune.contains(&defend) {
+        0.0
+    } else if strong.contains(&defend) {
+        2.0
+    } else if weak.contains(&defend) {
+        0.5
+    } else {
+        1.0
+    }
+}
+
+pub fn effectiveness(attack: &str, defender: &[String]) -> f32 {
+    defender
+        .iter()
+        .map(|kind| type_multiplier(attack, kind))
+        .product()
+}
+
+pub struct Battler {
+    pub name: String,
+    pub types: Vec<String>,
+    pub level: u16,
+    pub max_hp: u16,
+    pub hp: u16,
+    attack: u16,
+    defense: u16,
+    sp_attack: u16,
+    sp_defense: u16,
+    speed: u16,
+    pub moves: Vec<Move>,
+}
+
+impl Battler {
+    pub fn new(name: &str, types: &[String], stats: &BaseStats, level: u16) -> Self {
+        let stat = |base: u16| 2 * base * level / 100 + 5;
+        let max_hp = 2 * stats.hp * level / 100 + level + 10;
+        let mut moves = vec![TACKLE, QUICK_ATTACK];
+        for kind in types {
+            if let Some(extra) = type_move(kind)
+                && !moves.iter().any(|m| m.name == extra.name)
+            {
+                moves.push(extra);
+            }
+        }
+        Self {
+            name: name.to_string(),
+            types: types.to_vec(),
+            level,
+            max_hp,
+            hp: max_hp,
+            attack: stat(stats.attack),
+            defense: stat(stats.defense),
+            sp_attack: stat(stats.sp_attack),
+            sp_defense: stat(stats.sp_defense),
+            speed: stat(stats.speed),
+            moves,
+        }
+    }
+
+    pub fn hp_ratio(&self) -> f32 {
+        if self.max_hp == 0 {
+            return 0.0;
+        }
+        self.hp as f32 / self.max_hp as f32
+    }
+
+    pub fn fainted(&self) -> bool {
+        self.hp == 0
+    }
+
+    /// Finds a move by its 1-based number or (case-insensitive) name.
+    pub fn find_move(&self, input: &str) -> Option<usize> {
+        let input = input.trim();
+        if let Ok(num) = input.parse::<usize>() {
+            return (num >= 1 && num <= self.moves.len()).then(|| num - 1);
+        }
+        self.moves
+            .iter()
+            .position(|m| m.name.eq_ignore_ascii_case(input))
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Side {
+    Player,
+    Wild,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Outcome {
+    Ongoing,
+    Won,
+    Lost,
+}
+
+/// One wild encounter's fight. The wild side keeps its damage when the
+/// trainer switches partners or steps back to throw a ball.
+pub struct Battle {
+    pub player: Battler,
+    pub wild: Battler,
+    pub log: Vec<String>,
+    /// Partners that fainted against this wild Pokemon.
+    pub fainted: Vec<String>,
+    pub last_hit: Option<Side>,
+}
+
+impl Battle {
+    pub fn new(player: Battler, wild: Battler, player_label: &str) -> Self {
+        let mut battle = Self {
+            player,
+            wild,
+            log: Vec::new(),
+            fainted: Vec::new(),
+            last_hit: None,
+        };
+        battle.push_log(format!("Go! {player_label}!"));
+        battle
+    }
+
+    pub fn switch_player(&mut self, player: Battler, label: &str) {
+        self.player = player;
+        self.last_hit = None;
+        self.push_log(format!("Go! {label}!"));
+    }
+
+    /// Multiplier for the capture chance: a full-HP target gives 1.0, rising
+    /// to 2.5 as its HP approaches zero.
+    pub fn catch_bonus(&self) -> f32 {
+        1.0 + 1.5 * (1.0 - self.wild.hp_ratio())
+    }
+
+    /// Plays one round: the faster side (or the higher-priority move) acts
+    /// first, then the other if it is still standing. `roll` yields values in
+    /// `[0, 1)` for the wild move pick, speed ties, and damage spread.
+    pub fn play_turn(
+        &mut self,
+        player_move: usize,
+        labels: (&str, &str),
+        mut roll: impl FnMut() -> f32,
+    ) -> Outcome {
+        let wild_move = ((roll() * self.wild.moves.len() as f32) as usize)
+            .min(self.wild.moves.len().saturating_sub(1));
+        let player_priority = self.player.moves[player_move].priority;
+        let wild_priority = self.wild.moves[wild_move].priority;
+        let player_first = match player_priority.cmp(&wild_priority) {
+            std::cmp::Ordering::Greater => true,
+            std::cmp::Ordering::Less => false,
+            std::cmp::Ordering::Equal => match self.player.speed.cmp(&self.wild.speed) {
+                std::cmp::Ordering::Greater => true,
+                std::cmp::Ordering::Less => false,
+                std::cmp::Ordering::Equal => roll() < 0.5,
+            },
+        };
+        let order = if player_first {
+            [(Side::Player, player_move), (Side::Wild, wild_move)]
+        } else {
+            [(Side::Wild, wild_move), (Side::Player, player_move)]
+        };
+        for (side, move_idx) in order {
+            self.attack(side, move_idx, labels, roll());
+            if self.wild.fainted() {
+                self.push_log(format!("{} fainted!", labels.1));
+                return Outcome::Won;
+            }
+            if self.player.fainted() {
+                self.push_log(format!("{} fainted!", labels.0));
+                self.fainted.push(self.player.name.clone());
+                return Outcome::Lost;
+            }
+        }
+        Outcome::Ongoing
+    }
+
+    fn attack(&mut self, side: Side, move_idx: usize, labels: (&str, &str), spread: f32) {
+        let (attacker, defender, label, target) = match side {
+            Side::Player => (&self.player, &mut self.wild, labels.0, Side::Wild),
+            Side::Wild => (&self.wild, &mut self.player, labels.1, Side::Player),
+        };
+        let used = attacker.moves[move_idx].clone();
+        let multiplier = effectiveness(used.kind, &defender.types);
+        let (atk, def) = if is_special(used.kind) {
+            (attacker.sp_attack, defender.sp_defense)
+        } else {
+            (attacker.attack, defender.defense)
+        };
+        let mut damage = 0;
+        if multiplier > 0.0 {
+            let level = attacker.level as f32;
+            let base = ((2.0 * level / 5.0 + 2.0) * used.power as f32 * atk as f32
+                / def.max(1) as f32)
+                / 50.0
+                + 2.0;
+            let stab = if attacker.types.iter().any(|t| t == used.kind) {
+                1.5
+            } else {
+                1.0
+            };
+            damage = ((base * stab * multiplier * (0.85 + 0.15 * spread)) as u16).max(1);
+        }
+        defender.hp = defender.hp.saturating_sub(damage);
+        self.last_hit = Some(target);
+        self.push_log(format!("{label} used {}!", used.name));
+        if multiplier == 0.0 {
+            self.push_log("It had no effect...".to_string());
+        } else if multiplier > 1.0 {
+            self.push_log("It's super effective!".to_string());
+        } else if multiplier < 1.0 {
+            self.push_log("It's not very effective...".to_string());
+        }
+    }
+
+    pub fn push_log(&mut self, line: String) {
+        self.log.push(line);
+        if self.log.len() > LOG_LINES {
+            let excess = self.log.len() - LOG_LINES;
+            self.log.drain(..excess);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// A level 50 Pokemon with base HP 100 (160 HP) and every other base stat
+    /// at `base` (105 when `base` is 100).
+    fn battler(name: &str, kinds: &[&str], base: u16) -> Battler {
+        let stats = BaseStats {
+            hp: 100,
+            attack: base,
+            defense: base,
+            sp_attack: base,
+            sp_defense: base,
+            speed: base,
+        };
+        Battler::new(name, &types(kinds), &stats, 50)
+    }
+
+    #[test]
+    fn type_chart_multiplies_across_both_types() {
+        assert_eq!(effectiveness("water", &types(&["fire"])), 2.0);
+        assert_eq!(effectiveness("electric", &types(&["water", "flying"])), 4.0);
+        assert_eq!(effectiveness("grass", &types(&["fire", "flying"])), 0.25);
+        assert_eq!(effectiveness("ground", &types(&["flying", "rock"])), 0.0);
+        assert_eq!(effectiveness("normal", &types(&["water"])), 1.0);
+    }
+
+    #[test]
+    fn battlers_get_level_scaled_stats_and_a_move_per_type() {
+        let pikachu = battler("pikachu", &["electric"], 100);
+        assert_eq!(pikachu.max_hp, 160);
+        assert_eq!(pikachu.attack, 105);
+        let names: Vec<&str> = pikachu.moves.iter().map(|m| m.name).collect();
+        assert_eq!(names, ["Tackle", "Quick Attack", "Thunder Shock"]);
+        assert_eq!(pikachu.find_move("3"), Some(2));
+        assert_eq!(pikachu.find_move("quick attack"), Some(1));
+        assert_eq!(pikachu.find_move("0"), None);
+        assert_eq!(pikachu.find_move("4"), None);
+    }
+
+    #[test]
+    fn damage_follows_the_formula_with_stab_and_type_bonus() {
+        let player = battler("squirtle", &["water"], 100);
+        let wild = battler("charmander", &["fire"], 100);
+        let mut battle = Battle::new(player, wild, "Squirtle");
+        // Every roll is 0: the wild side picks Tackle, wins no tie and
+        // deals the lowest damage spread (x0.85).
+        let outcome = battle.play_turn(2, ("Squirtle", "Charmander"), || 0.0);
+        assert_eq!(outcome, Outcome::Ongoing);
+        // Water Gun: (22 * 60 * 105 / 105) / 50 + 2 = 28.4, x1.5 STAB, x2, x0.85.
+        assert_eq!(battle.wild.hp, 160 - 72);
+        // Tackle: (22 * 40) / 50 + 2 = 19.6, x0.85.
+        assert_eq!(battle.player.hp, 160 - 16);
+        assert!(battle.log.contains(&"It's super effective!".to_string()));
+    }
+
+    #[test]
+    fn immune_targets_take_no_damage() {
+        let player = battler("rattata", &["normal"], 100);
+        let wild = battler("gastly", &["ghost"], 10);
+        let mut battle = Battle::new(player, wild, "Rattata");
+        battle.play_turn(0, ("Rattata", "Gastly"), || 0.0);
+        assert_eq!(battle.wild.hp, battle.wild.max_hp);
+        assert!(battle.log.contains(&"It had no effect...".to_string()));
+    }
+
+    #[test]
+    fn priority_beats_speed_and_speed_decides_otherwise() {
+        // The wild side is faster, so it moves first with equal priority and
+        // the player lands the last hit.
+        let mut battle = Battle::new(
+            battler("slowpoke", &["water"], 20),
+            battler("jolteon", &["water"], 120),
+            "Slowpoke",
+        );
+        battle.play_turn(0, ("Slowpoke", "Jolteon"), || 0.0);
+        assert_eq!(battle.last_hit, Some(Side::Wild));
+        // Quick Attack goes first, so the wild side lands the last hit.
+        battle.play_turn(1, ("Slowpoke", "Jolteon"), || 0.0);
+        assert_eq!(battle.last_hit, Some(Side::Player));
+    }
+
+    #[test]
+    fn a_knockout_ends_the_round() {
+        let mut battle = Battle::new(
+            battler("pidgey", &["normal"], 20),
+            battler("rattata", &["normal"], 120),
+            "Pidgey",
+        );
+        battle.player.hp = 1;
+        assert_eq!(
+            battle.play_turn(0, ("Pidgey", "Rattata"), || 0.0),
+            Outcome::Lost
+        );
+        assert_eq!(battle.wild.hp, battle.wild.max_hp);
+        assert_eq!(battle.fainted, ["pidgey"]);
+
+        battle.switch_player(battler("mankey", &["fighting"], 20), "Mankey");
+        battle.wild.hp = 1;
+        assert_eq!(battle.catch_bonus(), 1.0 + 1.5 * (1.0 - 1.0 / 160.0));
+        assert_eq!(
+            battle.play_turn(1, ("Mankey", "Rattata"), || 0.0),
+            Outcome::Won
+        );
+        assert_eq!(battle.wild.hp, 0);
+        assert_eq!(battle.player.hp, battle.player.max_hp);
+        assert_eq!(battle.catch_bonus(), 2.5);
+    }
+}
@@ -0,0 +1,206 @@
+#[path = "../ascii.rs"]
+mod ascii;
+#[allow(dead_code)]
+#[path = "../dex.rs"]
+mod dex;
+#[allow(dead_code)]
+#[path = "../pokemon.rs"]
+mod pokemon;
+
+use std::env;
+use std::fmt::Write;
+use std::panic;
+use std::process;
+
+use ascii::{AsciiImage, ColorMode, IMG_CHARSET, rgb_to_ansi256};
+
+const MIN_COVERAGE: f32 = 0.08;
+const MAX_SOURCE_DIM: u32 = 1024;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    let manifest = pokemon::load_manifest()?;
+
+    match args.get(1).map(|s| s.as_str()) {
+        None | Some("check") => {
+            let problems = check_assets(&manifest)?;
+            if problems.is_empty() {
+                println!("all assets ok");
+                return Ok(());
+            }
+            for problem in &problems {
+                println!("- {problem}");
+            }
+            println!("{} problem(s) found", problems.len());
+            process::exit(1);
+        }
+        Some("preview") => {
+            let Some(name) = args.get(2).map(|s| s.to_lowercase()) else {
+                usage();
+            };
+            let modes = match args.get(3) {
+                Some(raw) => match parse_color_mode(raw) {
+                    Some(mode) => vec![mode],
+                    None => usage(),
+                },
+                None => vec![ColorMode::Truecolor, ColorMode::Ansi256, ColorMode::Mono],
+            };
+            let image = load_quietly(|| pokemon::load_named_pokemon(&manifest, &name, IMG_CHARSET))
+                .map_err(|err| format!("{name}: {err}"))?;
+            for mode in modes {
+                println!("-- {name} ({}) --", color_mode_label(mode));
+                print!("{}", render_image(&image, mode));
+            }
+            Ok(())
+        }
+        Some(_) => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("usage: asset_check [check]");
+    eprintln!("       asset_check preview <pokemon> [truecolor|ansi256|mono]");
+    process::exit(2);
+}
+
+fn check_assets(manifest: &pokemon::AssetManifest) -> Result<Vec<String>, std::io::Error> {
+    let names = dex::load_pokedex_view()?.names;
+    let screen_w = env_usize("POKESTREAM_WIDTH").unwrap_or(140);
+    let screen_h = env_usize("POKESTREAM_HEIGHT").unwrap_or(40);
+    let mut problems = manifest.validate(&names);
+
+    for name in names.iter().filter(|name| !name.is_empty()) {
+        let Some(path) = manifest.resolve_sprite(name) else {
+            continue;
+        };
+        if let Ok((w, h)) = image::image_dimensions(&path)
+            && (w > MAX_SOURCE_DIM || h > MAX_SOURCE_DIM)
+        {
+            problems.push(format!(
+                "{name}: source {path} is {w}x{h}, over the {MAX_SOURCE_DIM}px limit"
+            ));
+        }
+        let image = match load_quietly(|| pokemon::load_named_pokemon(manifest, name, IMG_CHARSET))
+        {
+            Ok(image) => image,
+            Err(err) => {
+                problems.push(format!("{name}: {err}"));
+                continue;
+            }
+        };
+
+        let coverage = coverage(&image);
+        if coverage == 0.0 {
+            problems.push(format!("{name}: conversion is empty (all background)"));
+        } else if coverage < MIN_COVERAGE {
+            problems.push(format!(
+                "{name}: conversion is mostly background ({:.1}% filled)",
+                coverage * 100.0
+            ));
+        }
+
+        // Mirrors SessionState::sprite_origin on the catch screen.
+        let (dx, dy) = manifest.offset(name);
+        let x = ((screen_w / 2) as i32 - 2 + dx).max(0) as usize;
+        let y = (5 + dy).max(0) as usize;
+        if x + image.width > screen_w || y + image.height > screen_h.saturating_sub(1) {
+            problems.push(format!(
+                "{name}: {}x{} sprite at ({x}, {y}) overflows the {screen_w}x{screen_h} catch screen",
+                image.width, image.height
+            ));
+        }
+    }
+
+    for entry in &manifest.welcome {
+        let path = &entry.path;
+        match load_quietly(|| pokemon::load_welcome_animation(entry, IMG_CHARSET)) {
+            Ok(anim) if anim.frames.is_empty() => {
+                problems.push(format!("welcome: {path} has no frames"));
+            }
+            Ok(_) if entry.width > screen_w || entry.height > screen_h => {
+                problems.push(format!(
+                    "welcome: {path} at {}x{} is larger than the {screen_w}x{screen_h} screen",
+                    entry.width, entry.height
+                ));
+            }
+            Ok(_) => {}
+            Err(err) => problems.push(format!("welcome: {err}")),
+        }
+    }
+
+    problems.sort();
+    problems.dedup();
+    Ok(problems)
+}
+
+fn load_quietly<T>(load: impl FnOnce() -> T + panic::UnwindSafe) -> Result<T, String> {
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(load);
+    panic::set_hook(previous);
+    result.map_err(|payload| {
+        payload
+            .downcast_ref::<String>()
+            .cloned()
+            .or_else(|| payload.downcast_ref::<&str>().map(|s| s.to_string()))
+            .unwrap_or_else(|| "failed to load".to_string())
+    })
+}
+
+fn coverage(image: &AsciiImage) -> f32 {
+    if image.chars.is_empty() {
+        return 0.0;
+    }
+    let filled = image.chars.iter().filter(|ch| **ch != ' ').count();
+    filled as f32 / image.chars.len() as f32
+}
+
+fn render_image(image: &AsciiImage, mode: ColorMode) -> String {
+    let mut out = String::with_capacity(image.width * image.height * 4);
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let idx = x + y * image.width;
+            let ch = image.chars[idx];
+            if ch == ' ' {
+                out.push(' ');
+                continue;
+            }
+            let (r, g, b) = image.colors[idx];
+            match mode {
+                ColorMode::Truecolor => {
+                    let _ = write!(out, "\x1b[38;2;{r};{g};{b}m{ch}");
+                }
+                ColorMode::Ansi256 => {
+                    let _ = write!(out, "\x1b[38;5;{}m{ch}", rgb_to_ansi256(r, g, b));
+                }
+                ColorMode::Mono => out.push(ch),
+            }
+        }
+        if mode != ColorMode::Mono {
+            out.push_str("\x1b[0m");
+        }
+        out.push('\n');
+    }
+    out
+}
+
+fn parse_color_mode(raw: &str) -> Option<ColorMode> {
+    match raw.to_lowercase().as_str() {
+        "truecolor" | "24bit" => Some(ColorMode::Truecolor),
+        "ansi256" | "256" => Some(ColorMode::Ansi256),
+        "mono" | "none" => Some(ColorMode::Mono),
+        _ => None,
+    }
+}
+
+fn color_mode_label(mode: ColorMode) -> &'static str {
+    match mode {
+        ColorMode::Truecolor => "truecolor",
+        ColorMode::Ansi256 => "ansi256",
+        ColorMode::Mono => "mono",
+    }
+}
+
+fn env_usize(key: &str) -> Option<usize> {
+    env::var(key).ok().and_then(|val| val.parse::<usize>().ok())
+}
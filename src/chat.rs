@@ -0,0 +1,168 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast;
+
+const HUB_CAPACITY: usize = 256;
+pub const MAX_MESSAGE_CHARS: usize = 120;
+/// At most `RATE_MESSAGES` messages per `RATE_WINDOW` from one trainer.
+const RATE_MESSAGES: usize = 5;
+const RATE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Words masked by `filter`, matched case-insensitively on whole words.
+const BLOCKED_WORDS: &[&str] = &[
+    "arse",
+    "ass",
+    "asshole",
+    "bastard",
+    "bitch",
+    "bollocks",
+    "bullshit",
+    "crap",
+    "cunt",
+    "damn",
+    "dick",
+    "fuck",
+    "fucker",
+    "fucking",
+    "motherfucker",
+    "piss",
+    "prick",
+    "shit",
+    "slut",
+    "twat",
+    "wanker",
+    "whore",
+];
+
+#[derive(Clone, Debug)]
+pub struct ChatMessage {
+    /// `None` for the global channel.
+    pub room: Option<String>,
+    pub from: String,
+    pub text: String,
+}
+
+fn hub() -> &'static broadcast::Sender<ChatMessage> {
+    static HUB: OnceLock<broadcast::Sender<ChatMessage>> = OnceLock::new();
+    HUB.get_or_init(|| broadcast::channel(HUB_CAPACITY).0)
+}
+
+pub fn subscribe() -> broadcast::Receiver<ChatMessage> {
+    hub().subscribe()
+}
+
+pub fn publish(message: ChatMessage) {
+    let _ = hub().send(message);
+}
+
+/// Checks `trainer`'s rate limit. Limits live in the hub rather than the
+/// session so reconnecting doesn't reset them.
+pub fn allow(trainer: &str, now: Instant) -> bool {
+    static LIMITS: OnceLock<Mutex<HashMap<String, RateLimit>>> = OnceLock::new();
+    let Ok(mut limits) = LIMITS.get_or_init(Default::default).lock() else {
+        return true;
+    };
+    limits.retain(|_, limit| !limit.expire(now));
+    limits.entry(trainer.to_string()).or_default().allow(now)
+}
+
+/// Drops control characters, so nobody can send escape sequences to other
+/// terminals, and replaces blocked words with asterisks, keeping the rest of
+/// the text as typed.
+pub fn filter(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+    let flush = |word: &mut String, out: &mut String| {
+        if BLOCKED_WORDS.contains(&word.to_lowercase().as_str()) {
+            out.extend(word.chars().map(|_| '*'));
+        } else {
+            out.push_str(word);
+        }
+        word.clear();
+    };
+    for ch in text.chars().filter(|ch| !ch.is_control()) {
+        if ch.is_alphanumeric() {
+            word.push(ch);
+        } else {
+            flush(&mut word, &mut out);
+            out.push(ch);
+        }
+    }
+    flush(&mut word, &mut out);
+    out
+}
+
+#[derive(Default)]
+pub struct RateLimit {
+    sent: VecDeque<Instant>,
+}
+
+impl RateLimit {
+    /// Drops messages older than the window; true when none are left.
+    fn expire(&mut self, now: Instant) -> bool {
+        while self
+            .sent
+            .front()
+            .is_some_and(|at| now.duration_since(*at) >= RATE_WINDOW)
+        {
+            self.sent.pop_front();
+        }
+        self.sent.is_empty()
+    }
+
+    /// Records a message at `now` if the sender is under the limit.
+    pub fn allow(&mut self, now: Instant) -> bool {
+        self.expire(now);
+        if self.sent.len() >= RATE_MESSAGES {
+            return false;
+        }
+        self.sent.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filter_masks_whole_blocked_words_only() {
+        assert_eq!(filter("Well, DAMN that Onix!"), "Well, **** that Onix!");
+        assert_eq!(filter("class assessment"), "class assessment");
+        assert_eq!(filter("shit-shit"), "****-****");
+    }
+
+    #[test]
+    fn filter_strips_control_characters() {
+        assert_eq!(
+            filter("hi\x1b[2J\x1b]0;pwned\x07 there"),
+            "hi[2J]0;pwned there"
+        );
+        assert_eq!(filter("line\r\nbreak\ttab\u{9b}"), "linebreaktab");
+        assert_eq!(filter("da\x1bmn"), "****");
+        assert_eq!(filter("\x1b\x07"), "");
+    }
+
+    #[test]
+    fn rate_limit_allows_a_burst_then_refills() {
+        let start = Instant::now();
+        let mut limit = RateLimit::default();
+        for _ in 0..RATE_MESSAGES {
+            assert!(limit.allow(start));
+        }
+        assert!(!limit.allow(start + Duration::from_secs(1)));
+        assert!(limit.allow(start + RATE_WINDOW));
+    }
+
+    #[test]
+    fn rate_limit_follows_the_trainer_not_the_session() {
+        let start = Instant::now();
+        for _ in 0..RATE_MESSAGES {
+            assert!(allow("chat-test-brock", start));
+        }
+        assert!(!allow("chat-test-brock", start));
+        assert!(allow("chat-test-erika", start));
+    }
+}
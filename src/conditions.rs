@@ -0,0 +1,224 @@
+use crate::tz::Zone;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TimeOfDay {
+    Morning,
+    Day,
+    Evening,
+    Night,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Weather {
+    Clear,
+    Rain,
+    Sun,
+    Fog,
+    Snow,
+    Sandstorm,
+}
+
+/// Chance of each weather out of 100, in the order rolled.
+const WEATHER_ODDS: [(Weather, u64); 6] = [
+    (Weather::Clear, 40),
+    (Weather::Rain, 20),
+    (Weather::Sun, 15),
+    (Weather::Fog, 10),
+    (Weather::Snow, 8),
+    (Weather::Sandstorm, 7),
+];
+
+/// Encounter weight multipliers per type. A Pokemon takes the strongest
+/// boost (or, failing that, the mildest penalty) across its types.
+const TIME_WEIGHTS: &[(TimeOfDay, &[(&str, f32)])] = &[
+    (
+        TimeOfDay::Morning,
+        &[("normal", 1.3), ("flying", 1.4), ("bug", 1.3)],
+    ),
+    (
+        TimeOfDay::Day,
+        &[("grass", 1.3), ("bug", 1.3), ("ghost", 0.5), ("dark", 0.5)],
+    ),
+    (
+        TimeOfDay::Evening,
+        &[("psychic", 1.4), ("fire", 1.2), ("poison", 1.2)],
+    ),
+    (
+        TimeOfDay::Night,
+        &[
+            ("ghost", 2.5),
+            ("dark", 2.5),
+            ("poison", 1.3),
+            ("grass", 0.7),
+            ("bug", 0.7),
+        ],
+    ),
+];
+
+const WEATHER_WEIGHTS: &[(Weather, &[(&str, f32)])] = &[
+    (
+        Weather::Rain,
+        &[("water", 2.0), ("electric", 1.5), ("fire", 0.5)],
+    ),
+    (
+        Weather::Sun,
+        &[("fire", 2.0), ("grass", 1.5), ("water", 0.7), ("ice", 0.5)],
+    ),
+    (Weather::Fog, &[("ghost", 1.8), ("psychic", 1.5)]),
+    (Weather::Snow, &[("ice", 3.0), ("fire", 0.7)]),
+    (
+        Weather::Sandstorm,
+        &[("rock", 2.0), ("ground", 2.0), ("steel", 1.5)],
+    ),
+];
+
+impl TimeOfDay {
+    /// Morning 05-09, day 10-16, evening 17-19, night 20-04.
+    pub fn from_hour(hour: i64) -> Self {
+        match hour {
+            5..=9 => Self::Morning,
+            10..=16 => Self::Day,
+            17..=19 => Self::Evening,
+            _ => Self::Night,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Morning => "MORNING",
+            Self::Day => "DAY",
+            Self::Evening => "EVENING",
+            Self::Night => "NIGHT",
+        }
+    }
+}
+
+impl Weather {
+    /// The same weather for everyone on a given day.
+    pub fn for_day(day: i64) -> Self {
+        let mut rng = (day as u64) ^ 0x5745_4154_4845_5200;
+        let mut roll = crate::rng::next_u64(&mut rng) % 100;
+        for (weather, chance) in WEATHER_ODDS {
+            if roll < chance {
+                return weather;
+            }
+            roll -= chance;
+        }
+        Weather::Clear
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Clear => "CLEAR",
+            Self::Rain => "RAIN",
+            Self::Sun => "HARSH SUN",
+            Self::Fog => "FOG",
+            Self::Snow => "SNOW",
+            Self::Sandstorm => "SANDSTORM",
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Conditions {
+    pub time: TimeOfDay,
+    pub weather: Weather,
+}
+
+impl Conditions {
+    /// Conditions at `unix` by the local clock and calendar of `zone`.
+    pub fn at(zone: &Zone, unix: i64) -> Self {
+        let local = unix + zone.offset_at(unix) as i64;
+        Self {
+            time: TimeOfDay::from_hour(local.rem_euclid(86_400) / 3600),
+            weather: Weather::for_day(zone.day_index(unix)),
+        }
+    }
+
+    pub fn label(self) -> String {
+        format!("{} - {}", self.time.label(), self.weather.label())
+    }
+
+    /// Encounter weight multiplier for a Pokemon with these types.
+    pub fn type_multiplier(self, types: &[String]) -> f32 {
+        let time = TIME_WEIGHTS
+            .iter()
+            .find(|(time, _)| *time == self.time)
+            .map_or(1.0, |(_, weights)| best(weights, types));
+        let weather = WEATHER_WEIGHTS
+            .iter()
+            .find(|(weather, _)| *weather == self.weather)
+            .map_or(1.0, |(_, weights)| best(weights, types));
+        time * weather
+    }
+}
+
+fn best(weights: &[(&str, f32)], types: &[String]) -> f32 {
+    let matched = types.iter().filter_map(|kind| {
+        weights
+            .iter()
+            .find(|(name, _)| *name == kind.as_str())
+            .map(|(_, weight)| *weight)
+    });
+    let (boost, penalty) = matched.fold((None, None), |(boost, penalty), weight| {
+        if weight >= 1.0 {
+            (Some(weight.max(boost.unwrap_or(1.0))), penalty)
+        } else {
+            (boost, Some(weight.max(penalty.unwrap_or(0.0))))
+        }
+    });
+    boost.or(penalty).unwrap_or(1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn types(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn hours_map_to_times_of_day() {
+        assert_eq!(TimeOfDay::from_hour(4), TimeOfDay::Night);
+        assert_eq!(TimeOfDay::from_hour(5), TimeOfDay::Morning);
+        assert_eq!(TimeOfDay::from_hour(10), TimeOfDay::Day);
+        assert_eq!(TimeOfDay::from_hour(17), TimeOfDay::Evening);
+        assert_eq!(TimeOfDay::from_hour(20), TimeOfDay::Night);
+    }
+
+    #[test]
+    fn conditions_follow_the_local_clock() {
+        let zone = Zone::load("UTC+2").unwrap();
+        // 1970-01-02 19:00 UTC is 21:00 local.
+        let at = Conditions::at(&zone, 86_400 + 19 * 3600);
+        assert_eq!(at.time, TimeOfDay::Night);
+        assert_eq!(at.weather, Weather::for_day(1));
+        // 22:30 UTC is already the next local day.
+        let next = Conditions::at(&zone, 86_400 + 22 * 3600 + 1800);
+        assert_eq!(next.weather, Weather::for_day(2));
+    }
+
+    #[test]
+    fn the_strongest_boost_wins_over_penalties() {
+        let night_clear = Conditions {
+            time: TimeOfDay::Night,
+            weather: Weather::Clear,
+        };
+        assert_eq!(night_clear.type_multiplier(&types(&["ghost"])), 2.5);
+        assert_eq!(
+            night_clear.type_multiplier(&types(&["grass", "poison"])),
+            1.3
+        );
+        assert_eq!(night_clear.type_multiplier(&types(&["grass", "bug"])), 0.7);
+        assert_eq!(night_clear.type_multiplier(&types(&["water"])), 1.0);
+        let rainy_day = Conditions {
+            time: TimeOfDay::Day,
+            weather: Weather::Rain,
+        };
+        assert_eq!(
+            rainy_day.type_multiplier(&types(&["grass", "fire"])),
+            1.3 * 0.5
+        );
+    }
+}
@@ -0,0 +1,418 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+const GEN1_CSV: &str = "sample_images/gen01.csv";
+const SPECIES_TAGS_PATH: &str = "assets/species_tags.json";
+const EVOLUTIONS_PATH: &str = "assets/evolutions.json";
+const SPECIES_INFO_PATH: &str = "assets/species_info.json";
+
+/// Generation CSVs loaded by default, in national-dex order. Kanto is required;
+/// later regions are picked up when their file exists.
+const REGION_FILES: &[(&str, &str)] = &[
+    ("kanto", GEN1_CSV),
+    ("johto", "sample_images/gen02.csv"),
+    ("hoenn", "sample_images/gen03.csv"),
+    ("sinnoh", "sample_images/gen04.csv"),
+    ("unova", "sample_images/gen05.csv"),
+    ("kalos", "sample_images/gen06.csv"),
+    ("alola", "sample_images/gen07.csv"),
+    ("galar", "sample_images/gen08.csv"),
+    ("paldea", "sample_images/gen09.csv"),
+];
+
+const DEFAULT_LEGENDARIES: &[&str] = &["articuno", "zapdos", "moltres", "mewtwo", "mew"];
+const DEFAULT_STARTERS: &[&str] = &["bulbasaur", "charmander", "squirtle", "pikachu", "eevee"];
+
+pub struct Region {
+    pub name: String,
+    /// National dex numbers covered by this region, inclusive.
+    pub first: usize,
+    pub last: usize,
+}
+
+impl Region {
+    pub fn len(&self) -> usize {
+        self.last + 1 - self.first
+    }
+}
+
+/// Base stats from the generation CSVs (`HP`..`Speed` columns).
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BaseStats {
+    pub hp: u16,
+    pub attack: u16,
+    pub defense: u16,
+    pub sp_attack: u16,
+    pub sp_defense: u16,
+    pub speed: u16,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum EvolutionTrigger {
+    Level(u16),
+    Trade,
+}
+
+/// Field notes from `assets/species_info.json` for the Pokedex detail page.
+#[derive(Clone, Debug, Default)]
+pub struct SpeciesInfo {
+    /// Category, e.g. "Seed Pokemon".
+    pub genus: Option<String>,
+    pub height_m: Option<f32>,
+    pub weight_kg: Option<f32>,
+    pub flavor: Option<String>,
+}
+
+/// One evolution step from `assets/evolutions.json`.
+#[derive(Clone, Debug)]
+pub struct Evolution {
+    pub into: String,
+    pub trigger: EvolutionTrigger,
+}
+
+pub struct PokedexView {
+    /// Species names indexed by national dex number - 1; gaps are empty strings.
+    pub names: Vec<String>,
+    pub totals_by_name: HashMap<String, u16>,
+    /// Lowercase type names, primary type first.
+    pub types_by_name: HashMap<String, Vec<String>>,
+    pub stats_by_name: HashMap<String, BaseStats>,
+    pub regions: Vec<Region>,
+    pub legendaries: HashSet<String>,
+    pub starters: HashSet<String>,
+    pub evolutions: HashMap<String, Evolution>,
+    pub info: HashMap<String, SpeciesInfo>,
+}
+
+impl PokedexView {
+    pub fn is_legendary(&self, name: &str) -> bool {
+        self.legendaries.contains(name)
+    }
+
+    pub fn is_starter(&self, name: &str) -> bool {
+        self.starters.contains(name)
+    }
+
+    pub fn find_region(&self, name: &str) -> Option<usize> {
+        let name = name.trim().to_lowercase();
+        self.regions.iter().position(|region| region.name == name)
+    }
+
+    /// Species a Pokemon evolves into once it reaches `level`, if any.
+    pub fn level_evolution(&self, name: &str, level: u16) -> Option<&str> {
+        let evolution = self.evolutions.get(name)?;
+        match evolution.trigger {
+            EvolutionTrigger::Level(at) if level >= at => Some(evolution.into.as_str()),
+            _ => None,
+        }
+    }
+
+    /// The whole evolution line `name` belongs to, from its first stage,
+    /// with the trigger that leads into each later stage.
+    pub fn evolution_chain<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> Vec<(&'a str, Option<EvolutionTrigger>)> {
+        let mut first = name;
+        for _ in 0..self.evolutions.len() {
+            match self
+                .evolutions
+                .iter()
+                .find(|(_, evolution)| evolution.into == first)
+            {
+                Some((from, _)) if from != name => first = from,
+                _ => break,
+            }
+        }
+        let mut chain = vec![(first, None)];
+        let mut current = first;
+        while let Some(evolution) = self.evolutions.get(current) {
+            if chain.iter().any(|(stage, _)| *stage == evolution.into) {
+                break;
+            }
+            current = evolution.into.as_str();
+            chain.push((current, Some(evolution.trigger)));
+        }
+        chain
+    }
+
+    /// Species a Pokemon evolves into when traded, if any.
+    pub fn trade_evolution(&self, name: &str) -> Option<&str> {
+        let evolution = self.evolutions.get(name)?;
+        (evolution.trigger == EvolutionTrigger::Trade).then_some(evolution.into.as_str())
+    }
+}
+
+struct CsvEntry {
+    id: usize,
+    name: String,
+    total: u16,
+    types: Vec<String>,
+    stats: Option<BaseStats>,
+}
+
+pub fn load_pokedex_view() -> io::Result<PokedexView> {
+    let mut names: Vec<String> = Vec::new();
+    let mut totals_by_name = HashMap::new();
+    let mut types_by_name = HashMap::new();
+    let mut stats_by_name = HashMap::new();
+    let mut regions = Vec::new();
+
+    for (i, (region, path)) in region_sources().into_iter().enumerate() {
+        if i > 0 && !Path::new(&path).exists() {
+            continue;
+        }
+        let entries = load_generation_csv(&path)?;
+        let (Some(first), Some(last)) = (
+            entries.iter().map(|e| e.id).min(),
+            entries.iter().map(|e| e.id).max(),
+        ) else {
+            continue;
+        };
+        if names.len() < last {
+            names.resize(last, String::new());
+        }
+        for entry in entries {
+            names[entry.id - 1] = entry.name.clone();
+            if let Some(stats) = entry.stats {
+                stats_by_name.insert(entry.name.clone(), stats);
+            }
+            if !entry.types.is_empty() {
+                types_by_name.insert(entry.name.clone(), entry.types);
+            }
+            if entry.total > 0 {
+                totals_by_name.insert(entry.name, entry.total);
+            }
+        }
+        regions.push(Region {
+            name: region,
+            first,
+            last,
+        });
+    }
+
+    let (legendaries, starters) = load_species_tags()?;
+    let known: HashSet<&str> = names.iter().map(|s| s.as_str()).collect();
+    let mut evolutions = load_evolutions()?;
+    evolutions.retain(|from, evolution| {
+        known.contains(from.as_str()) && known.contains(evolution.into.as_str())
+    });
+    let info = load_species_info()?;
+    Ok(PokedexView {
+        names,
+        totals_by_name,
+        types_by_name,
+        stats_by_name,
+        regions,
+        legendaries,
+        starters,
+        evolutions,
+        info,
+    })
+}
+
+/// `POKESTREAM_DEX=kanto:path.csv,johto:other.csv` replaces the default region list.
+fn region_sources() -> Vec<(String, String)> {
+    if let Ok(raw) = env::var("POKESTREAM_DEX") {
+        let sources: Vec<(String, String)> = raw
+            .split(',')
+            .filter_map(|item| item.split_once(':'))
+            .map(|(region, path)| (region.trim().to_lowercase(), path.trim().to_string()))
+            .filter(|(region, path)| !region.is_empty() && !path.is_empty())
+            .collect();
+        if !sources.is_empty() {
+            return sources;
+        }
+    }
+    REGION_FILES
+        .iter()
+        .map(|(region, path)| (region.to_string(), path.to_string()))
+        .collect()
+}
+
+fn load_generation_csv(path: &str) -> io::Result<Vec<CsvEntry>> {
+    let data = fs::read_to_string(path)?;
+    let mut entries = Vec::new();
+    for (i, line) in data.lines().enumerate() {
+        if i == 0 {
+            continue;
+        }
+        let fields = parse_csv_line(line);
+        if fields.len() < 6 {
+            continue;
+        }
+        let id: usize = match fields[0].trim().parse() {
+            Ok(v) => v,
+            Err(_) => continue,
+        };
+        if id == 0 {
+            continue;
+        }
+        let name = fields[1].trim();
+        let form = fields[2].trim();
+        let total: u16 = fields[5].trim().parse().unwrap_or(0);
+        let types = fields[3..5]
+            .iter()
+            .map(|t| t.trim().to_lowercase())
+            .filter(|t| !t.is_empty())
+            .collect();
+        entries.push(CsvEntry {
+            id,
+            name: normalize_pokemon_name(name, form),
+            total,
+            types,
+            stats: parse_base_stats(&fields),
+        });
+    }
+    Ok(entries)
+}
+
+fn parse_base_stats(fields: &[String]) -> Option<BaseStats> {
+    let stat = |i: usize| fields.get(i)?.trim().parse::<u16>().ok();
+    Some(BaseStats {
+        hp: stat(6)?,
+        attack: stat(7)?,
+        defense: stat(8)?,
+        sp_attack: stat(9)?,
+        sp_defense: stat(10)?,
+        speed: stat(11)?,
+    })
+}
+
+fn load_species_tags() -> io::Result<(HashSet<String>, HashSet<String>)> {
+    let defaults = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<HashSet<_>>();
+    if !Path::new(SPECIES_TAGS_PATH).exists() {
+        return Ok((defaults(DEFAULT_LEGENDARIES), defaults(DEFAULT_STARTERS)));
+    }
+    let text = fs::read_to_string(SPECIES_TAGS_PATH)?;
+    let root: Value = serde_json::from_str(&text).map_err(io::Error::other)?;
+    let read = |key: &str| -> HashSet<String> {
+        root.get(key)
+            .and_then(|v| v.as_array())
+            .map(|list| {
+                list.iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|s| s.trim().to_lowercase())
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+    Ok((read("legendary"), read("starter")))
+}
+
+fn load_evolutions() -> io::Result<HashMap<String, Evolution>> {
+    if !Path::new(EVOLUTIONS_PATH).exists() {
+        return Ok(HashMap::new());
+    }
+    let text = fs::read_to_string(EVOLUTIONS_PATH)?;
+    let root: Value = serde_json::from_str(&text).map_err(io::Error::other)?;
+    let mut evolutions = HashMap::new();
+    for entry in root
+        .get("evolutions")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let read = |key: &str| {
+            entry
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_lowercase())
+        };
+        let (Some(from), Some(into)) = (read("from"), read("to")) else {
+            continue;
+        };
+        let trigger = if let Some(level) = entry.get("level").and_then(|v| v.as_u64()) {
+            EvolutionTrigger::Level(level as u16)
+        } else if entry.get("trade").and_then(|v| v.as_bool()) == Some(true) {
+            EvolutionTrigger::Trade
+        } else {
+            continue;
+        };
+        evolutions.insert(from, Evolution { into, trigger });
+    }
+    Ok(evolutions)
+}
+
+fn load_species_info() -> io::Result<HashMap<String, SpeciesInfo>> {
+    if !Path::new(SPECIES_INFO_PATH).exists() {
+        return Ok(HashMap::new());
+    }
+    let text = fs::read_to_string(SPECIES_INFO_PATH)?;
+    let root: Value = serde_json::from_str(&text).map_err(io::Error::other)?;
+    let mut info = HashMap::new();
+    for entry in root
+        .get("species")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let read = |key: &str| {
+            entry
+                .get(key)
+                .and_then(|v| v.as_str())
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty())
+        };
+        let measure = |key: &str| entry.get(key).and_then(|v| v.as_f64()).map(|v| v as f32);
+        let Some(name) = read("name") else {
+            continue;
+        };
+        info.insert(
+            name.to_lowercase(),
+            SpeciesInfo {
+                genus: read("genus"),
+                height_m: measure("height_m"),
+                weight_kg: measure("weight_kg"),
+                flavor: read("flavor"),
+            },
+        );
+    }
+    Ok(info)
+}
+
+fn parse_csv_line(line: &str) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut buf = String::new();
+    let mut in_quotes = false;
+    for ch in line.chars() {
+        match ch {
+            '"' => {
+                in_quotes = !in_quotes;
+            }
+            ',' if !in_quotes => {
+                out.push(buf.clone());
+                buf.clear();
+            }
+            _ => buf.push(ch),
+        }
+    }
+    out.push(buf);
+    out
+}
+
+fn normalize_pokemon_name(name: &str, form: &str) -> String {
+    let mut base = name.trim().to_lowercase();
+    base = base.replace('.', "");
+    base = base.replace('\'', "");
+    base = base.replace(' ', "-");
+    let form = form.trim();
+    if !form.is_empty() && form != " " {
+        let form = form.to_lowercase().replace(' ', "-");
+        if base == "nidoran" {
+            if form == "female" {
+                return "nidoran-f".to_string();
+            }
+            if form == "male" {
+                return "nidoran-m".to_string();
+            }
+        }
+        return format!("{}-{}", base, form);
+    }
+    base
+}
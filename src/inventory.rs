@@ -0,0 +1,219 @@
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BallKind {
+    Poke,
+    Great,
+    Ultra,
+    Master,
+}
+
+pub const BALL_KINDS: [BallKind; 4] = [
+    BallKind::Poke,
+    BallKind::Great,
+    BallKind::Ultra,
+    BallKind::Master,
+];
+
+/// Balls topped up at each daily rollover. Master Balls only restock weekly.
+const DAILY_ALLOWANCE: [u32; 4] = [20, 5, 2, 0];
+const MASTER_RESTOCK_DAYS: i64 = 7;
+
+impl BallKind {
+    pub fn parse(raw: &str) -> Option<Self> {
+        let raw = raw.trim();
+        let raw = raw.strip_suffix("ball").unwrap_or(raw).trim();
+        match raw {
+            "" | "poke" | "pokeball" => Some(Self::Poke),
+            "great" => Some(Self::Great),
+            "ultra" => Some(Self::Ultra),
+            "master" => Some(Self::Master),
+            _ => None,
+        }
+    }
+
+    pub fn index(self) -> usize {
+        match self {
+            Self::Poke => 0,
+            Self::Great => 1,
+            Self::Ultra => 2,
+            Self::Master => 3,
+        }
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Poke => "poke",
+            Self::Great => "great",
+            Self::Ultra => "ultra",
+            Self::Master => "master",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Poke => "Poke Ball",
+            Self::Great => "Great Ball",
+            Self::Ultra => "Ultra Ball",
+            Self::Master => "Master Ball",
+        }
+    }
+
+    /// Multiplier applied to the species' capture chance; `None` never fails.
+    pub fn modifier(self) -> Option<f32> {
+        match self {
+            Self::Poke => Some(1.0),
+            Self::Great => Some(1.5),
+            Self::Ultra => Some(2.0),
+            Self::Master => None,
+        }
+    }
+
+    /// ANSI color for the upper shell at a point on the unit sphere (`oy < 0`).
+    pub fn shell_color(self, ox: f32, oy: f32) -> &'static str {
+        match self {
+            Self::Poke => "\x1b[91m",
+            Self::Great => {
+                if ox.abs() > 0.55 && oy < -0.25 {
+                    "\x1b[91m"
+                } else {
+                    "\x1b[94m"
+                }
+            }
+            Self::Ultra => {
+                if ox.abs() < 0.22 || oy < -0.8 {
+                    "\x1b[90m"
+                } else {
+                    "\x1b[93m"
+                }
+            }
+            Self::Master => {
+                if oy < -0.35 && oy > -0.7 && (ox.abs() - 0.45).abs() < 0.12 {
+                    "\x1b[91m"
+                } else {
+                    "\x1b[95m"
+                }
+            }
+        }
+    }
+}
+
+pub struct Inventory {
+    pub counts: [u32; 4],
+    pub restock_day: i64,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Self {
+            counts: [0; 4],
+            restock_day: i64::MIN,
+        }
+    }
+}
+
+impl Inventory {
+    pub fn count(&self, ball: BallKind) -> u32 {
+        self.counts[ball.index()]
+    }
+
+    pub fn take(&mut self, ball: BallKind) -> bool {
+        let count = &mut self.counts[ball.index()];
+        if *count == 0 {
+            return false;
+        }
+        *count -= 1;
+        true
+    }
+
+    /// Tops every ball up to the daily allowance; balls above it are kept.
+    /// Returns true when a new day started and the inventory needs saving;
+    /// earlier days (after a time zone change) don't restock again.
+    pub fn restock(&mut self, day: i64) -> bool {
+        if day <= self.restock_day {
+            return false;
+        }
+        let master_due = self.restock_day == i64::MIN
+            || day.div_euclid(MASTER_RESTOCK_DAYS)
+                != self.restock_day.div_euclid(MASTER_RESTOCK_DAYS);
+        for ball in BALL_KINDS {
+            let mut allowance = DAILY_ALLOWANCE[ball.index()];
+            if ball == BallKind::Master && master_due {
+                allowance = 1;
+            }
+            let count = &mut self.counts[ball.index()];
+            *count = (*count).max(allowance);
+        }
+        self.restock_day = day;
+        true
+    }
+
+    pub fn summary(&self) -> String {
+        BALL_KINDS
+            .iter()
+            .map(|ball| format!("{} x{}", ball.key().to_uppercase(), self.count(*ball)))
+            .collect::<Vec<_>>()
+            .join("  ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn balls_parse_with_or_without_the_suffix() {
+        assert_eq!(BallKind::parse(""), Some(BallKind::Poke));
+        assert_eq!(BallKind::parse("pokeball"), Some(BallKind::Poke));
+        assert_eq!(BallKind::parse("great ball"), Some(BallKind::Great));
+        assert_eq!(BallKind::parse(" ultraball "), Some(BallKind::Ultra));
+        assert_eq!(BallKind::parse("master"), Some(BallKind::Master));
+        assert_eq!(BallKind::parse("premier"), None);
+        for ball in BALL_KINDS {
+            assert_eq!(BallKind::parse(ball.key()), Some(ball));
+            assert_eq!(BALL_KINDS[ball.index()], ball);
+        }
+    }
+
+    #[test]
+    fn first_restock_fills_the_allowance_and_a_master_ball() {
+        let mut inventory = Inventory::default();
+        assert!(inventory.restock(100));
+        assert_eq!(inventory.counts, [20, 5, 2, 1]);
+        assert_eq!(
+            inventory.summary(),
+            "POKE x20  GREAT x5  ULTRA x2  MASTER x1"
+        );
+    }
+
+    #[test]
+    fn restocks_top_up_without_taking_extras_away() {
+        let mut inventory = Inventory::default();
+        inventory.restock(100);
+        inventory.counts = [3, 9, 0, 0];
+        assert!(inventory.restock(101));
+        assert_eq!(&inventory.counts[..3], &[20, 9, 2]);
+    }
+
+    #[test]
+    fn master_balls_come_back_once_a_week() {
+        // Day 98 starts a week (98 = 14 * 7).
+        let mut inventory = Inventory::default();
+        inventory.restock(98);
+        assert!(inventory.take(BallKind::Master));
+        assert!(!inventory.take(BallKind::Master));
+        inventory.restock(104);
+        assert_eq!(inventory.count(BallKind::Master), 0);
+        inventory.restock(105);
+        assert_eq!(inventory.count(BallKind::Master), 1);
+    }
+
+    #[test]
+    fn the_same_or_an_earlier_day_does_not_restock() {
+        let mut inventory = Inventory::default();
+        inventory.restock(100);
+        inventory.take(BallKind::Poke);
+        assert!(!inventory.restock(100));
+        assert!(!inventory.restock(99));
+        assert_eq!(inventory.count(BallKind::Poke), 19);
+        assert_eq!(inventory.restock_day, 100);
+    }
+}
@@ -0,0 +1,98 @@
+/// One trainer's row in the `leaderboard` table.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub trainer: String,
+    pub caught: usize,
+    pub shinies: usize,
+    pub legendaries: usize,
+    /// Unix time the trainer first filled every dex entry.
+    pub completed_at: Option<i64>,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum SortKey {
+    Dex,
+    Shinies,
+    Legendaries,
+}
+
+impl SortKey {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim() {
+            "dex" | "caught" | "completion" => Some(Self::Dex),
+            "shiny" | "shinies" => Some(Self::Shinies),
+            "legendary" | "legendaries" => Some(Self::Legendaries),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Dex => "DEX",
+            Self::Shinies => "SHINIES",
+            Self::Legendaries => "LEGENDARIES",
+        }
+    }
+}
+
+/// Orders by the chosen column, then dex completion, with whoever completed
+/// the dex first ahead on ties.
+pub fn rank(entries: &mut [Entry], key: SortKey) {
+    entries.sort_by(|a, b| {
+        let primary = match key {
+            SortKey::Dex => b.caught.cmp(&a.caught),
+            SortKey::Shinies => b.shinies.cmp(&a.shinies),
+            SortKey::Legendaries => b.legendaries.cmp(&a.legendaries),
+        };
+        primary
+            .then(b.caught.cmp(&a.caught))
+            .then(
+                a.completed_at
+                    .unwrap_or(i64::MAX)
+                    .cmp(&b.completed_at.unwrap_or(i64::MAX)),
+            )
+            .then(a.trainer.cmp(&b.trainer))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(trainer: &str, caught: usize, shinies: usize, completed_at: Option<i64>) -> Entry {
+        Entry {
+            trainer: trainer.to_string(),
+            caught,
+            shinies,
+            legendaries: 0,
+            completed_at,
+        }
+    }
+
+    fn order(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|e| e.trainer.as_str()).collect()
+    }
+
+    #[test]
+    fn earlier_completion_wins_ties() {
+        let mut entries = vec![
+            entry("brock", 151, 0, Some(300)),
+            entry("misty", 151, 0, None),
+            entry("ash", 151, 0, Some(100)),
+            entry("gary", 150, 0, None),
+        ];
+        rank(&mut entries, SortKey::Dex);
+        assert_eq!(order(&entries), ["ash", "brock", "misty", "gary"]);
+    }
+
+    #[test]
+    fn other_keys_fall_back_to_dex_count() {
+        let mut entries = vec![
+            entry("ash", 10, 1, None),
+            entry("misty", 20, 1, None),
+            entry("brock", 5, 3, None),
+        ];
+        rank(&mut entries, SortKey::Shinies);
+        assert_eq!(order(&entries), ["brock", "misty", "ash"]);
+    }
+}
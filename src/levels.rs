@@ -0,0 +1,58 @@
+use crate::inventory::BallKind;
+
+pub const MAX_LEVEL: u16 = 100;
+
+/// Level given to species that were caught before levels were tracked.
+pub const LEGACY_LEVEL: u16 = 5;
+
+/// One caught Pokemon. A trainer can own several of the same species.
+#[derive(Clone, Debug)]
+pub struct Capture {
+    pub id: i64,
+    pub species: String,
+    pub level: u16,
+    pub xp: u32,
+    /// Unix time it was caught; 0 for legacy captures.
+    pub caught_at: i64,
+    pub shiny: bool,
+    pub nickname: Option<String>,
+    /// Ball it was caught in; `None` for legacy captures.
+    pub ball: Option<BallKind>,
+    /// Login (row in `play_sessions`) that made the catch; `None` for legacy
+    /// captures.
+    pub session_id: Option<i64>,
+}
+
+impl Capture {
+    /// Adds experience and returns the number of levels gained.
+    pub fn gain_xp(&mut self, amount: u32) -> u16 {
+        let before = self.level;
+        self.xp = self.xp.saturating_add(amount);
+        while self.level < MAX_LEVEL && self.xp >= xp_for_level(self.level + 1) {
+            self.level += 1;
+        }
+        self.level - before
+    }
+}
+
+/// Total experience needed to reach `level` (the medium-fast curve).
+pub fn xp_for_level(level: u16) -> u32 {
+    let level = level.min(MAX_LEVEL) as u32;
+    level * level * level
+}
+
+/// Wild levels scale with the species' base stat total, with a little spread
+/// from `roll` so the same species is not always the same level.
+pub fn wild_level(total: Option<u16>, roll: u64) -> u16 {
+    let total = total.unwrap_or(300) as i32;
+    let base = (total - 180) / 9 + 3;
+    let spread = (roll % 5) as i32 - 2;
+    (base + spread).clamp(2, 70) as u16
+}
+
+/// Experience for beating or catching a wild Pokemon. A quarter of the base
+/// stat total stands in for the species' base experience yield.
+pub fn xp_reward(total: Option<u16>, level: u16) -> u32 {
+    let base_exp = (total.unwrap_or(300) / 4) as u32;
+    (base_exp * level as u32 / 7).max(1)
+}
@@ -1,14 +1,29 @@
+mod achievements;
 mod ascii;
+mod battle;
+mod chat;
+mod conditions;
+mod dex;
+mod inventory;
+mod leaderboard;
+mod levels;
+mod pc;
 mod pokemon;
+mod quests;
+mod rng;
+mod routes;
+mod sessions;
+mod trade;
+mod tz;
 
 use std::collections::{HashMap, HashSet};
 use std::env;
 use std::fmt::Write;
-use std::fs;
 use std::sync::{Arc, Mutex, OnceLock};
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
 use rusqlite::{Connection, OptionalExtension};
+use server::store;
 use tokio::io::{self, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::net::tcp::OwnedWriteHalf;
 use tokio::net::{TcpListener, TcpStream};
@@ -16,6 +31,20 @@ use tokio::sync::mpsc;
 use tokio::task;
 use tokio::time::{self, Duration, MissedTickBehavior};
 
+use ascii::{ColorMode, IMG_CHARSET, rgb_to_ansi256};
+use battle::{Battle, Battler, Outcome, Side};
+use chat::ChatMessage;
+use conditions::{Conditions, TimeOfDay, Weather};
+use dex::PokedexView;
+use inventory::{BallKind, Inventory};
+use leaderboard::SortKey;
+use levels::Capture;
+use pc::{BoxFilter, BoxSort};
+use rng::next_u64;
+use sessions::{SessionEvent, TradeOffer};
+use store::{TrainerStore, db_path};
+use trade::{Stage, Trade};
+
 #[derive(PartialEq)]
 enum GameState {
     Idle,
@@ -25,6 +54,8 @@ enum GameState {
     Closing,
     Shaking,
     StarHold,
+    Breakout,
+    Evolving,
 }
 
 #[derive(Copy, Clone)]
@@ -36,8 +67,12 @@ enum CellColor {
 
 struct Assets {
     pokemons: Vec<PokemonAsset>,
-    arcanine_frames: Vec<ascii::AsciiImage>,
+    welcome: Vec<pokemon::WelcomeAnimation>,
     pokedex: PokedexView,
+    /// Server-wide zone for the daily rollover (`POKESTREAM_TZ`).
+    zone: tz::Zone,
+    /// Never empty; the first route is where trainers start.
+    routes: Vec<routes::Route>,
 }
 
 enum Screen {
@@ -45,6 +80,14 @@ enum Screen {
     Pokedex,
     PokedexDetail,
     Game,
+    Battle,
+    Trade,
+    Lobby,
+    Spectate,
+    Leaderboard,
+    Badges,
+    Map,
+    Box,
 }
 
 #[derive(Copy, Clone, PartialEq)]
@@ -53,13 +96,6 @@ enum SelectionMode {
     RandomPerSession,
 }
 
-#[derive(Copy, Clone, PartialEq)]
-enum ColorMode {
-    Truecolor,
-    Ansi256,
-    Mono,
-}
-
 struct RenderBuffers {
     output: Vec<char>,
     zbuffer: Vec<f32>,
@@ -83,14 +119,28 @@ impl RenderBuffers {
     }
 }
 
+/// A copy of a session's composed cells, re-composed by each spectator in
+/// their own color mode and size.
+struct MirrorFrame {
+    width: usize,
+    height: usize,
+    output: Vec<char>,
+    color_buf: Vec<CellColor>,
+}
+
 struct PokemonAsset {
     name: String,
     image: ascii::AsciiImage,
+    shiny: ascii::AsciiImage,
+    offset: (i32, i32),
 }
 
-struct PokedexView {
-    names: Vec<String>,
-    totals_by_name: HashMap<String, u16>,
+struct EvolutionScene {
+    from: String,
+    into: String,
+    old_particles: Vec<StreamParticle>,
+    new_particles: Vec<StreamParticle>,
+    frame: u16,
 }
 
 struct StreamParticle {
@@ -119,16 +169,34 @@ fn pokemon_cache() -> &'static Mutex<HashMap<String, String>> {
     CACHE.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
-const IMG_CHARSET: &str =
-    ".'`^\",:;Il!i><~+_-?][}{1)(|\\/*tfjrxnuvczXYUJCLQ0OZmwqpdbkhao*#MW&8%B@$Ñ";
+static TRAINER_STORE: OnceLock<Box<dyn TrainerStore>> = OnceLock::new();
+
+/// Backend for trainer records, chosen by `POKESTREAM_STORE` in `main`.
+fn trainer_store() -> &'static dyn TrainerStore {
+    TRAINER_STORE
+        .get()
+        .expect("trainer store is set up before sessions start")
+        .as_ref()
+}
 
-const DB_PATH: &str = "pokedex.db";
 const OPEN_FRAMES: u16 = 10;
 const ABSORB_FRAMES: u16 = 22;
 const CLOSE_FRAMES: u16 = 10;
 const SHAKE_FRAMES: u16 = 28;
 const SHAKE_COUNT: u8 = 3;
 const STAR_FRAMES: u16 = 26;
+const BREAKOUT_FRAMES: u16 = OPEN_FRAMES + ABSORB_FRAMES + 12;
+const MESSAGE_FRAMES: u16 = 45;
+const TOAST_FRAMES: u16 = 120;
+const CHAT_LINES: usize = 6;
+const CHAT_WIDTH: usize = 50;
+/// Chat pane stays up this long after the last message.
+const CHAT_FRAMES: u16 = 450;
+/// Minimum time between a trainer's time zone changes.
+const TZ_CHANGE_SECS: i64 = 86_400;
+/// Old sprite streams into a point, then the new one streams back out of it.
+const EVOLVE_STREAM_FRAMES: u16 = ABSORB_FRAMES + 12;
+const EVOLVE_FRAMES: u16 = EVOLVE_STREAM_FRAMES * 2 + 30;
 
 enum CommandAction {
     None,
@@ -150,8 +218,72 @@ struct SessionState {
     selection_mode: SelectionMode,
     trainer_name: Option<String>,
     pokedex: HashSet<String>,
+    shinies: HashSet<String>,
+    /// Species that have appeared to this trainer, with when they were first
+    /// seen. Caught species may be missing from trainers older than this.
+    seen: HashMap<String, i64>,
+    shiny: bool,
+    shiny_odds: u64,
+    sparkle_frame: u32,
+    battle: Option<Battle>,
+    battle_flash: u16,
+    battle_partner: Option<i64>,
+    captures: Vec<Capture>,
+    wild_level: u16,
+    pending_evolutions: Vec<(String, String)>,
+    evolution: Option<EvolutionScene>,
+    session_id: u64,
+    /// Row in `play_sessions` for the current login; new captures record it.
+    play_session: Option<i64>,
+    events: mpsc::UnboundedSender<SessionEvent>,
+    trade: Option<Trade>,
+    /// Trainers waiting on an answer to their trade request.
+    trade_requests: Vec<String>,
+    /// Activity last published to the session registry.
+    presence: &'static str,
+    toast: Option<String>,
+    toast_timer: u16,
+    /// `None` is the global channel.
+    chat_room: Option<String>,
+    chat_lines: Vec<(String, &'static str)>,
+    chat_timer: u16,
+    muted: HashSet<String>,
+    mirror: Arc<sessions::Mirror>,
+    allow_spectators: bool,
+    spectating: Option<(String, sessions::MirrorFeed)>,
+    mirror_frame: Option<Arc<MirrorFrame>>,
+    leaderboard: Vec<leaderboard::Entry>,
+    leaderboard_sort: SortKey,
+    leaderboard_notice: Option<String>,
+    friends: HashSet<String>,
+    friends_only: bool,
+    /// Unlocked badge ids with the Unix time they were earned.
+    badges: HashMap<String, i64>,
+    badges_page: usize,
+    login_day: i64,
+    login_streak: u32,
+    /// Zone the trainer's day rolls over in; the server's unless they set one.
+    zone: tz::Zone,
+    custom_zone: bool,
+    /// Index into `Assets::routes` of where the trainer is looking for Pokemon.
+    route: usize,
+    map_notice: Option<String>,
+    box_sort: BoxSort,
+    box_filter: BoxFilter,
+    box_page: usize,
+    box_notice: Option<String>,
+    /// Capture waiting on `confirm` before it is released.
+    box_release: Option<i64>,
+    quests: Vec<quests::Quest>,
+    /// Extra daily encounters earned from today's quests.
+    bonus_encounters: usize,
+    inventory: Inventory,
+    ball: BallKind,
     screen: Screen,
     pokedex_detail: Option<usize>,
+    pokedex_detail_shiny: bool,
+    pokedex_region: usize,
+    pokedex_page: usize,
     state: GameState,
     frame_count: u32,
     capture_recorded: bool,
@@ -165,6 +297,9 @@ struct SessionState {
     star_hold: u16,
     caught_message: Option<String>,
     caught_message_timer: u16,
+    caught_message_color: &'static str,
+    shake_chance: f32,
+    rng: u64,
     pokedex_notice: Option<String>,
     pokedex_notice_timer: u16,
     agent_lines: Vec<String>,
@@ -175,12 +310,15 @@ struct SessionState {
     ball_scale: f32,
     a: f32,
     tilt_phase: f32,
+    welcome_index: usize,
     welcome_frame: usize,
     welcome_accum: u64,
     welcome_frame_ms: u64,
     last_cmd: String,
     color_mode: ColorMode,
     daily_key: i64,
+    encounters_per_day: usize,
+    encounter_slot: usize,
 }
 
 impl SessionState {
@@ -189,12 +327,21 @@ impl SessionState {
         height: usize,
         color_mode: ColorMode,
         selection_mode: SelectionMode,
+        events: mpsc::UnboundedSender<SessionEvent>,
         assets: &Assets,
     ) -> Self {
         let pokemon_index = match selection_mode {
-            SelectionMode::RandomPerSession => pick_pokemon_index(&assets.pokemons),
+            SelectionMode::RandomPerSession => {
+                pick_pokemon_index(&assets.pokemons, &assets.routes[0])
+            }
             SelectionMode::DailyWeighted => 0,
         };
+        let welcome_index = pick_welcome_index(&assets.welcome);
+        let welcome_frame_ms = assets
+            .welcome
+            .get(welcome_index)
+            .map(|anim| anim.frame_ms)
+            .unwrap_or(1000 / 12);
         Self {
             width,
             height,
@@ -204,8 +351,61 @@ impl SessionState {
             selection_mode,
             trainer_name: None,
             pokedex: HashSet::new(),
+            shinies: HashSet::new(),
+            seen: HashMap::new(),
+            shiny: false,
+            shiny_odds: shiny_odds_from_env(),
+            sparkle_frame: 0,
+            battle: None,
+            battle_flash: 0,
+            battle_partner: None,
+            captures: Vec::new(),
+            wild_level: levels::LEGACY_LEVEL,
+            pending_evolutions: Vec::new(),
+            evolution: None,
+            session_id: sessions::next_session_id(),
+            play_session: None,
+            events,
+            trade: None,
+            trade_requests: Vec::new(),
+            presence: "name",
+            toast: None,
+            toast_timer: 0,
+            chat_room: None,
+            chat_lines: Vec::new(),
+            chat_timer: 0,
+            muted: HashSet::new(),
+            mirror: Arc::new(tokio::sync::watch::channel(None).0),
+            allow_spectators: true,
+            spectating: None,
+            mirror_frame: None,
+            leaderboard: Vec::new(),
+            leaderboard_sort: SortKey::Dex,
+            leaderboard_notice: None,
+            friends: HashSet::new(),
+            friends_only: false,
+            badges: HashMap::new(),
+            badges_page: 0,
+            login_day: 0,
+            login_streak: 0,
+            zone: assets.zone.clone(),
+            custom_zone: false,
+            route: 0,
+            map_notice: None,
+            box_sort: BoxSort::Date,
+            box_filter: BoxFilter::All,
+            box_page: 0,
+            box_notice: None,
+            box_release: None,
+            quests: Vec::new(),
+            bonus_encounters: 0,
+            inventory: Inventory::default(),
+            ball: BallKind::Poke,
             screen: Screen::Name,
             pokedex_detail: None,
+            pokedex_detail_shiny: false,
+            pokedex_region: 0,
+            pokedex_page: 0,
             state: GameState::Idle,
             frame_count: 0,
             capture_recorded: false,
@@ -219,6 +419,9 @@ impl SessionState {
             star_hold: 0,
             caught_message: None,
             caught_message_timer: 0,
+            caught_message_color: "\x1b[92m",
+            shake_chance: 1.0,
+            rng: session_rng_seed(),
             pokedex_notice: None,
             pokedex_notice_timer: 0,
             agent_lines: Vec::new(),
@@ -229,12 +432,15 @@ impl SessionState {
             ball_scale: 1.0,
             a: 0.0,
             tilt_phase: 0.0,
+            welcome_index,
             welcome_frame: 0,
             welcome_accum: 0,
-            welcome_frame_ms: 1000 / 12,
+            welcome_frame_ms,
             last_cmd: String::new(),
             color_mode,
             daily_key: -1,
+            encounters_per_day: daily_encounters_from_env(),
+            encounter_slot: 0,
         }
     }
 
@@ -242,6 +448,180 @@ impl SessionState {
         &assets.pokemons[self.pokemon_index]
     }
 
+    fn sprite<'a>(&self, assets: &'a Assets) -> &'a ascii::AsciiImage {
+        let pokemon = self.pokemon(assets);
+        if self.shiny {
+            &pokemon.shiny
+        } else {
+            &pokemon.image
+        }
+    }
+
+    fn sprite_origin(&self, pokemon: &PokemonAsset) -> (usize, usize) {
+        let (dx, dy) = pokemon.offset;
+        let x = (self.width / 2) as i32 - 2 + dx;
+        let y = 5 + dy;
+        (x.max(0) as usize, y.max(0) as usize)
+    }
+
+    fn welcome_frames<'a>(&self, assets: &'a Assets) -> &'a [ascii::AsciiImage] {
+        assets
+            .welcome
+            .get(self.welcome_index)
+            .map(|anim| anim.frames.as_slice())
+            .unwrap_or(&[])
+    }
+
+    async fn throw_ball(&mut self, ball: BallKind) {
+        if !self.inventory.take(ball) {
+            self.show_caught_message(format!("Out of {}s!", ball.label()), "\x1b[91m");
+            return;
+        }
+        if let Some(name) = self.trainer_name.as_ref() {
+            let _ = save_inventory(name, &self.inventory).await;
+        }
+        self.ball = ball;
+        self.state = GameState::Throwing;
+        self.frame_count = 0;
+        self.capture_recorded = false;
+        self.caught_message = None;
+        self.caught_message_timer = 0;
+    }
+
+    async fn refresh_inventory(&mut self) {
+        let Some(name) = self.trainer_name.as_ref() else {
+            return;
+        };
+        if self.inventory.restock(self.current_day()) {
+            let _ = save_inventory(name, &self.inventory).await;
+        }
+    }
+
+    fn show_caught_message(&mut self, message: String, color: &'static str) {
+        self.caught_message = Some(message);
+        self.caught_message_timer = MESSAGE_FRAMES;
+        self.caught_message_color = color;
+    }
+
+    /// Short notice in the top-right corner, shown on every screen.
+    fn show_toast(&mut self, message: String) {
+        self.toast = Some(message);
+        self.toast_timer = TOAST_FRAMES;
+    }
+
+    /// Chat works from every screen once a trainer name is set. `input` keeps
+    /// the trainer's casing. Returns false for anything that isn't a chat command.
+    fn handle_chat_command(&mut self, input: &str) -> bool {
+        let (head, rest) = match input.split_once(char::is_whitespace) {
+            Some((head, rest)) => (head.to_lowercase(), rest.trim()),
+            None => (input.to_lowercase(), ""),
+        };
+        match head.as_str() {
+            "say" if !rest.is_empty() => self.send_chat(self.chat_room.clone(), rest),
+            "shout" if !rest.is_empty() => self.send_chat(None, rest),
+            "/mute" | "/unmute" => {
+                let Some(trainer) = sanitize_trainer_name(rest) else {
+                    self.push_chat_line(format!("* Usage: {head} <trainer>"), "\x1b[90m");
+                    return true;
+                };
+                if head == "/mute" {
+                    self.muted.insert(trainer.clone());
+                    self.push_chat_line(format!("* Muted {trainer}."), "\x1b[90m");
+                } else {
+                    self.muted.remove(&trainer);
+                    self.push_chat_line(format!("* Unmuted {trainer}."), "\x1b[90m");
+                }
+            }
+            "/join" => {
+                let Some(room) = sanitize_trainer_name(rest) else {
+                    self.push_chat_line("* Usage: /join <room>".to_string(), "\x1b[90m");
+                    return true;
+                };
+                self.chat_room = (room != "global").then_some(room);
+                let label = self.chat_room.as_deref().unwrap_or("global");
+                self.push_chat_line(format!("* Joined #{label}."), "\x1b[90m");
+            }
+            "/leave" => {
+                self.chat_room = None;
+                self.push_chat_line("* Back in #global.".to_string(), "\x1b[90m");
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    fn send_chat(&mut self, room: Option<String>, text: &str) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let text: String = chat::filter(text)
+            .chars()
+            .take(chat::MAX_MESSAGE_CHARS)
+            .collect();
+        if text.trim().is_empty() {
+            return;
+        }
+        if !chat::allow(&name, Instant::now()) {
+            self.push_chat_line(
+                "* Slow down! Wait a few seconds before chatting again.".to_string(),
+                "\x1b[90m",
+            );
+            return;
+        }
+        chat::publish(ChatMessage {
+            room,
+            from: name,
+            text,
+        });
+    }
+
+    /// Every session hears every message; this drops muted trainers and rooms
+    /// the trainer isn't in.
+    fn receive_chat(&mut self, message: ChatMessage) {
+        let Some(me) = self.trainer_name.as_ref() else {
+            return;
+        };
+        if self.muted.contains(&message.from)
+            || (message.room.is_some() && message.room != self.chat_room)
+        {
+            return;
+        }
+        let color = if message.from == *me {
+            "\x1b[96m"
+        } else {
+            "\x1b[97m"
+        };
+        let prefix = match (&message.room, &self.chat_room) {
+            (Some(room), _) => format!("[{room}] "),
+            (None, Some(_)) => "[global] ".to_string(),
+            (None, None) => String::new(),
+        };
+        self.push_chat_line(format!("{prefix}{}: {}", message.from, message.text), color);
+    }
+
+    /// Adds to the chat pane, scrolling the oldest lines off the top.
+    fn push_chat_line(&mut self, line: String, color: &'static str) {
+        for wrapped in word_wrap(&line, CHAT_WIDTH) {
+            self.chat_lines.push((wrapped, color));
+        }
+        if self.chat_lines.len() > CHAT_LINES {
+            let excess = self.chat_lines.len() - CHAT_LINES;
+            self.chat_lines.drain(..excess);
+        }
+        self.chat_timer = CHAT_FRAMES;
+    }
+
+    /// Keeps this trainer's entry in the lobby in step with the open screen.
+    fn publish_presence(&mut self) {
+        let label = self.screen_label();
+        if let Some(name) = self.trainer_name.as_ref()
+            && label != self.presence
+        {
+            sessions::set_activity(name, self.session_id, label);
+            self.presence = label;
+        }
+    }
+
     fn set_agent_message(&mut self, message: String) {
         let normalized = normalize_whitespace(&message);
         let max_w = self.width.saturating_sub(6).max(20);
@@ -256,7 +636,256 @@ impl SessionState {
             Screen::Pokedex => "pokedex",
             Screen::PokedexDetail => "pokedex_detail",
             Screen::Game => "game",
+            Screen::Battle => "battle",
+            Screen::Trade => "trade",
+            Screen::Lobby => "lobby",
+            Screen::Spectate => "spectate",
+            Screen::Leaderboard => "leaderboard",
+            Screen::Badges => "badges",
+            Screen::Map => "map",
+            Screen::Box => "box",
+        }
+    }
+
+    /// Whether the trainer has seen or caught `name`, so its entry can open.
+    fn pokedex_entry_known(&self, name: &str) -> bool {
+        !name.is_empty() && (self.pokedex.contains(name) || self.seen.contains_key(name))
+    }
+
+    /// Shows entry `idx` on the detail page and moves the grid to the page
+    /// holding it, so `back` lands there.
+    fn open_pokedex_entry(&mut self, assets: &Assets, idx: usize) {
+        let Some(name) = assets.pokedex.names.get(idx) else {
+            return;
+        };
+        self.pokedex_detail = Some(idx);
+        self.pokedex_detail_shiny = self.shinies.contains(name);
+        if let Some(region) = assets
+            .pokedex
+            .regions
+            .iter()
+            .position(|region| (region.first..=region.last).contains(&(idx + 1)))
+        {
+            self.pokedex_region = region;
+            self.pokedex_page =
+                (idx + 1 - assets.pokedex.regions[region].first) / POKEDEX_PAGE_SIZE;
+        }
+    }
+
+    /// Pages the detail view to the next or previous entry the trainer has
+    /// seen, wrapping around the dex.
+    fn step_pokedex_entry(&mut self, assets: &Assets, forward: bool) {
+        let names = &assets.pokedex.names;
+        let Some(current) = self.pokedex_detail else {
+            return;
+        };
+        let len = names.len();
+        let next = (1..len)
+            .map(|offset| {
+                if forward {
+                    (current + offset) % len
+                } else {
+                    (current + len - offset) % len
+                }
+            })
+            .find(|idx| self.pokedex_entry_known(&names[*idx]));
+        if let Some(idx) = next {
+            self.open_pokedex_entry(assets, idx);
+        }
+    }
+
+    fn pokedex_page_count(&self, assets: &Assets) -> usize {
+        assets
+            .pokedex
+            .regions
+            .get(self.pokedex_region)
+            .map(|region| region.len().div_ceil(POKEDEX_PAGE_SIZE).max(1))
+            .unwrap_or(1)
+    }
+
+    fn turn_pokedex_page(&mut self, assets: &Assets, forward: bool) {
+        let pages = self.pokedex_page_count(assets);
+        let regions = assets.pokedex.regions.len().max(1);
+        if forward {
+            if self.pokedex_page + 1 < pages {
+                self.pokedex_page += 1;
+            } else {
+                self.pokedex_region = (self.pokedex_region + 1) % regions;
+                self.pokedex_page = 0;
+            }
+        } else if self.pokedex_page > 0 {
+            self.pokedex_page -= 1;
+        } else {
+            self.pokedex_region = (self.pokedex_region + regions - 1) % regions;
+            self.pokedex_page = self.pokedex_page_count(assets) - 1;
+        }
+    }
+
+    /// Called whenever the Pokedex or login streak may have changed: updates
+    /// the leaderboard row and unlocks any newly earned badges.
+    async fn record_progress(&mut self, assets: &Assets) {
+        self.refresh_leaderboard(assets).await;
+        self.check_achievements(assets).await;
+    }
+
+    async fn check_achievements(&mut self, assets: &Assets) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let progress = achievements::Progress {
+            pokedex: &self.pokedex,
+            view: &assets.pokedex,
+            streak: self.login_streak,
+        };
+        let unlocked: Vec<achievements::Badge> = achievements::catalog(&assets.pokedex)
+            .into_iter()
+            .filter(|badge| !self.badges.contains_key(&badge.id))
+            .filter(|badge| achievements::earned(&badge.rule, &progress))
+            .collect();
+        if unlocked.is_empty() {
+            return;
+        }
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs() as i64;
+        for badge in &unlocked {
+            if save_badge(&name, &badge.id, now).await.is_ok() {
+                self.badges.insert(badge.id.clone(), now);
+            }
+        }
+        let titles: Vec<&str> = unlocked.iter().map(|badge| badge.title.as_str()).collect();
+        let label = if titles.len() == 1 { "Badge" } else { "Badges" };
+        self.show_toast(format!("{label} unlocked: {}", titles.join(", ")));
+    }
+
+    /// Counts today toward the login streak and loads today's quests, once
+    /// per day. A zone change that puts the local date behind the last
+    /// recorded day leaves the trainer on that day.
+    async fn record_login(&mut self, assets: &Assets) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let today = self.zone.day_index(unix_now());
+        let (day, streak) = save_login(&name, today).await.unwrap_or((today, 1));
+        self.login_day = day;
+        self.login_streak = streak;
+        self.quests = match load_quests(&name, day).await {
+            Ok(quests) if !quests.is_empty() => quests,
+            _ => {
+                let types = &assets.pokedex.types_by_name;
+                let quests = quests::daily(
+                    daily_seed(&name, day),
+                    |kind| types.values().any(|types| types.iter().any(|t| t == kind)),
+                    self.selection_mode == SelectionMode::DailyWeighted,
+                );
+                let _ = save_quests(&name, day, &quests).await;
+                quests
+            }
+        };
+        self.bonus_encounters = self
+            .quests
+            .iter()
+            .filter(|quest| quest.done())
+            .map(|quest| match quest.reward {
+                quests::Reward::Encounters(count) => count,
+                quests::Reward::Balls(..) => 0,
+            })
+            .sum();
+    }
+
+    /// Counts `event` toward today's quests and hands out the reward for any
+    /// quest it completes.
+    async fn record_quest(&mut self, assets: &Assets, event: quests::Event<'_>) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let before: Vec<u32> = self.quests.iter().map(|quest| quest.progress).collect();
+        let mut completed = Vec::new();
+        for quest in &mut self.quests {
+            if quest.record(&event) {
+                completed.push(quest.clone());
+            }
+        }
+        if self
+            .quests
+            .iter()
+            .zip(&before)
+            .all(|(quest, progress)| quest.progress == *progress)
+        {
+            return;
+        }
+        let _ = save_quests(&name, self.login_day, &self.quests).await;
+        for quest in &completed {
+            match quest.reward {
+                quests::Reward::Balls(ball, count) => {
+                    self.inventory.counts[ball.index()] += count;
+                    let _ = save_inventory(&name, &self.inventory).await;
+                }
+                quests::Reward::Encounters(count) => {
+                    let was_done = self.encounters_done();
+                    self.bonus_encounters += count;
+                    if was_done && self.state == GameState::Idle {
+                        self.pick_daily_encounter(assets).await;
+                        self.announce_shiny(assets);
+                    }
+                }
+            }
+            self.show_toast(format!(
+                "Quest complete: {} (+{})",
+                quest.describe(),
+                quest.reward.describe()
+            ));
+        }
+    }
+
+    /// Writes this trainer's current counts to the leaderboard table.
+    async fn refresh_leaderboard(&self, assets: &Assets) {
+        let Some(name) = self.trainer_name.as_ref() else {
+            return;
+        };
+        let (caught, total, legendaries) = dex_counts(&self.pokedex, &assets.pokedex);
+        let _ = save_leaderboard_entry(
+            name,
+            (caught, self.shinies.len(), legendaries),
+            total > 0 && caught >= total,
+            dex_species(&assets.pokedex),
+        )
+        .await;
+    }
+
+    async fn open_leaderboard(&mut self) {
+        self.leaderboard = load_leaderboard().await.unwrap_or_default();
+        leaderboard::rank(&mut self.leaderboard, self.leaderboard_sort);
+        self.leaderboard_notice = None;
+        self.screen = Screen::Leaderboard;
+    }
+
+    async fn update_friend(&mut self, friend: &str, add: bool) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let Some(friend) = sanitize_trainer_name(friend) else {
+            self.leaderboard_notice = Some("That's not a trainer name.".to_string());
+            return;
+        };
+        if friend == name {
+            self.leaderboard_notice = Some("You're always on your own list.".to_string());
+            return;
         }
+        let changed = if add {
+            self.friends.insert(friend.clone())
+        } else {
+            self.friends.remove(&friend)
+        };
+        if changed {
+            let _ = save_friend(&name, &friend, add).await;
+        }
+        self.leaderboard_notice = Some(if add {
+            format!("{friend} is on your friends list.")
+        } else {
+            format!("{friend} is off your friends list.")
+        });
     }
 
     fn dex_progress(&self, assets: &Assets) -> (usize, usize, usize) {
@@ -283,7 +912,7 @@ impl SessionState {
         }
 
         let trainer_name = self.trainer_name.clone();
-        let current_pokemon = if matches!(self.screen, Screen::Game) {
+        let current_pokemon = if matches!(self.screen, Screen::Game) && !self.encounters_done() {
             Some(self.pokemon(assets).name.clone())
         } else {
             None
@@ -292,6 +921,9 @@ impl SessionState {
         let (caught_count, total_count, left_count) = self.dex_progress(assets);
 
         let query = normalized.to_lowercase();
+        let asked_about = current_pokemon
+            .clone()
+            .filter(|_| is_agent_stats_query(&query) || is_agent_pokemon_query(&query));
 
         let response = if is_agent_stats_query(&query) {
             if let Some(pokemon_name) = current_pokemon.as_deref() {
@@ -324,7 +956,7 @@ impl SessionState {
         } else if is_agent_missing_list_query(&query) {
             let missing = missing_pokemon_preview(&self.pokedex, assets, 8);
             if missing.is_empty() {
-                "Agent: your Gen 1 dex is complete. Legendaries are unlocked.".to_string()
+                "Agent: your dex is complete. Legendaries are unlocked.".to_string()
             } else {
                 format!(
                     "Agent: you are missing {}. Next few: {}.",
@@ -351,6 +983,10 @@ impl SessionState {
         };
 
         self.set_agent_message(response);
+        if let Some(species) = asked_about {
+            self.record_quest(assets, quests::Event::AskedAgent { species: &species })
+                .await;
+        }
         true
     }
 
@@ -365,17 +1001,56 @@ impl SessionState {
             return CommandAction::Disconnect;
         }
         self.last_cmd = raw_cmd;
+        if self.trainer_name.is_some() && self.handle_chat_command(cmd.trim()) {
+            return CommandAction::None;
+        }
         match self.screen {
             Screen::Name => {
-                if let Some(name) = sanitize_trainer_name(&cmd_trim) {
+                if let Some(trainer) = cmd_trim.strip_prefix("watch ") {
+                    self.start_spectating(trainer.trim());
+                } else if let Some(name) = sanitize_trainer_name(&cmd_trim) {
                     self.pokedex = load_pokedex(&name).await.unwrap_or_default();
+                    self.shinies = load_shinies(&name).await.unwrap_or_default();
+                    self.seen = load_seen(&name).await.unwrap_or_default();
+                    self.captures = load_captures(&name, &self.pokedex)
+                        .await
+                        .unwrap_or_default();
+                    self.inventory = load_inventory(&name).await.unwrap_or_default();
+                    self.allow_spectators = load_allow_spectators(&name).await.unwrap_or(true);
+                    self.friends = load_friends(&name).await.unwrap_or_default();
+                    self.badges = load_badges(&name).await.unwrap_or_default();
+                    let zone = load_timezone(&name)
+                        .await
+                        .ok()
+                        .and_then(|(zone, _)| tz::Zone::load(&zone?).ok());
+                    self.custom_zone = zone.is_some();
+                    self.zone = zone.unwrap_or_else(|| assets.zone.clone());
+                    let (caught, _, _) = self.dex_progress(assets);
+                    self.route = load_route(&name)
+                        .await
+                        .ok()
+                        .flatten()
+                        .and_then(|id| assets.routes.iter().position(|route| route.id == id))
+                        .filter(|idx| assets.routes[*idx].unlock <= caught)
+                        .unwrap_or(0);
+                    sessions::register(
+                        &name,
+                        self.session_id,
+                        self.events.clone(),
+                        Arc::clone(&self.mirror),
+                        self.allow_spectators,
+                    );
+                    self.play_session = start_play_session(&name).await.ok();
+                    self.presence = "game";
                     self.trainer_name = Some(name);
+                    self.refresh_inventory().await;
+                    self.record_login(assets).await;
+                    self.record_progress(assets).await;
                     match self.selection_mode {
-                        SelectionMode::DailyWeighted => self.refresh_daily_pokemon(assets),
-                        SelectionMode::RandomPerSession => {
-                            self.pokemon_index = pick_pokemon_index(&assets.pokemons);
-                        }
+                        SelectionMode::DailyWeighted => self.refresh_daily_pokemon(assets).await,
+                        SelectionMode::RandomPerSession => self.pick_random_encounter(assets).await,
                     }
+                    self.announce_shiny(assets);
                     self.screen = Screen::Game;
                 }
             }
@@ -385,18 +1060,26 @@ impl SessionState {
                     self.pokedex_detail = None;
                     self.pokedex_notice = None;
                     self.pokedex_notice_timer = 0;
+                } else if cmd_trim == "next" || cmd_trim == "prev" {
+                    self.turn_pokedex_page(assets, cmd_trim == "next");
+                } else if let Some(region) = assets
+                    .pokedex
+                    .find_region(cmd_trim.strip_prefix("region ").unwrap_or(&cmd_trim))
+                {
+                    self.pokedex_region = region;
+                    self.pokedex_page = 0;
                 } else if let Ok(id) = cmd_trim.parse::<usize>() {
-                    if id >= 1 && id <= 151 {
-                        if let Some(name) = assets.pokedex.names.get(id - 1) {
-                            if !name.is_empty() && self.pokedex.contains(name) {
-                                self.pokedex_detail = Some(id - 1);
-                                self.screen = Screen::PokedexDetail;
-                                self.pokedex_notice = None;
-                                self.pokedex_notice_timer = 0;
-                            } else {
-                                self.pokedex_notice = Some("POKEMON NOT CAUGHT YET".to_string());
-                                self.pokedex_notice_timer = 45;
-                            }
+                    if id >= 1
+                        && let Some(name) = assets.pokedex.names.get(id - 1)
+                    {
+                        if self.pokedex_entry_known(name) {
+                            self.open_pokedex_entry(assets, id - 1);
+                            self.screen = Screen::PokedexDetail;
+                            self.pokedex_notice = None;
+                            self.pokedex_notice_timer = 0;
+                        } else {
+                            self.pokedex_notice = Some("POKEMON NOT SEEN YET".to_string());
+                            self.pokedex_notice_timer = 45;
                         }
                     }
                 } else if self.answer_query_with_agent(&cmd_trim, assets).await {
@@ -406,15 +1089,69 @@ impl SessionState {
             Screen::PokedexDetail => {
                 if cmd_trim == "back" {
                     self.screen = Screen::Pokedex;
+                } else if cmd_trim == "next" || cmd_trim == "prev" {
+                    self.step_pokedex_entry(assets, cmd_trim == "next");
+                } else if cmd_trim == "shiny" || cmd_trim == "normal" {
+                    if let Some(name) = self
+                        .pokedex_detail
+                        .and_then(|idx| assets.pokedex.names.get(idx))
+                        && self.shinies.contains(name)
+                    {
+                        self.pokedex_detail_shiny = cmd_trim == "shiny";
+                    }
                 } else if self.answer_query_with_agent(&cmd_trim, assets).await {
                     return CommandAction::None;
                 }
             }
             Screen::Game => {
-                if cmd_trim == "catch" && self.state == GameState::Idle {
-                    self.state = GameState::Throwing;
-                    self.frame_count = 0;
-                    self.capture_recorded = false;
+                let throw = if cmd_trim == "catch" {
+                    Some(Some(BallKind::Poke))
+                } else {
+                    cmd_trim.strip_prefix("throw").map(BallKind::parse)
+                };
+                if let Some(ball) = throw {
+                    self.handle_throw(ball).await;
+                } else if cmd_trim == "next" || cmd_trim == "run" {
+                    if self.state == GameState::Idle {
+                        self.skip_encounter(assets, "ran", "Got away safely!".to_string())
+                            .await;
+                    }
+                } else if cmd_trim == "battle" || cmd_trim == "fight" {
+                    self.start_battle(assets, None);
+                } else if let Some(partner) = cmd_trim
+                    .strip_prefix("battle ")
+                    .or_else(|| cmd_trim.strip_prefix("fight "))
+                {
+                    self.start_battle(assets, Some(partner.trim()));
+                } else if let Some(partner) = cmd_trim.strip_prefix("trade ") {
+                    self.request_trade(partner.trim());
+                } else if matches!(cmd_trim.as_str(), "leaderboard" | "rank" | "top") {
+                    self.open_leaderboard().await;
+                } else if matches!(cmd_trim.as_str(), "map" | "routes") {
+                    self.map_notice = None;
+                    self.screen = Screen::Map;
+                } else if let Some(route) = cmd_trim.strip_prefix("travel ") {
+                    self.travel(assets, route).await;
+                } else if matches!(cmd_trim.as_str(), "box" | "pc" | "storage") {
+                    self.box_page = 0;
+                    self.box_notice = None;
+                    self.box_release = None;
+                    self.screen = Screen::Box;
+                } else if matches!(cmd_trim.as_str(), "badges" | "achievements") {
+                    self.badges_page = 0;
+                    self.screen = Screen::Badges;
+                } else if matches!(cmd_trim.as_str(), "online" | "lobby" | "who") {
+                    self.screen = Screen::Lobby;
+                } else if cmd_trim == "tz" || cmd_trim == "timezone" {
+                    self.show_timezone();
+                } else if let Some(zone) = cmd
+                    .trim()
+                    .strip_prefix("tz ")
+                    .or_else(|| cmd.trim().strip_prefix("timezone "))
+                {
+                    self.set_timezone(assets, zone.trim()).await;
+                } else if cmd_trim == "spectators on" || cmd_trim == "spectators off" {
+                    self.set_allow_spectators(cmd_trim == "spectators on").await;
                 } else if cmd_trim == "pokedex" || cmd_trim == "dex" {
                     self.screen = Screen::Pokedex;
                     self.pokedex_detail = None;
@@ -424,6 +1161,124 @@ impl SessionState {
                     return CommandAction::None;
                 }
             }
+            Screen::Battle => {
+                let throw = if cmd_trim == "catch" {
+                    Some(Some(BallKind::Poke))
+                } else {
+                    cmd_trim.strip_prefix("throw").map(BallKind::parse)
+                };
+                if let Some(ball) = throw {
+                    self.screen = Screen::Game;
+                    self.handle_throw(ball).await;
+                } else if cmd_trim == "back" || cmd_trim == "run" {
+                    self.screen = Screen::Game;
+                } else if let Some(partner) = cmd_trim.strip_prefix("switch ") {
+                    self.start_battle(assets, Some(partner.trim()));
+                } else if let Some(battle) = self.battle.as_ref()
+                    && let Some(move_idx) = battle.player.find_move(&cmd_trim)
+                {
+                    self.battle_turn(assets, move_idx).await;
+                }
+            }
+            Screen::Map => {
+                self.map_notice = None;
+                if cmd_trim == "back" {
+                    self.screen = Screen::Game;
+                } else if !cmd_trim.is_empty() {
+                    self.travel(
+                        assets,
+                        cmd_trim.strip_prefix("travel ").unwrap_or(&cmd_trim),
+                    )
+                    .await;
+                }
+            }
+            Screen::Box => {
+                self.box_notice = None;
+                let release = self.box_release.take();
+                if cmd_trim == "back" {
+                    self.screen = Screen::Game;
+                } else if cmd_trim == "confirm"
+                    && let Some(id) = release
+                {
+                    self.release_capture(id).await;
+                } else if cmd_trim == "next" {
+                    self.box_page += 1;
+                } else if cmd_trim == "prev" {
+                    self.box_page = self.box_page.saturating_sub(1);
+                } else if let Some(key) = cmd_trim.strip_prefix("sort ") {
+                    match BoxSort::parse(key) {
+                        Some(key) => {
+                            self.box_sort = key;
+                            self.box_page = 0;
+                        }
+                        None => {
+                            self.box_notice =
+                                Some("Sort by date, level, species, or name.".to_string());
+                        }
+                    }
+                } else if let Some(filter) = cmd_trim.strip_prefix("filter ") {
+                    self.box_filter = BoxFilter::parse(filter);
+                    self.box_page = 0;
+                } else if cmd_trim == "filter" || cmd_trim == "all" {
+                    self.box_filter = BoxFilter::All;
+                    self.box_page = 0;
+                } else if cmd_trim == "nickname" || cmd_trim.starts_with("nickname ") {
+                    let args = cmd.trim().split_once(' ').map_or("", |(_, args)| args);
+                    self.nickname_capture(args).await;
+                } else if let Some(pick) = cmd_trim.strip_prefix("release ") {
+                    self.ask_release(pick.trim());
+                } else if cmd_trim == "confirm" {
+                    self.box_notice = Some("Type 'release <number>' first.".to_string());
+                }
+            }
+            Screen::Badges => {
+                if cmd_trim == "back" {
+                    self.screen = Screen::Game;
+                } else if cmd_trim == "next" {
+                    self.badges_page += 1;
+                } else if cmd_trim == "prev" {
+                    self.badges_page = self.badges_page.saturating_sub(1);
+                }
+            }
+            Screen::Leaderboard => {
+                self.leaderboard_notice = None;
+                if cmd_trim == "back" {
+                    self.screen = Screen::Game;
+                } else if cmd_trim == "friends" || cmd_trim == "all" {
+                    self.friends_only = cmd_trim == "friends";
+                } else if let Some(key) =
+                    SortKey::parse(cmd_trim.strip_prefix("sort ").unwrap_or(&cmd_trim))
+                {
+                    self.leaderboard_sort = key;
+                    leaderboard::rank(&mut self.leaderboard, key);
+                } else if let Some(friend) = cmd_trim.strip_prefix("friend ") {
+                    self.update_friend(friend.trim(), true).await;
+                } else if let Some(friend) = cmd_trim.strip_prefix("unfriend ") {
+                    self.update_friend(friend.trim(), false).await;
+                }
+            }
+            Screen::Spectate => {
+                if cmd_trim == "back" || cmd_trim == "stop" {
+                    self.stop_spectating();
+                }
+            }
+            Screen::Lobby => {
+                if cmd_trim == "back" {
+                    self.screen = Screen::Game;
+                } else if let Some(partner) = cmd_trim.strip_prefix("trade ") {
+                    self.request_trade(partner.trim());
+                }
+            }
+            Screen::Trade => {
+                if cmd_trim == "cancel" || cmd_trim == "back" {
+                    self.cancel_trade("Trade cancelled.".to_string());
+                } else if cmd_trim == "confirm" {
+                    self.confirm_trade(assets).await;
+                } else {
+                    let pick = cmd_trim.strip_prefix("offer ").unwrap_or(&cmd_trim);
+                    self.offer_trade(pick.trim());
+                }
+            }
         }
         CommandAction::None
     }
@@ -436,16 +1291,48 @@ impl SessionState {
             }
         }
 
+        if self.toast_timer > 0 {
+            self.toast_timer -= 1;
+            if self.toast_timer == 0 {
+                self.toast = None;
+            }
+        }
+        self.publish_presence();
+        if self.chat_timer > 0 {
+            self.chat_timer -= 1;
+            if self.chat_timer == 0 {
+                self.chat_lines.clear();
+            }
+        }
+
+        self.sparkle_frame = self.sparkle_frame.wrapping_add(1);
+        if self.trainer_name.is_some() && self.login_day != self.current_day() {
+            self.record_login(assets).await;
+            self.record_progress(assets).await;
+        }
         if self.selection_mode == SelectionMode::DailyWeighted
             && self.trainer_name.is_some()
             && self.state == GameState::Idle
         {
-            self.refresh_daily_pokemon(assets);
+            self.refresh_daily_pokemon(assets).await;
+        }
+        if self.state == GameState::Idle {
+            self.refresh_inventory().await;
         }
 
         if let Screen::Game = self.screen {
             match self.state {
                 GameState::Idle => {
+                    if !self.pending_evolutions.is_empty() {
+                        let (from, into) = self.pending_evolutions.remove(0);
+                        self.start_evolution(assets, from, into);
+                    }
+                    if self.caught_message_timer > 0 {
+                        self.caught_message_timer = self.caught_message_timer.saturating_sub(1);
+                        if self.caught_message_timer == 0 {
+                            self.caught_message = None;
+                        }
+                    }
                     self.frame_count = self.frame_count.saturating_add(1);
                     if self.frame_count > 60 {
                         self.frame_count = 0;
@@ -463,12 +1350,11 @@ impl SessionState {
                         self.capture_frame = 0;
                         self.open_amount = 0.0;
                         self.align_start_a = self.a;
-                        let grow_start_y = 5;
-                        let grow_start_x = (self.width / 2) - 2;
+                        let (grow_start_x, grow_start_y) = self.sprite_origin(self.pokemon(assets));
                         let ball_center_x = self.width as f32 / 2.0 + self.ball_x;
                         let ball_center_y = self.height as f32 / 2.0 + self.ball_y;
                         self.stream_particles = build_stream_particles(
-                            self.pokemon(assets),
+                            self.sprite(assets),
                             grow_start_x,
                             grow_start_y,
                             ball_center_x,
@@ -496,14 +1382,6 @@ impl SessionState {
                     if self.capture_frame >= ABSORB_FRAMES {
                         self.state = GameState::Closing;
                         self.capture_frame = 0;
-                        if !self.capture_recorded {
-                            if let Some(name) = self.trainer_name.as_ref() {
-                                if self.pokedex.insert(self.pokemon(assets).name.clone()) {
-                                    let _ = save_pokedex(name, &self.pokedex).await;
-                                }
-                            }
-                            self.capture_recorded = true;
-                        }
                     }
                 }
                 GameState::Closing => {
@@ -517,6 +1395,13 @@ impl SessionState {
                         self.shake_frame = 0;
                         self.shake_count = 0;
                         self.open_amount = 0.0;
+                        let name = &self.pokemon(assets).name;
+                        let total = assets.pokedex.totals_by_name.get(name).copied();
+                        let bonus = self.battle.as_ref().map_or(1.0, Battle::catch_bonus);
+                        self.shake_chance = match self.ball.modifier() {
+                            Some(modifier) => shake_chance(capture_chance(total, modifier * bonus)),
+                            None => 1.0,
+                        };
                     }
                 }
                 GameState::Shaking => {
@@ -529,16 +1414,70 @@ impl SessionState {
                     self.ball_y = self.floor_y;
                     if self.shake_frame >= SHAKE_FRAMES {
                         self.shake_frame = 0;
-                        self.shake_count = self.shake_count.saturating_add(1);
-                        if self.shake_count >= SHAKE_COUNT {
+                        if roll_unit(&mut self.rng) >= self.shake_chance {
+                            self.state = GameState::Breakout;
+                            self.capture_frame = 0;
+                            self.ball_x = 15.0;
+                        } else {
+                            self.shake_count = self.shake_count.saturating_add(1);
+                        }
+                        if self.state == GameState::Shaking && self.shake_count >= SHAKE_COUNT {
                             self.star_frame = STAR_FRAMES;
                             self.star_hold = 45;
                             self.state = GameState::StarHold;
-                            self.caught_message = Some(format!(
-                                "{} Caught!",
-                                display_pokemon_name(&self.pokemon(assets).name)
-                            ));
-                            self.caught_message_timer = 45;
+                            let species = display_pokemon_name(&self.pokemon(assets).name);
+                            if self.shiny {
+                                self.show_caught_message(
+                                    format!("Shiny {species} Caught!"),
+                                    "\x1b[93m",
+                                );
+                            } else {
+                                self.show_caught_message(format!("{species} Caught!"), "\x1b[92m");
+                            }
+                            if !self.capture_recorded {
+                                if let Some(name) = self.trainer_name.as_ref()
+                                    && self.pokedex.insert(self.pokemon(assets).name.clone())
+                                {
+                                    let _ = save_pokedex(name, &self.pokedex).await;
+                                }
+                                self.record_capture(assets).await;
+                                let species = self.pokemon(assets).name.clone();
+                                if assets.pokedex.is_legendary(&species)
+                                    && let Some(name) = self.trainer_name.clone()
+                                {
+                                    sessions::broadcast(
+                                        &name,
+                                        SessionEvent::LegendaryCaught {
+                                            trainer: name.clone(),
+                                            species,
+                                        },
+                                    );
+                                }
+                                if let Some(level_up) = self.award_battle_xp(assets).await
+                                    && let Some(message) = self.caught_message.as_mut()
+                                {
+                                    message.push_str("  ");
+                                    message.push_str(&level_up);
+                                }
+                                if self.shiny
+                                    && let Some(name) = self.trainer_name.as_ref()
+                                    && self.shinies.insert(self.pokemon(assets).name.clone())
+                                {
+                                    let _ = save_shiny(name, &self.pokemon(assets).name).await;
+                                }
+                                self.record_progress(assets).await;
+                                let species = self.pokemon(assets).name.clone();
+                                let types = assets
+                                    .pokedex
+                                    .types_by_name
+                                    .get(&species)
+                                    .cloned()
+                                    .unwrap_or_default();
+                                self.record_quest(assets, quests::Event::Caught { types: &types })
+                                    .await;
+                                self.resolve_encounter(assets, "caught").await;
+                                self.capture_recorded = true;
+                            }
                         }
                     }
                 }
@@ -559,6 +1498,46 @@ impl SessionState {
                         self.stream_particles.clear();
                         self.caught_message = None;
                         self.caught_message_timer = 0;
+                        self.battle = None;
+                        self.next_encounter(assets).await;
+                        self.announce_shiny(assets);
+                    }
+                }
+                GameState::Breakout => {
+                    self.capture_frame = self.capture_frame.saturating_add(1);
+                    self.open_amount = (self.capture_frame as f32 / OPEN_FRAMES as f32).min(1.0);
+                    self.ball_x = 15.0;
+                    self.ball_y = self.floor_y;
+                    if self.capture_frame >= BREAKOUT_FRAMES {
+                        self.state = GameState::Idle;
+                        self.ball_x = -45.0;
+                        self.frame_count = 0;
+                        self.open_amount = 0.0;
+                        self.stream_particles.clear();
+                        self.show_caught_message("It broke free!".to_string(), "\x1b[91m");
+                    }
+                }
+                GameState::Evolving => {
+                    let done = match self.evolution.as_mut() {
+                        Some(scene) => {
+                            scene.frame = scene.frame.saturating_add(1);
+                            scene.frame >= EVOLVE_FRAMES
+                        }
+                        None => true,
+                    };
+                    if done {
+                        self.state = GameState::Idle;
+                        self.frame_count = 0;
+                        if let Some(scene) = self.evolution.take() {
+                            self.show_caught_message(
+                                format!(
+                                    "{} evolved into {}!",
+                                    display_pokemon_name(&scene.from),
+                                    display_pokemon_name(&scene.into)
+                                ),
+                                "\x1b[96m",
+                            );
+                        }
                     }
                 }
             }
@@ -566,12 +1545,18 @@ impl SessionState {
 
         match self.screen {
             Screen::Name => {
+                if self.caught_message_timer > 0 {
+                    self.caught_message_timer -= 1;
+                    if self.caught_message_timer == 0 {
+                        self.caught_message = None;
+                    }
+                }
                 self.welcome_accum += 30;
                 if self.welcome_accum >= self.welcome_frame_ms {
                     self.welcome_accum = 0;
-                    if !assets.arcanine_frames.is_empty() {
-                        self.welcome_frame =
-                            (self.welcome_frame + 1) % assets.arcanine_frames.len();
+                    let frames = self.welcome_frames(assets);
+                    if !frames.is_empty() {
+                        self.welcome_frame = (self.welcome_frame + 1) % frames.len();
                     }
                 }
             }
@@ -583,7 +1568,17 @@ impl SessionState {
                     }
                 }
             }
-            Screen::PokedexDetail => {}
+            Screen::PokedexDetail
+            | Screen::Trade
+            | Screen::Lobby
+            | Screen::Leaderboard
+            | Screen::Badges
+            | Screen::Map
+            | Screen::Box => {}
+            Screen::Spectate => self.poll_mirror(),
+            Screen::Battle => {
+                self.battle_flash = self.battle_flash.saturating_sub(1);
+            }
             Screen::Game => {
                 if self.state == GameState::Throwing {
                     self.a -= 0.2;
@@ -601,15 +1596,14 @@ impl SessionState {
         let zbuffer = &mut buffers.zbuffer;
         let color_buf = &mut buffers.color_buf;
         let reset = "\x1b[0m";
-        let red = "\x1b[91m";
         let white = "\x1b[97m";
         let black = "\x1b[30m";
 
         match self.screen {
             Screen::Name => {
-                if !assets.arcanine_frames.is_empty() {
-                    let frame =
-                        &assets.arcanine_frames[self.welcome_frame % assets.arcanine_frames.len()];
+                let frames = self.welcome_frames(assets);
+                if !frames.is_empty() {
+                    let frame = &frames[self.welcome_frame % frames.len()];
                     let start_x = (self.width.saturating_sub(frame.width)) / 2;
                     let start_y = (self.height.saturating_sub(frame.height)) / 2;
 
@@ -636,6 +1630,9 @@ impl SessionState {
                 render_pokedex(
                     &assets.pokedex,
                     &self.pokedex,
+                    &self.seen,
+                    self.pokedex_region,
+                    self.pokedex_page,
                     output,
                     color_buf,
                     zbuffer,
@@ -645,9 +1642,22 @@ impl SessionState {
             }
             Screen::PokedexDetail => {
                 if let Some(detail) = self.pokedex_detail {
+                    let record = assets
+                        .pokedex
+                        .names
+                        .get(detail)
+                        .and_then(|name| self.catch_summary(name));
+                    let caught = assets
+                        .pokedex
+                        .names
+                        .get(detail)
+                        .is_some_and(|name| self.pokedex.contains(name));
                     render_pokedex_detail(
                         assets,
                         detail,
+                        caught,
+                        self.pokedex_detail_shiny && caught,
+                        record.as_deref(),
                         output,
                         color_buf,
                         zbuffer,
@@ -656,38 +1666,227 @@ impl SessionState {
                     );
                 }
             }
-            Screen::Game => {
-                let pokemon = self.pokemon(assets);
-                if matches!(
-                    self.state,
-                    GameState::Idle | GameState::Throwing | GameState::Opening
-                ) {
-                    let grow_start_y = 5;
-                    let grow_start_x = (self.width / 2) - 2;
-
-                    for y in 0..pokemon.image.height {
-                        for x in 0..pokemon.image.width {
+            Screen::Battle => {
+                if let Some(battle) = self.battle.as_ref() {
+                    let player_sprite =
+                        find_pokemon_asset(assets, &battle.player.name).map(|asset| {
+                            if self.shinies.contains(&battle.player.name) {
+                                &asset.shiny
+                            } else {
+                                &asset.image
+                            }
+                        });
+                    let hidden = (self.battle_flash / 2) % 2 == 1;
+                    render_battle(
+                        battle,
+                        player_sprite,
+                        self.sprite(assets),
+                        battle.last_hit.filter(|_| hidden),
+                        output,
+                        color_buf,
+                        zbuffer,
+                        self.width,
+                        self.height,
+                    );
+                }
+            }
+            Screen::Map => {
+                let (caught, _, _) = self.dex_progress(assets);
+                render_map(
+                    &assets.routes,
+                    self.route,
+                    caught,
+                    self.map_notice.as_deref(),
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
+            }
+            Screen::Box => {
+                let entries = pc::arrange(&self.captures, self.box_sort, &self.box_filter);
+                let pending = self.box_release;
+                self.box_page = render_box(
+                    &entries,
+                    self.captures.len(),
+                    self.box_sort,
+                    &self.box_filter,
+                    self.box_page,
+                    pending,
+                    self.box_notice.as_deref(),
+                    &self.zone,
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
+            }
+            Screen::Badges => {
+                let catalog = achievements::catalog(&assets.pokedex);
+                self.badges_page = render_badges(
+                    &catalog,
+                    &self.badges,
+                    self.badges_page,
+                    self.login_streak,
+                    &self.zone,
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
+            }
+            Screen::Leaderboard => {
+                let (_, total, _) = self.dex_progress(assets);
+                render_leaderboard(
+                    &self.leaderboard,
+                    self.leaderboard_sort,
+                    total,
+                    self.trainer_name.as_deref(),
+                    self.friends_only.then_some(&self.friends),
+                    self.leaderboard_notice.as_deref(),
+                    &self.zone,
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
+            }
+            Screen::Spectate => {
+                if let Some((trainer, _)) = self.spectating.as_ref() {
+                    render_mirror(
+                        self.mirror_frame.as_deref(),
+                        trainer,
+                        output,
+                        color_buf,
+                        zbuffer,
+                        self.width,
+                        self.height,
+                    );
+                }
+            }
+            Screen::Lobby => {
+                render_lobby(
+                    &sessions::online(),
+                    self.trainer_name.as_deref(),
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
+            }
+            Screen::Trade => {
+                if let Some(trade) = self.trade.as_ref() {
+                    render_trade(
+                        trade,
+                        &self.trade_candidates(),
+                        assets,
+                        output,
+                        color_buf,
+                        zbuffer,
+                        self.width,
+                        self.height,
+                    );
+                }
+            }
+            Screen::Game => {
+                render_backdrop(
+                    self.conditions(),
+                    self.sparkle_frame,
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
+                let pokemon = self.pokemon(assets);
+                if self.encounters_done() && self.state == GameState::Idle {
+                    let notice = "No more wild Pokemon today. Come back tomorrow!";
+                    let row = self.height / 3;
+                    let start_x = self.width.saturating_sub(notice.len()) / 2;
+                    for (i, ch) in notice.chars().enumerate() {
+                        let x = start_x + i;
+                        if x >= self.width || row >= self.height {
+                            continue;
+                        }
+                        let idx = x + row * self.width;
+                        output[idx] = ch;
+                        color_buf[idx] = CellColor::Ansi("\x1b[97m");
+                        zbuffer[idx] = 0.4;
+                    }
+                } else if matches!(
+                    self.state,
+                    GameState::Idle | GameState::Throwing | GameState::Opening
+                ) {
+                    let (grow_start_x, grow_start_y) = self.sprite_origin(pokemon);
+                    let image = self.sprite(assets);
+
+                    for y in 0..image.height {
+                        for x in 0..image.width {
                             let target_y = grow_start_y + y;
                             let target_x = grow_start_x + x;
                             if target_y < self.height && target_x < self.width {
-                                let src_idx = x + y * pokemon.image.width;
-                                let ch = pokemon.image.chars[src_idx];
+                                let src_idx = x + y * image.width;
+                                let ch = image.chars[src_idx];
                                 if ch != ' ' {
                                     let idx = target_x + target_y * self.width;
                                     output[idx] = ch;
-                                    let (r, g, b) = pokemon.image.colors[src_idx];
+                                    let (r, g, b) = image.colors[src_idx];
                                     color_buf[idx] = CellColor::Rgb(r, g, b);
                                     zbuffer[idx] = 0.4;
                                 }
                             }
                         }
                     }
+
+                    if self.shiny {
+                        render_sparkles(
+                            image,
+                            grow_start_x,
+                            grow_start_y,
+                            self.sparkle_frame,
+                            output,
+                            color_buf,
+                            zbuffer,
+                            self.width,
+                            self.height,
+                        );
+                    }
                 }
 
                 if self.state == GameState::Absorbing {
                     render_stream(
                         &self.stream_particles,
                         self.capture_frame,
+                        false,
+                        output,
+                        color_buf,
+                        zbuffer,
+                        self.width,
+                        self.height,
+                    );
+                } else if self.state == GameState::Evolving
+                    && let Some(scene) = self.evolution.as_ref()
+                {
+                    render_evolution(
+                        scene,
+                        assets,
+                        self.sprite_origin_for(&scene.into, assets),
+                        output,
+                        color_buf,
+                        zbuffer,
+                        self.width,
+                        self.height,
+                    );
+                } else if self.state == GameState::Breakout && self.capture_frame >= OPEN_FRAMES {
+                    render_stream(
+                        &self.stream_particles,
+                        self.capture_frame - OPEN_FRAMES,
+                        true,
                         output,
                         color_buf,
                         zbuffer,
@@ -708,9 +1907,9 @@ impl SessionState {
                 lz /= l_len;
 
                 let mut phi: f32 = 0.0;
-                while phi < 6.28 {
+                while phi < std::f32::consts::TAU {
                     let mut theta: f32 = 0.0;
-                    while theta < 3.14 {
+                    while theta < std::f32::consts::PI {
                         let ox = theta.sin() * phi.cos();
                         let oy = theta.cos();
                         let oz = theta.sin() * phi.sin();
@@ -730,7 +1929,7 @@ impl SessionState {
                             pixel_color = black;
                             pixel_char = '#';
                         } else if oy < 0.0 {
-                            pixel_color = red;
+                            pixel_color = self.ball.shell_color(ox, oy);
                         } else {
                             pixel_color = white;
                         }
@@ -772,7 +1971,7 @@ impl SessionState {
                                     let dot = x_final * lx + y_final * ly + z_final * lz;
                                     let diffuse = dot.max(0.0);
                                     let rz = 2.0 * dot * z_final - lz;
-                                    let spec = (rz * -1.0).max(0.0).powf(16.0);
+                                    let spec = (-rz).max(0.0).powf(16.0);
                                     let shade = (0.12 + diffuse * 0.9 + spec * 0.6).min(1.0);
 
                                     let mut l_idx =
@@ -792,90 +1991,199 @@ impl SessionState {
             }
         }
 
-        if let Screen::Game = self.screen {
-            if self.star_frame > 0 {
-                render_starburst(
-                    self.width as i32,
-                    self.height as i32,
-                    self.ball_x,
-                    self.ball_y,
-                    output,
-                    color_buf,
-                    zbuffer,
-                    self.star_frame,
-                );
-                self.star_frame = self.star_frame.saturating_sub(1);
+        if let Screen::Game = self.screen
+            && self.star_frame > 0
+        {
+            render_starburst(
+                self.width as i32,
+                self.height as i32,
+                self.ball_x,
+                self.ball_y,
+                output,
+                color_buf,
+                zbuffer,
+                self.star_frame,
+            );
+            self.star_frame = self.star_frame.saturating_sub(1);
+        }
+
+        if let Screen::Pokedex = self.screen
+            && let Some(notice) = self.pokedex_notice.as_ref()
+            && self.pokedex_notice_timer > 0
+        {
+            let row = self.height.saturating_sub(3);
+            let start_x = (self.width.saturating_sub(notice.len())) / 2;
+            for (i, ch) in notice.chars().enumerate() {
+                let x = start_x + i;
+                if x >= self.width || row >= self.height {
+                    continue;
+                }
+                let idx = x + row * self.width;
+                output[idx] = ch;
+                color_buf[idx] = CellColor::Ansi("\x1b[91m");
+                zbuffer[idx] = 0.4;
             }
         }
 
-        if let Screen::Pokedex = self.screen {
-            if let Some(notice) = self.pokedex_notice.as_ref() {
-                if self.pokedex_notice_timer > 0 {
-                    let row = self.height.saturating_sub(3);
-                    let start_x = (self.width.saturating_sub(notice.len())) / 2;
-                    for (i, ch) in notice.chars().enumerate() {
-                        let x = start_x + i;
-                        if x >= self.width || row >= self.height {
-                            continue;
-                        }
-                        let idx = x + row * self.width;
-                        output[idx] = ch;
-                        color_buf[idx] = CellColor::Ansi("\x1b[91m");
-                        zbuffer[idx] = 0.4;
-                    }
+        if matches!(self.screen, Screen::Game | Screen::Name)
+            && let Some(message) = self.caught_message.as_ref()
+            && self.caught_message_timer > 0
+        {
+            let row = self.height.saturating_sub(3);
+            let start_x = (self.width.saturating_sub(message.len())) / 2;
+            for (i, ch) in message.chars().enumerate() {
+                let x = start_x + i;
+                if x >= self.width || row >= self.height {
+                    continue;
                 }
+                let idx = x + row * self.width;
+                output[idx] = ch;
+                color_buf[idx] = CellColor::Ansi(self.caught_message_color);
+                zbuffer[idx] = 0.5;
             }
         }
 
-        if let Screen::Game = self.screen {
-            if let Some(message) = self.caught_message.as_ref() {
-                if self.caught_message_timer > 0 {
-                    let row = self.height.saturating_sub(3);
-                    let start_x = (self.width.saturating_sub(message.len())) / 2;
-                    for (i, ch) in message.chars().enumerate() {
-                        let x = start_x + i;
-                        if x >= self.width || row >= self.height {
-                            continue;
-                        }
-                        let idx = x + row * self.width;
-                        output[idx] = ch;
-                        color_buf[idx] = CellColor::Ansi("\x1b[92m");
-                        zbuffer[idx] = 0.5;
+        if let Screen::Game = self.screen
+            && !self.agent_lines.is_empty()
+            && self.agent_message_timer > 0
+        {
+            let num_lines = self.agent_lines.len();
+            let base_row = self.height.saturating_sub(2 + num_lines);
+            let start_x = 3;
+            for (li, line) in self.agent_lines.iter().enumerate() {
+                let row = base_row + li;
+                if row >= self.height {
+                    continue;
+                }
+                for (i, ch) in line.chars().enumerate() {
+                    let x = start_x + i;
+                    if x >= self.width {
+                        break;
                     }
+                    let idx = x + row * self.width;
+                    output[idx] = ch;
+                    color_buf[idx] = CellColor::Ansi("\x1b[96m");
+                    zbuffer[idx] = 0.9;
                 }
             }
         }
 
-        if let Screen::Game = self.screen {
-            if !self.agent_lines.is_empty() && self.agent_message_timer > 0 {
-                let num_lines = self.agent_lines.len();
-                let base_row = self.height.saturating_sub(2 + num_lines);
-                let start_x = 3;
-                for (li, line) in self.agent_lines.iter().enumerate() {
-                    let row = base_row + li;
-                    if row >= self.height {
-                        continue;
-                    }
-                    for (i, ch) in line.chars().enumerate() {
-                        let x = start_x + i;
-                        if x >= self.width {
-                            break;
-                        }
-                        let idx = x + row * self.width;
-                        output[idx] = ch;
-                        color_buf[idx] = CellColor::Ansi("\x1b[96m");
-                        zbuffer[idx] = 0.9;
-                    }
+        if let Screen::Game = self.screen
+            && self.trainer_name.is_some()
+        {
+            let mut summary = self.inventory.summary();
+            if self.selection_mode == SelectionMode::DailyWeighted {
+                let _ = write!(
+                    summary,
+                    "    ENCOUNTERS LEFT {}/{}",
+                    self.encounters_left(),
+                    self.encounter_limit()
+                );
+            }
+            if !self.encounters_done() {
+                let _ = write!(summary, "    WILD LV{}", self.wild_level);
+            }
+            let _ = write!(
+                summary,
+                "    {} - {}",
+                assets.routes[self.route].name.to_uppercase(),
+                self.conditions().label()
+            );
+            let spectators = self.mirror.receiver_count();
+            if spectators > 0 {
+                let _ = write!(summary, "    SPECTATORS {spectators}");
+            }
+            for (i, ch) in summary.chars().enumerate() {
+                let x = 2 + i;
+                if x >= self.width {
+                    break;
                 }
+                let idx = x + self.width;
+                output[idx] = ch;
+                color_buf[idx] = CellColor::Ansi("\x1b[97m");
+                zbuffer[idx] = 0.9;
+            }
+        }
+
+        if let Screen::Game = self.screen
+            && !self.quests.is_empty()
+        {
+            render_quests(
+                &self.quests,
+                self.login_streak,
+                output,
+                color_buf,
+                zbuffer,
+                self.width,
+                self.height,
+            );
+        }
+
+        if !matches!(self.screen, Screen::Name) && self.chat_timer > 0 {
+            let base_row = self.height.saturating_sub(2 + self.chat_lines.len());
+            let x = self.width.saturating_sub(CHAT_WIDTH + 2);
+            for (i, (line, color)) in self.chat_lines.iter().enumerate() {
+                draw_text(
+                    line,
+                    x,
+                    base_row + i,
+                    color,
+                    output,
+                    color_buf,
+                    zbuffer,
+                    self.width,
+                    self.height,
+                );
             }
         }
 
+        if !matches!(self.screen, Screen::Name)
+            && let Some(toast) = self.toast.as_ref()
+        {
+            let x = self.width.saturating_sub(toast.len() + 2);
+            draw_text(
+                toast,
+                x,
+                0,
+                "\x1b[93m",
+                output,
+                color_buf,
+                zbuffer,
+                self.width,
+                self.height,
+            );
+        }
+
         let prompt = match self.screen {
-            Screen::Name => "enter a unique trainer name to begin catching (q to quit)",
-            Screen::Pokedex => "type a caught number (1-151), or 'back' to return (q to quit)",
-            Screen::PokedexDetail => "type 'back' to return to the pokedex (q to quit)",
+            Screen::Name => {
+                "enter a unique trainer name to begin catching, or 'watch <trainer>' to spectate (q to quit)"
+            }
+            Screen::Spectate => "type 'back' to stop watching (q to quit)",
+            Screen::Badges => "type 'next'/'prev' to page, or 'back' to return (q to quit)",
+            Screen::Map => "type a route number or name to travel there, or 'back' (q to quit)",
+            Screen::Box => {
+                "type 'sort date|level|species|name', 'filter <species|shiny|all>', 'nickname <n> <name>', 'release <n>', 'next'/'prev', or 'back' (q to quit)"
+            }
+            Screen::Leaderboard => {
+                "type 'sort dex|shinies|legendaries', 'friends'/'all', 'friend <trainer>', 'unfriend <trainer>', or 'back' (q to quit)"
+            }
+            Screen::Pokedex => {
+                "type a seen or caught number, 'next'/'prev', a region name, or 'back' to return (q to quit)"
+            }
+            Screen::PokedexDetail => {
+                "type 'next'/'prev' for other entries, 'shiny'/'normal' to switch sprites, or 'back' to return to the pokedex (q to quit)"
+            }
             Screen::Game => {
-                "type 'catch'/'pokedex' or ask a question like 'what is this pokemon?' (q to quit)"
+                "type 'catch', 'throw <ball>', 'battle [pokemon]', 'run', 'trade <trainer>', 'map', 'box', 'online', 'leaderboard', 'badges', 'pokedex' or ask a question (q to quit)"
+            }
+            Screen::Battle => {
+                "type a move number or name, 'switch <pokemon>', 'catch'/'throw <ball>', or 'back' (q to quit)"
+            }
+            Screen::Lobby => {
+                "type 'trade <trainer>' to trade, 'say <msg>' to chat, or 'back' to return (q to quit)"
+            }
+            Screen::Trade => {
+                "type a number to offer that pokemon, 'confirm' to trade, or 'cancel' (q to quit)"
             }
         };
         let prompt_line = format!("command: {} ({})", self.last_cmd, prompt);
@@ -891,6 +2199,20 @@ impl SessionState {
         }
 
         let _ = reset;
+        self.publish_mirror(buffers);
+    }
+
+    /// Shares the frame with spectators, if anyone is watching.
+    fn publish_mirror(&self, buffers: &RenderBuffers) {
+        if !self.allow_spectators || self.mirror.receiver_count() == 0 {
+            return;
+        }
+        self.mirror.send_replace(Some(Arc::new(MirrorFrame {
+            width: self.width,
+            height: self.height,
+            output: buffers.output.clone(),
+            color_buf: buffers.color_buf.clone(),
+        })));
     }
 
     fn compose_frame(&self, buffers: &RenderBuffers) -> String {
@@ -971,1214 +2293,4940 @@ impl SessionState {
         frame
     }
 
-    fn refresh_daily_pokemon(&mut self, assets: &Assets) {
+    async fn pick_random_encounter(&mut self, assets: &Assets) {
+        self.battle = None;
+        self.pokemon_index = pick_pokemon_index(&assets.pokemons, &assets.routes[self.route]);
+        self.shiny = shiny_roll(next_u64(&mut self.rng), self.shiny_odds);
+        let total = assets
+            .pokedex
+            .totals_by_name
+            .get(&self.pokemon(assets).name);
+        self.wild_level = levels::wild_level(total.copied(), next_u64(&mut self.rng));
+        self.mark_seen(assets).await;
+    }
+
+    fn announce_shiny(&mut self, assets: &Assets) {
+        if self.shiny && !self.encounters_done() {
+            let species = display_pokemon_name(&self.pokemon(assets).name);
+            self.show_caught_message(format!("A shiny {species} appeared!"), "\x1b[93m");
+        }
+    }
+
+    async fn refresh_daily_pokemon(&mut self, assets: &Assets) {
         if self.selection_mode != SelectionMode::DailyWeighted {
             return;
         }
         let Some(name) = self.trainer_name.as_ref() else {
             return;
         };
-        let day = current_day_index_est();
+        let day = self.current_day();
         if day == self.daily_key {
             return;
         }
         self.daily_key = day;
-        let legendary_unlocked = legendaries_unlocked(&self.pokedex, &assets.pokedex.names);
-        let seed = daily_seed(name, day);
-        self.pokemon_index =
-            pick_weighted_pokemon(&assets.pokemons, &assets.pokedex, seed, legendary_unlocked);
+        self.encounter_slot = load_encounter_count(name, day).await.unwrap_or(0);
         self.stream_particles.clear();
         self.caught_message = None;
         self.caught_message_timer = 0;
+        self.pick_daily_encounter(assets).await;
+        self.announce_shiny(assets);
     }
-}
-const GEN1_CSV: &str = "sample_images/gen01.csv";
-const POKEDEX_COLS: usize = 15;
-const POKEDEX_ROWS: usize = 11;
-const POKEDEX_CELL_W: usize = 9;
-const POKEDEX_CELL_H: usize = 3;
-
-#[tokio::main]
-async fn main() -> io::Result<()> {
-    init_db().await?;
-    let pokedex = load_pokedex_view().unwrap_or_else(|err| {
-        panic!("failed to load pokedex assets: {err}");
-    });
-    let pokemons = load_pokemon_assets(&pokedex.names, IMG_CHARSET);
-    let assets = Arc::new(Assets {
-        pokemons,
-        arcanine_frames: pokemon::load_arcanine_frames(IMG_CHARSET),
-        pokedex,
-    });
 
-    let selection_mode = selection_mode_from_env();
-    let port = server_port_from_env(selection_mode);
-    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
-    loop {
-        let (stream, _) = listener.accept().await?;
-        let _ = stream.set_nodelay(true);
-        let assets = Arc::clone(&assets);
-        tokio::spawn(async move {
-            let _ = run_session(stream, assets).await;
-        });
+    /// Daily encounters are drawn from the trainer's seed and slot number, under
+    /// the conditions and on the route the slot was first revealed on, so a
+    /// reconnect or a trip lands on the same species (and shininess) until the
+    /// slot is resolved.
+    async fn pick_daily_encounter(&mut self, assets: &Assets) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        if self.encounters_done() {
+            self.shiny = false;
+            return;
+        }
+        self.battle = None;
+        let current = assets.routes[self.route].id.clone();
+        let (revealed, route) =
+            reveal_encounter(&name, self.daily_key, self.encounter_slot, &current)
+                .await
+                .unwrap_or_else(|_| (unix_now(), current));
+        let route = assets
+            .routes
+            .iter()
+            .position(|candidate| candidate.id == route)
+            .unwrap_or(self.route);
+        let legendary_unlocked = legendaries_unlocked(&self.pokedex, &assets.pokedex);
+        let seed = encounter_seed(daily_seed(&name, self.daily_key), self.encounter_slot);
+        self.pokemon_index = pick_weighted_pokemon(
+            &assets.pokemons,
+            &assets.pokedex,
+            seed,
+            legendary_unlocked,
+            Conditions::at(&self.zone, revealed),
+            &assets.routes[route],
+        );
+        self.shiny = shiny_roll(seed, self.shiny_odds);
+        let total = assets
+            .pokedex
+            .totals_by_name
+            .get(&self.pokemon(assets).name);
+        self.wild_level = levels::wild_level(total.copied(), level_roll(seed));
+        self.mark_seen(assets).await;
     }
-}
-
-async fn run_session(stream: TcpStream, assets: Arc<Assets>) -> io::Result<()> {
-    let (read_half, write_half) = stream.into_split();
-    let mut reader = BufReader::new(read_half);
 
-    let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<String>();
-    let reader_task = tokio::spawn(async move {
-        let mut line = String::new();
-        loop {
-            line.clear();
-            match reader.read_line(&mut line).await {
-                Ok(0) => {
-                    let _ = cmd_tx.send(String::from("__disconnect__"));
-                    break;
-                }
-                Ok(_) => {
-                    let _ = cmd_tx.send(line.clone());
-                }
-                Err(_) => {
-                    let _ = cmd_tx.send(String::from("__disconnect__"));
-                    break;
-                }
-            }
+    /// Brings out the next wild Pokemon for the server's selection mode.
+    async fn next_encounter(&mut self, assets: &Assets) {
+        match self.selection_mode {
+            SelectionMode::DailyWeighted => self.pick_daily_encounter(assets).await,
+            SelectionMode::RandomPerSession => self.pick_random_encounter(assets).await,
         }
-    });
+    }
 
-    let width = env_usize("POKESTREAM_WIDTH").unwrap_or(140);
-    let height = env_usize("POKESTREAM_HEIGHT").unwrap_or(40);
-    let color_mode = color_mode_from_env();
-    let selection_mode = selection_mode_from_env();
-    let mut session = SessionState::new(width, height, color_mode, selection_mode, &assets);
-    let mut buffers = RenderBuffers::new(width, height);
+    fn encounters_done(&self) -> bool {
+        self.selection_mode == SelectionMode::DailyWeighted
+            && self.encounter_slot >= self.encounter_limit()
+    }
 
-    let (out_tx, out_rx) = mpsc::channel::<OutputMessage>(2);
-    let writer_task = tokio::spawn(async move {
-        let mut write_half = write_half;
-        let mut out_rx = out_rx;
-        while let Some(msg) = out_rx.recv().await {
-            match msg {
-                OutputMessage::Bytes(bytes) => {
-                    if write_half.write_all(&bytes).await.is_err() {
-                        break;
-                    }
-                }
-                OutputMessage::Close { send_bye } => {
-                    if send_bye {
-                        let _ = close_session(&mut write_half).await;
-                    } else {
-                        let _ = cleanup_terminal(&mut write_half).await;
-                        let _ = write_half.shutdown().await;
-                    }
-                    break;
-                }
-            }
+    fn encounter_limit(&self) -> usize {
+        self.encounters_per_day + self.bonus_encounters
+    }
+
+    fn encounters_left(&self) -> usize {
+        self.encounter_limit().saturating_sub(self.encounter_slot)
+    }
+
+    /// Persists the outcome of the current daily encounter and moves the slot on.
+    /// The next species is picked separately so the catch animation can finish.
+    async fn resolve_encounter(&mut self, assets: &Assets, outcome: &str) {
+        if self.selection_mode != SelectionMode::DailyWeighted || self.encounters_done() {
+            return;
         }
-    });
+        if let Some(name) = self.trainer_name.as_ref() {
+            let species = self.pokemon(assets).name.clone();
+            let _ =
+                save_encounter(name, self.daily_key, self.encounter_slot, &species, outcome).await;
+        }
+        self.encounter_slot += 1;
+    }
 
-    let _ = out_tx
-        .send(OutputMessage::Bytes(
-            b"\x1b[?1049h\x1b[?7l\x1b[2J\x1b[H\x1b[?25l".to_vec(),
-        ))
-        .await;
-    let _ = out_tx
-        .send(OutputMessage::Bytes(b"\x1b[2J\x1b[H\x1b[?25l".to_vec()))
-        .await;
+    /// Ends the current encounter without a capture (running away or knocking
+    /// the wild Pokemon out) and brings out the next one.
+    async fn skip_encounter(&mut self, assets: &Assets, outcome: &str, message: String) {
+        if self.encounters_done() {
+            return;
+        }
+        self.resolve_encounter(assets, outcome).await;
+        self.next_encounter(assets).await;
+        if !self.encounters_done() {
+            self.show_caught_message(message, "\x1b[97m");
+        }
+        self.announce_shiny(assets);
+    }
 
-    let frame_interval = frame_interval_from_env();
-    let mut ticker = time::interval(frame_interval);
-    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
-    let mut last_frame_hash: u64 = 0;
+    async fn handle_throw(&mut self, ball: Option<BallKind>) {
+        if self.state != GameState::Idle {
+            return;
+        }
+        if self.encounters_done() {
+            self.show_caught_message("No more wild Pokemon today!".to_string(), "\x1b[91m");
+            return;
+        }
+        match ball {
+            Some(ball) => self.throw_ball(ball).await,
+            None => self.show_caught_message(
+                "Unknown ball! Try poke, great, ultra or master.".to_string(),
+                "\x1b[91m",
+            ),
+        }
+    }
 
-    loop {
-        tokio::select! {
-            maybe_cmd = cmd_rx.recv() => {
-                let cmd = match maybe_cmd {
-                    Some(cmd) => cmd,
-                    None => String::from("__disconnect__"),
+    /// Opens the battle screen against the current wild Pokemon, or swaps the
+    /// trainer's Pokemon if a battle is already underway. Without a name the
+    /// strongest caught species that has not fainted this encounter is sent out.
+    fn start_battle(&mut self, assets: &Assets, requested: Option<&str>) {
+        if self.state != GameState::Idle || self.encounters_done() {
+            return;
+        }
+        let fainted = self
+            .battle
+            .as_ref()
+            .map(|battle| battle.fainted.as_slice())
+            .unwrap_or(&[]);
+        let partner = match requested {
+            Some(raw) => {
+                let name = raw.replace(' ', "-");
+                let Some(capture) = self
+                    .captures
+                    .iter()
+                    .filter(|capture| capture.species == name)
+                    .max_by_key(|capture| capture.level)
+                else {
+                    self.battle_notice(format!(
+                        "You haven't caught {}!",
+                        display_pokemon_name(&name)
+                    ));
+                    return;
                 };
-                match session.handle_command(&cmd, &assets).await {
-                    CommandAction::Exit => {
-                        reader_task.abort();
-                        let _ = reader_task.await;
-                        let _ = out_tx.send(OutputMessage::Close { send_bye: true }).await;
-                        break;
-                    }
-                    CommandAction::Disconnect => {
-                        reader_task.abort();
-                        let _ = reader_task.await;
-                        let _ = out_tx.send(OutputMessage::Close { send_bye: false }).await;
-                        break;
-                    }
-                    CommandAction::None => {}
+                if fainted.contains(&name) {
+                    self.battle_notice(format!(
+                        "{} is too tired to battle!",
+                        display_pokemon_name(&name)
+                    ));
+                    return;
                 }
-
-                if out_tx.capacity() > 0 {
-                    session.render(&assets, &mut buffers);
-                    let frame = session.compose_frame(&buffers);
-                    let hash = fast_hash(frame.as_bytes());
-                    if hash != last_frame_hash {
-                        last_frame_hash = hash;
-                        let _ = out_tx.try_send(OutputMessage::Bytes(frame.into_bytes()));
+                capture.clone()
+            }
+            None => {
+                let best = self
+                    .captures
+                    .iter()
+                    .filter(|capture| !fainted.contains(&capture.species))
+                    .filter(|capture| assets.pokedex.stats_by_name.contains_key(&capture.species))
+                    .max_by_key(|capture| {
+                        (
+                            capture.level,
+                            assets.pokedex.totals_by_name.get(&capture.species).copied(),
+                            std::cmp::Reverse(capture.species.clone()),
+                        )
+                    });
+                match best {
+                    Some(capture) => capture.clone(),
+                    None if self.captures.is_empty() => {
+                        self.battle_notice("You have no Pokemon to battle with!".to_string());
+                        return;
+                    }
+                    None => {
+                        self.battle_notice("Your Pokemon are too tired to battle!".to_string());
+                        return;
                     }
                 }
             }
-            _ = ticker.tick() => {
-                if out_tx.capacity() == 0 {
-                    continue;
-                }
-                session.update(&assets).await;
-                session.render(&assets, &mut buffers);
-                let frame = session.compose_frame(&buffers);
-                let hash = fast_hash(frame.as_bytes());
-                if hash != last_frame_hash {
-                    last_frame_hash = hash;
-                    let _ = out_tx.try_send(OutputMessage::Bytes(frame.into_bytes()));
+        };
+        let Some(player) = battler_for(&assets.pokedex, &partner.species, partner.level) else {
+            self.battle_notice(format!(
+                "No battle data for {}.",
+                display_pokemon_name(&partner.species)
+            ));
+            return;
+        };
+        let label = display_pokemon_name(&partner.species);
+        self.battle_partner = Some(partner.id);
+        match self.battle.as_mut() {
+            Some(battle) => {
+                if battle.player.name != partner.species || battle.player.fainted() {
+                    battle.switch_player(player, &label);
                 }
             }
+            None => {
+                let wild_name = &self.pokemon(assets).name;
+                let Some(wild) = battler_for(&assets.pokedex, wild_name, self.wild_level) else {
+                    self.battle_notice(format!(
+                        "No battle data for {}.",
+                        display_pokemon_name(wild_name)
+                    ));
+                    return;
+                };
+                self.battle = Some(Battle::new(player, wild, &label));
+            }
         }
+        self.battle_flash = 0;
+        self.screen = Screen::Battle;
     }
 
-    let _ = writer_task.await;
-    Ok(())
-}
-
-async fn cleanup_terminal(write_half: &mut OwnedWriteHalf) -> io::Result<()> {
-    write_half
-        .write_all(b"\x1b[0m\x1b[?25h\x1b[?7h\x1b[?1049l")
-        .await
-}
-
-async fn close_session(write_half: &mut OwnedWriteHalf) -> io::Result<()> {
-    cleanup_terminal(write_half).await?;
-    write_half.write_all(b"bye\r\n").await?;
-    write_half.shutdown().await
-}
-
-fn build_stream_particles(
-    pokemon: &PokemonAsset,
-    start_x: usize,
-    start_y: usize,
-    target_x: f32,
-    target_y: f32,
-) -> Vec<StreamParticle> {
-    let mut particles = Vec::new();
-    let mut idx: u16 = 0;
-    for y in 0..pokemon.image.height {
-        for x in 0..pokemon.image.width {
-            let src_idx = x + y * pokemon.image.width;
-            let ch = pokemon.image.chars[src_idx];
-            if ch == ' ' {
-                continue;
-            }
-            let color = pokemon.image.colors[src_idx];
-            particles.push(StreamParticle {
-                x0: (start_x + x) as f32,
-                y0: (start_y + y) as f32,
-                x1: target_x,
-                y1: target_y,
-                ch,
-                color,
-                start_frame: idx % 12,
-            });
-            idx = idx.wrapping_add(1);
+    /// Stores the Pokemon that was just caught as a new capture at its wild level.
+    async fn record_capture(&mut self, assets: &Assets) {
+        let Some(name) = self.trainer_name.as_ref() else {
+            return;
+        };
+        let mut capture = Capture {
+            id: 0,
+            species: self.pokemon(assets).name.clone(),
+            level: self.wild_level,
+            xp: levels::xp_for_level(self.wild_level),
+            caught_at: unix_now(),
+            shiny: self.shiny,
+            nickname: None,
+            ball: Some(self.ball),
+            session_id: self.play_session,
+        };
+        if let Ok(id) = insert_capture(name, &capture).await {
+            capture.id = id;
+            self.captures.push(capture);
         }
     }
-    particles
-}
 
-fn render_stream(
-    particles: &[StreamParticle],
-    frame: u16,
-    output: &mut [char],
-    color_buf: &mut [CellColor],
-    zbuffer: &mut [f32],
-    width: usize,
-    height: usize,
-) {
-    for particle in particles {
-        if frame < particle.start_frame {
-            continue;
+    /// How the trainer's first capture of `species` still in the box was
+    /// caught, with a count when there are more, or when it was first seen if
+    /// it hasn't been caught. `None` if never seen.
+    fn catch_summary(&self, species: &str) -> Option<String> {
+        if !self.pokedex.contains(species) {
+            let seen_at = self.seen.get(species)?;
+            return Some(format!(
+                "Seen {} - not caught yet",
+                self.zone.format_date(*seen_at)
+            ));
         }
-        let t = (frame - particle.start_frame) as f32 / ABSORB_FRAMES as f32;
-        let t = t.min(1.0);
-        let x = particle.x0 + (particle.x1 - particle.x0) * t;
-        let y = particle.y0 + (particle.y1 - particle.y0) * t;
-        let xi = x.round() as i32;
-        let yi = y.round() as i32;
-        if xi < 0 || yi < 0 || xi >= width as i32 || yi >= height as i32 {
-            continue;
+        let mut held: Vec<&Capture> = self
+            .captures
+            .iter()
+            .filter(|capture| capture.species == species)
+            .collect();
+        held.sort_by_key(|capture| (capture.caught_at, capture.id));
+        let Some(first) = held.first() else {
+            return Some("Caught before - none left in your box".to_string());
+        };
+        let mut summary = pc::catch_record(first, &self.zone);
+        if let Some(nickname) = first.nickname.as_ref() {
+            summary = format!("{nickname}: {summary}");
         }
-        let idx = (xi + yi * width as i32) as usize;
-        if 0.6 > zbuffer[idx] {
-            zbuffer[idx] = 0.6;
-            output[idx] = particle.ch;
-            let (r, g, b) = particle.color;
-            color_buf[idx] = CellColor::Rgb(r, g, b);
+        if held.len() > 1 {
+            summary.push_str(&format!(" - {} in your box", held.len()));
         }
+        Some(summary)
     }
-}
 
-fn render_starburst(
-    width: i32,
-    height: i32,
-    ball_x: f32,
-    ball_y: f32,
-    output: &mut [char],
-    color_buf: &mut [CellColor],
-    zbuffer: &mut [f32],
-    frame: u16,
-) {
-    let center_x = (width as f32 / 2.0 + ball_x).round() as i32;
-    let center_y = (height as f32 / 2.0 + ball_y).round() as i32 - 10;
-    let t = frame as f32 / STAR_FRAMES as f32;
-    let spread = (1.0 - t) * 11.0;
-    let stars = [
-        (0.0, -1.0),
-        (1.0, 0.0),
-        (-1.0, 0.0),
-        (0.0, 1.0),
-        (0.8, -0.6),
-        (-0.8, -0.6),
-        (0.8, 0.6),
-        (-0.8, 0.6),
-        (1.2, -0.2),
-        (-1.2, -0.2),
-        (1.2, 0.2),
-        (-1.2, 0.2),
-        (0.0, -1.6),
-        (0.0, -2.0),
-        (0.0, -2.4),
-        (0.4, -1.8),
-        (-0.4, -1.8),
-    ];
-    for (dx, dy) in stars {
-        let x = center_x + (dx * spread) as i32;
-        let y = center_y + (dy * spread) as i32;
-        if x < 0 || y < 0 || x >= width || y >= height {
-            continue;
+    /// Records a newly brought-out wild Pokemon as seen the first time its
+    /// species appears. The encounter picks call it, so nothing is marked once
+    /// the day's encounters are done.
+    async fn mark_seen(&mut self, assets: &Assets) {
+        let Some(name) = self.trainer_name.as_ref() else {
+            return;
+        };
+        let species = &self.pokemon(assets).name;
+        if self.seen.contains_key(species) {
+            return;
         }
-        let idx = (x + y * width) as usize;
-        output[idx] = '*';
-        color_buf[idx] = CellColor::Ansi("\x1b[93m");
-        zbuffer[idx] = 0.9;
+        let now = unix_now();
+        self.seen.insert(species.clone(), now);
+        let _ = save_seen(name, species, now).await;
     }
-}
 
-fn color_mode_from_env() -> ColorMode {
-    if let Ok(mode) = env::var("POKESTREAM_COLOR") {
-        let mode = mode.to_lowercase();
-        if mode == "truecolor" || mode == "24bit" {
-            return ColorMode::Truecolor;
-        }
-        if mode == "ansi256" || mode == "256" {
-            return ColorMode::Ansi256;
-        }
-        if mode == "mono" || mode == "none" {
-            return ColorMode::Mono;
-        }
-    }
-    if let Ok(ct) = env::var("COLORTERM") {
-        let ct = ct.to_lowercase();
-        if ct.contains("truecolor") || ct.contains("24bit") {
-            return ColorMode::Truecolor;
-        }
+    /// The capture numbered `pick` (from 1) in the box's current order.
+    fn box_pick(&self, pick: &str) -> Option<Capture> {
+        let index = pick.trim().parse::<usize>().ok()?.checked_sub(1)?;
+        pc::arrange(&self.captures, self.box_sort, &self.box_filter)
+            .get(index)
+            .map(|capture| (*capture).clone())
     }
-    if let Ok(term) = env::var("TERM") {
-        let term = term.to_lowercase();
-        if term.contains("truecolor") || term.contains("24bit") || term.contains("direct") {
-            return ColorMode::Truecolor;
+
+    /// Sets or, with no name, clears a capture's nickname. `args` keeps the
+    /// trainer's casing.
+    async fn nickname_capture(&mut self, args: &str) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let (pick, nickname) = args.trim().split_once(' ').unwrap_or((args.trim(), ""));
+        let Some(capture) = self.box_pick(pick) else {
+            self.box_notice = Some("Type 'nickname <number> <name>'.".to_string());
+            return;
+        };
+        let nickname = if nickname.trim().is_empty() {
+            None
+        } else {
+            match pc::sanitize_nickname(nickname) {
+                Some(nickname) => Some(nickname),
+                None => {
+                    self.box_notice = Some(format!(
+                        "Nicknames are up to {} plain characters.",
+                        pc::NICKNAME_LEN
+                    ));
+                    return;
+                }
+            }
+        };
+        if save_nickname(&name, capture.id, nickname.as_deref())
+            .await
+            .is_err()
+        {
+            self.box_notice = Some("Couldn't save that nickname.".to_string());
+            return;
         }
-        if term.contains("256color") {
-            return ColorMode::Ansi256;
+        let species = display_pokemon_name(&capture.species);
+        self.box_notice = Some(match &nickname {
+            Some(nickname) => format!("{species} is now called {nickname}."),
+            None => format!("{species} no longer has a nickname."),
+        });
+        if let Some(capture) = self.captures.iter_mut().find(|c| c.id == capture.id) {
+            capture.nickname = nickname;
         }
     }
-    ColorMode::Ansi256
-}
 
-fn env_usize(key: &str) -> Option<usize> {
-    env::var(key).ok().and_then(|val| val.parse::<usize>().ok())
-}
-
-fn selection_mode_from_env() -> SelectionMode {
-    if let Ok(mode) = env::var("POKESTREAM_MODE") {
-        let mode = mode.to_lowercase();
-        if matches!(
-            mode.as_str(),
-            "random" | "dev_random" | "random_per_session"
-        ) {
-            return SelectionMode::RandomPerSession;
+    /// Asks the trainer to confirm releasing a capture.
+    fn ask_release(&mut self, pick: &str) {
+        let Some(capture) = self.box_pick(pick) else {
+            self.box_notice = Some("Type 'release <number>'.".to_string());
+            return;
+        };
+        let label = pc::display_name(&capture);
+        if self.trade.as_ref().is_some_and(|trade| {
+            trade
+                .mine
+                .as_ref()
+                .is_some_and(|o| o.capture_id == capture.id)
+        }) {
+            self.box_notice = Some(format!("{label} is offered in a trade."));
+            return;
         }
-    }
-    if let Ok(raw) = env::var("POKESTREAM_RANDOM") {
-        let raw = raw.to_lowercase();
-        if matches!(raw.as_str(), "1" | "true" | "yes") {
-            return SelectionMode::RandomPerSession;
+        if self.battle.is_some() && self.battle_partner == Some(capture.id) {
+            self.box_notice = Some(format!("{label} is in the middle of a battle."));
+            return;
         }
+        self.box_release = Some(capture.id);
+        self.box_notice = Some(format!(
+            "Release {label} (Lv{})? Type 'confirm' to say goodbye.",
+            capture.level
+        ));
     }
-    SelectionMode::DailyWeighted
-}
 
-fn server_port_from_env(selection_mode: SelectionMode) -> u16 {
-    if let Ok(raw) = env::var("POKESTREAM_PORT") {
-        if let Ok(port) = raw.parse::<u16>() {
-            return port;
+    /// Lets a capture go. The species stays in the Pokedex.
+    async fn release_capture(&mut self, id: i64) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let Some(index) = self.captures.iter().position(|c| c.id == id) else {
+            return;
+        };
+        match delete_capture(&name, id).await {
+            Ok(true) => {
+                let capture = self.captures.remove(index);
+                if self.battle_partner == Some(id) {
+                    self.battle_partner = None;
+                }
+                self.box_notice = Some(format!(
+                    "Bye-bye, {}! It stays in your Pokedex.",
+                    pc::display_name(&capture)
+                ));
+            }
+            Ok(false) => {
+                self.captures.remove(index);
+                self.box_notice = Some("That Pokemon is no longer in your box.".to_string());
+            }
+            Err(_) => {
+                self.box_notice = Some("Couldn't release that Pokemon.".to_string());
+            }
         }
     }
-    match selection_mode {
-        SelectionMode::RandomPerSession => 8081,
-        SelectionMode::DailyWeighted => 8080,
-    }
-}
 
-fn frame_interval_from_env() -> Duration {
-    if let Ok(raw) = env::var("POKESTREAM_FPS") {
-        if let Ok(fps) = raw.parse::<u64>() {
-            if fps > 0 {
-                let ms = (1000 / fps).max(10);
-                return Duration::from_millis(ms);
-            }
+    /// Gives the Pokemon that battled this encounter its experience, queueing
+    /// an evolution when it crosses a level threshold. Returns a level-up
+    /// message, if any.
+    async fn award_battle_xp(&mut self, assets: &Assets) -> Option<String> {
+        let battle = self.battle.as_ref()?;
+        if battle.player.fainted() {
+            return None;
         }
-    }
-    if let Ok(raw) = env::var("POKESTREAM_FRAME_MS") {
-        if let Ok(ms) = raw.parse::<u64>() {
-            if ms > 0 {
-                return Duration::from_millis(ms);
+        let wild_total = assets
+            .pokedex
+            .totals_by_name
+            .get(&battle.wild.name)
+            .copied();
+        let amount = levels::xp_reward(wild_total, battle.wild.level);
+        let partner_id = self.battle_partner?;
+        let name = self.trainer_name.clone()?;
+        let capture = self.captures.iter_mut().find(|c| c.id == partner_id)?;
+        let gained = capture.gain_xp(amount);
+        let mut message = None;
+        if gained > 0 {
+            message = Some(format!(
+                "{} grew to Lv{}!",
+                display_pokemon_name(&capture.species),
+                capture.level
+            ));
+            if let Some(into) = assets
+                .pokedex
+                .level_evolution(&capture.species, capture.level)
+            {
+                let from = std::mem::replace(&mut capture.species, into.to_string());
+                self.pending_evolutions.push((from, into.to_string()));
             }
         }
+        let _ = update_capture(&name, capture).await;
+        let evolved = capture.species.clone();
+        if self.pokedex.insert(evolved) {
+            let _ = save_pokedex(&name, &self.pokedex).await;
+            self.record_progress(assets).await;
+        }
+        message
     }
-    Duration::from_millis(30)
-}
 
-fn current_day_index_est() -> i64 {
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default();
-    let secs = now.as_secs() as i64;
-    let shifted = secs - 5 * 3600;
-    shifted.div_euclid(86_400)
-}
-
-fn daily_seed(trainer: &str, day_index: i64) -> u64 {
-    let mut hash: u64 = 0xcbf29ce484222325;
-    for b in trainer.as_bytes() {
-        hash ^= *b as u64;
-        hash = hash.wrapping_mul(0x100000001b3);
+    fn sprite_origin_for(&self, species: &str, assets: &Assets) -> (usize, usize) {
+        match find_pokemon_asset(assets, species) {
+            Some(asset) => self.sprite_origin(asset),
+            None => self.sprite_origin(self.pokemon(assets)),
+        }
     }
-    for b in day_index.to_le_bytes() {
-        hash ^= b as u64;
-        hash = hash.wrapping_mul(0x100000001b3);
+
+    /// Plays the evolution on the catch screen; species without sprites skip
+    /// straight to the message.
+    fn start_evolution(&mut self, assets: &Assets, from: String, into: String) {
+        let (Some(old), Some(new)) = (
+            find_pokemon_asset(assets, &from),
+            find_pokemon_asset(assets, &into),
+        ) else {
+            self.show_caught_message(
+                format!(
+                    "{} evolved into {}!",
+                    display_pokemon_name(&from),
+                    display_pokemon_name(&into)
+                ),
+                "\x1b[96m",
+            );
+            return;
+        };
+        let (old_x, old_y) = self.sprite_origin(old);
+        let (new_x, new_y) = self.sprite_origin(new);
+        let center_x = (new_x + new.image.width / 2) as f32;
+        let center_y = (new_y + new.image.height / 2) as f32;
+        let message = format!("What? {} is evolving!", display_pokemon_name(&from));
+        self.evolution = Some(EvolutionScene {
+            old_particles: build_stream_particles(&old.image, old_x, old_y, center_x, center_y),
+            new_particles: build_stream_particles(&new.image, new_x, new_y, center_x, center_y),
+            from,
+            into,
+            frame: 0,
+        });
+        self.state = GameState::Evolving;
+        self.show_caught_message(message, "\x1b[96m");
+        self.caught_message_timer = EVOLVE_STREAM_FRAMES * 2;
     }
-    hash
-}
 
-fn next_u64(state: &mut u64) -> u64 {
-    let mut x = *state;
-    x ^= x >> 12;
-    x ^= x << 25;
-    x ^= x >> 27;
-    *state = x;
-    x.wrapping_mul(0x2545F4914F6CDD1D)
-}
+    /// Battle setup errors go to the battle log while the battle screen is open.
+    fn battle_notice(&mut self, message: String) {
+        match self.battle.as_mut() {
+            Some(battle) if matches!(self.screen, Screen::Battle) => battle.push_log(message),
+            _ => self.show_caught_message(message, "\x1b[91m"),
+        }
+    }
 
-fn is_legendary(name: &str) -> bool {
-    matches!(name, "articuno" | "zapdos" | "moltres" | "mewtwo" | "mew")
-}
+    async fn battle_turn(&mut self, assets: &Assets, move_idx: usize) {
+        let Some(battle) = self.battle.as_mut() else {
+            return;
+        };
+        if battle.player.fainted() || battle.wild.fainted() {
+            return;
+        }
+        let player_label = display_pokemon_name(&battle.player.name);
+        let wild_label = format!("Wild {}", display_pokemon_name(&battle.wild.name));
+        let rng = &mut self.rng;
+        let outcome = battle.play_turn(move_idx, (&player_label, &wild_label), || roll_unit(rng));
+        self.battle_flash = 12;
+        match outcome {
+            Outcome::Ongoing => {}
+            Outcome::Won => {
+                self.screen = Screen::Game;
+                let mut message = format!("{wild_label} fainted!");
+                if let Some(level_up) = self.award_battle_xp(assets).await {
+                    message.push_str("  ");
+                    message.push_str(&level_up);
+                }
+                self.record_quest(assets, quests::Event::WonBattle).await;
+                self.skip_encounter(assets, "fainted", message).await;
+            }
+            Outcome::Lost => {
+                self.screen = Screen::Game;
+                self.show_caught_message(format!("{player_label} fainted!"), "\x1b[91m");
+            }
+        }
+    }
 
-fn is_starter(name: &str) -> bool {
-    matches!(
-        name,
-        "bulbasaur" | "charmander" | "squirtle" | "pikachu" | "eevee"
-    )
-}
+    /// Captures in the order the trade screen numbers them.
+    fn trade_candidates(&self) -> Vec<&Capture> {
+        let mut list: Vec<&Capture> = self.captures.iter().collect();
+        list.sort_by(|a, b| a.species.cmp(&b.species).then(b.level.cmp(&a.level)));
+        list
+    }
 
-fn legendaries_unlocked(pokedex: &HashSet<String>, names: &[String]) -> bool {
-    for name in names {
-        if name.is_empty() || is_legendary(name) {
-            continue;
+    /// Sends a trade request, or accepts one if that trainer already asked.
+    fn request_trade(&mut self, partner: &str) {
+        let Some(me) = self.trainer_name.clone() else {
+            return;
+        };
+        if self.state != GameState::Idle {
+            return;
         }
-        if !pokedex.contains(name) {
-            return false;
+        let Some(partner) = sanitize_trainer_name(partner) else {
+            self.show_caught_message("That's not a trainer name.".to_string(), "\x1b[91m");
+            return;
+        };
+        if partner == me {
+            self.show_caught_message("You can't trade with yourself!".to_string(), "\x1b[91m");
+            return;
+        }
+        if self.captures.is_empty() {
+            self.show_caught_message("You have no Pokemon to trade!".to_string(), "\x1b[91m");
+            return;
+        }
+        if !sessions::is_online(&partner) {
+            self.show_caught_message(format!("{partner} isn't online."), "\x1b[91m");
+            return;
         }
+        let stage = if self.trade_requests.contains(&partner) {
+            self.trade_requests.retain(|name| *name != partner);
+            sessions::send(&partner, SessionEvent::TradeAccept { from: me });
+            Stage::Choosing
+        } else {
+            sessions::send(&partner, SessionEvent::TradeRequest { from: me });
+            Stage::Requested
+        };
+        self.trade = Some(Trade::new(&partner, stage));
+        self.screen = Screen::Trade;
     }
-    true
-}
 
-fn pokemon_weight(name: &str, total: Option<u16>, legendary_unlocked: bool) -> u32 {
-    if is_legendary(name) && !legendary_unlocked {
-        return 0;
+    /// Backs out of the current trade, telling the partner.
+    fn cancel_trade(&mut self, message: String) {
+        let Some(trade) = self.trade.take() else {
+            return;
+        };
+        if let Some(me) = self.trainer_name.clone() {
+            sessions::send(&trade.partner, SessionEvent::TradeCancel { from: me });
+        }
+        self.screen = Screen::Game;
+        self.show_caught_message(message, "\x1b[97m");
     }
-    let total = total.unwrap_or(350) as i32;
-    let mut weight = (600 - total).clamp(20, 500) as f32;
 
-    if total >= 500 {
-        weight *= 0.35;
-    } else if total >= 450 {
-        weight *= 0.55;
-    } else if total >= 400 {
-        weight *= 0.75;
-    } else if total <= 300 {
-        weight *= 1.25;
+    fn offer_trade(&mut self, pick: &str) {
+        let candidates = self.trade_candidates();
+        let name = pick.replace(' ', "-");
+        let chosen = match pick.parse::<usize>() {
+            Ok(num) => num.checked_sub(1).and_then(|idx| candidates.get(idx)),
+            Err(_) => candidates.iter().find(|capture| capture.species == name),
+        };
+        let offer = chosen.map(|capture| TradeOffer {
+            capture_id: capture.id,
+            species: capture.species.clone(),
+            level: capture.level,
+        });
+        let (Some(me), Some(trade)) = (self.trainer_name.clone(), self.trade.as_mut()) else {
+            return;
+        };
+        if trade.stage != Stage::Choosing {
+            return;
+        }
+        let Some(offer) = offer else {
+            trade.notice = Some(format!("You don't have {pick} to offer."));
+            return;
+        };
+        if trade.confirmed {
+            trade.notice = Some("You already confirmed. 'cancel' to back out.".to_string());
+            return;
+        }
+        trade.notice = None;
+        trade.set_mine(Some(offer.clone()));
+        sessions::send(
+            &trade.partner,
+            SessionEvent::TradeOffer {
+                from: me,
+                offer: Some(offer),
+            },
+        );
     }
 
-    if is_starter(name) {
-        weight *= 0.8;
+    async fn confirm_trade(&mut self, assets: &Assets) {
+        let (Some(me), Some(trade)) = (self.trainer_name.clone(), self.trade.as_mut()) else {
+            return;
+        };
+        let Some((mine, theirs)) = trade.offer_ids() else {
+            trade.notice = Some("Both trainers need to offer a Pokemon first.".to_string());
+            return;
+        };
+        trade.notice = None;
+        trade.confirmed = true;
+        sessions::send(
+            &trade.partner,
+            SessionEvent::TradeConfirm {
+                from: me,
+                mine,
+                theirs,
+            },
+        );
+        self.try_swap(assets).await;
     }
 
-    if is_legendary(name) {
-        weight *= 0.05;
+    /// Once both sides have confirmed, one of them swaps the captures in the
+    /// database and reports back to the other.
+    async fn try_swap(&mut self, assets: &Assets) {
+        let Some(me) = self.trainer_name.clone() else {
+            return;
+        };
+        let Some(trade) = self.trade.as_ref() else {
+            return;
+        };
+        if !trade.ready() || !trade.runs_swap(&me) {
+            return;
+        }
+        let (Some(mine), Some(theirs)) = (trade.mine.as_ref(), trade.theirs.as_ref()) else {
+            return;
+        };
+        let ok = save_trade(
+            &me,
+            mine,
+            assets.pokedex.trade_evolution(&mine.species),
+            &trade.partner,
+            theirs,
+            assets.pokedex.trade_evolution(&theirs.species),
+        )
+        .await
+        .is_ok();
+        sessions::send(
+            &trade.partner,
+            SessionEvent::TradeComplete {
+                from: me.clone(),
+                ok,
+            },
+        );
+        self.finish_trade(assets, ok).await;
     }
 
-    weight.max(1.0) as u32
-}
-
-fn pick_weighted_pokemon(
-    pokemons: &[PokemonAsset],
-    pokedex_view: &PokedexView,
-    seed: u64,
-    legendary_unlocked: bool,
-) -> usize {
-    let mut weights = Vec::with_capacity(pokemons.len());
-    for (idx, pokemon) in pokemons.iter().enumerate() {
-        let total = pokedex_view.totals_by_name.get(&pokemon.name).copied();
-        let weight = pokemon_weight(&pokemon.name, total, legendary_unlocked);
-        if weight > 0 {
-            weights.push((idx, weight));
+    /// Reloads captures and the Pokedex after a swap, queueing the evolution
+    /// of a received Pokemon that evolves by trade.
+    async fn finish_trade(&mut self, assets: &Assets, ok: bool) {
+        let trade = self.trade.take();
+        if ok {
+            self.reload_after_trade(assets).await;
+        }
+        if matches!(self.screen, Screen::Trade) {
+            self.screen = Screen::Game;
+        }
+        let Some(trade) = trade else {
+            return;
+        };
+        if !ok {
+            self.show_caught_message("The trade fell through.".to_string(), "\x1b[91m");
+            return;
+        }
+        let (Some(mine), Some(theirs)) = (trade.mine, trade.theirs) else {
+            return;
+        };
+        self.show_caught_message(
+            format!(
+                "Traded {} for {}'s {}!",
+                display_pokemon_name(&mine.species),
+                trade.partner,
+                display_pokemon_name(&theirs.species)
+            ),
+            "\x1b[92m",
+        );
+        if let Some(received) = self.captures.iter().find(|c| c.id == theirs.capture_id)
+            && received.species != theirs.species
+        {
+            self.pending_evolutions
+                .push((theirs.species, received.species.clone()));
         }
     }
-    if weights.is_empty() {
-        return 0;
+
+    async fn handle_event(&mut self, event: SessionEvent, assets: &Assets) {
+        let partner = self.trade.as_ref().map(|trade| trade.partner.clone());
+        let from_partner = |from: &str| partner.as_deref() == Some(from);
+        match event {
+            SessionEvent::TradeRequest { from } => {
+                if let Some(trade) = self.trade.as_mut()
+                    && trade.partner == from
+                    && trade.stage == Stage::Requested
+                {
+                    // Both asked at once; treat each request as the other's answer.
+                    trade.stage = Stage::Choosing;
+                    return;
+                }
+                if !self.trade_requests.contains(&from) {
+                    self.trade_requests.push(from.clone());
+                }
+                self.show_caught_message(
+                    format!("{from} wants to trade! Type 'trade {from}' to accept."),
+                    "\x1b[96m",
+                );
+            }
+            SessionEvent::TradeAccept { from } => {
+                if let Some(trade) = self.trade.as_mut()
+                    && trade.partner == from
+                {
+                    trade.stage = Stage::Choosing;
+                }
+            }
+            SessionEvent::TradeOffer { from, offer } => {
+                if let Some(trade) = self.trade.as_mut()
+                    && trade.partner == from
+                {
+                    trade.set_theirs(offer);
+                }
+            }
+            SessionEvent::TradeConfirm { from, mine, theirs } => {
+                if let Some(trade) = self.trade.as_mut()
+                    && trade.partner == from
+                    && trade.partner_confirms(mine, theirs)
+                {
+                    self.try_swap(assets).await;
+                }
+            }
+            SessionEvent::TradeCancel { from } => {
+                self.trade_requests.retain(|name| *name != from);
+                if from_partner(&from) {
+                    self.trade = None;
+                    if matches!(self.screen, Screen::Trade) {
+                        self.screen = Screen::Game;
+                    }
+                    self.show_caught_message(format!("{from} cancelled the trade."), "\x1b[91m");
+                }
+            }
+            SessionEvent::LegendaryCaught { trainer, species } => {
+                self.show_toast(format!(
+                    "{trainer} caught the legendary {}!",
+                    display_pokemon_name(&species)
+                ));
+            }
+            SessionEvent::TradeComplete { from, ok } => {
+                if from_partner(&from) {
+                    self.finish_trade(assets, ok).await;
+                } else if ok {
+                    // The trade went through after we backed out of it; any
+                    // trade open now is with someone else and stays open.
+                    self.reload_after_trade(assets).await;
+                }
+            }
+        }
     }
 
-    let total_weight: u64 = weights.iter().map(|(_, w)| *w as u64).sum();
-    let mut rng = seed;
-    let roll = next_u64(&mut rng) % total_weight;
-    let mut acc = 0u64;
-    for (idx, weight) in weights {
-        acc += weight as u64;
-        if roll < acc {
-            return idx;
+    /// Picks up a swap from the database, dropping a battle whose Pokemon
+    /// was traded away.
+    async fn reload_after_trade(&mut self, assets: &Assets) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        self.pokedex = load_pokedex(&name).await.unwrap_or_default();
+        self.shinies = load_shinies(&name).await.unwrap_or_default();
+        self.captures = load_captures(&name, &self.pokedex)
+            .await
+            .unwrap_or_default();
+        self.record_progress(assets).await;
+        if self
+            .battle_partner
+            .is_some_and(|id| !self.captures.iter().any(|c| c.id == id))
+        {
+            self.battle = None;
+            self.battle_partner = None;
         }
     }
-    0
-}
 
-fn rgb_to_ansi256(r: u8, g: u8, b: u8) -> u8 {
-    // 16-231: 6x6x6 color cube, 232-255: grayscale
-    let r = r as u16;
-    let g = g as u16;
-    let b = b as u16;
-    let gray = (r + g + b) / 3;
-    if gray > 8
-        && gray < 248
-        && (r as i16 - g as i16).abs() < 12
-        && (r as i16 - b as i16).abs() < 12
-    {
-        let gray_index = ((gray - 8) * 24 / 247) as u8;
-        return 232 + gray_index;
+    fn start_spectating(&mut self, trainer: &str) {
+        let Some(trainer) = sanitize_trainer_name(trainer) else {
+            return;
+        };
+        let Some(feed) = sessions::spectate(&trainer) else {
+            let reason = if sessions::is_online(&trainer) {
+                format!("{trainer} isn't taking spectators.")
+            } else {
+                format!("{trainer} isn't online.")
+            };
+            self.show_caught_message(reason, "\x1b[91m");
+            return;
+        };
+        self.mirror_frame = feed.borrow().clone();
+        self.spectating = Some((trainer, feed));
+        self.screen = Screen::Spectate;
     }
-    let rc = (r * 5 / 255) as u8;
-    let gc = (g * 5 / 255) as u8;
-    let bc = (b * 5 / 255) as u8;
-    16 + 36 * rc + 6 * gc + bc
-}
 
-fn pick_pokemon_index(pokemons: &[PokemonAsset]) -> usize {
-    if pokemons.is_empty() {
-        panic!("no pokemon assets loaded");
+    fn stop_spectating(&mut self) {
+        self.spectating = None;
+        self.mirror_frame = None;
+        self.screen = Screen::Name;
     }
-    let nanos = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_nanos();
-    (nanos % pokemons.len() as u128) as usize
-}
 
-fn load_pokedex_view() -> io::Result<PokedexView> {
-    let (names, totals_by_name) = load_gen1_data(GEN1_CSV)?;
-    Ok(PokedexView {
-        names,
-        totals_by_name,
-    })
-}
+    /// Picks up the watched trainer's latest frame, leaving when they stop
+    /// sharing or disconnect.
+    fn poll_mirror(&mut self) {
+        let Some((trainer, feed)) = self.spectating.as_mut() else {
+            return;
+        };
+        let ended = match feed.has_changed() {
+            Ok(false) => false,
+            Ok(true) => {
+                let frame = feed.borrow_and_update().clone();
+                let ended = frame.is_none();
+                if frame.is_some() {
+                    self.mirror_frame = frame;
+                }
+                ended
+            }
+            Err(_) => true,
+        };
+        if ended {
+            let message = format!("{trainer} stopped sharing their screen.");
+            self.stop_spectating();
+            self.show_caught_message(message, "\x1b[97m");
+        }
+    }
 
-fn load_pokemon_assets(names: &[String], charset: &str) -> Vec<PokemonAsset> {
-    let mut assets = Vec::new();
-    for name in names {
-        if name.is_empty() {
-            continue;
+    async fn set_allow_spectators(&mut self, allow: bool) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        self.allow_spectators = allow;
+        sessions::set_allow_spectators(&name, self.session_id, allow);
+        if !allow {
+            self.mirror.send_replace(None);
         }
-        let image = pokemon::load_named_pokemon(name, charset);
-        assets.push(PokemonAsset {
-            name: name.clone(),
-            image,
-        });
+        let _ = save_allow_spectators(&name, allow).await;
+        let message = if allow {
+            "Spectators can watch you again."
+        } else {
+            "Spectators turned off."
+        };
+        self.show_caught_message(message.to_string(), "\x1b[97m");
     }
-    assets
-}
 
-fn load_gen1_data(path: &str) -> io::Result<(Vec<String>, HashMap<String, u16>)> {
-    let data = fs::read_to_string(path)?;
-    let mut names = vec![String::new(); 151];
-    let mut totals_by_name = HashMap::new();
-    for (i, line) in data.lines().enumerate() {
-        if i == 0 {
-            continue;
+    /// Days since the epoch in the trainer's zone; daily encounters, ball
+    /// restocks, streaks and quests all roll over when it changes. It never
+    /// goes back past the last recorded login day, so moving to a zone further
+    /// west doesn't reopen yesterday.
+    fn current_day(&self) -> i64 {
+        self.zone.day_index(unix_now()).max(self.login_day)
+    }
+
+    /// Moves the trainer to another unlocked route. In daily mode the current
+    /// wild Pokemon stays until it is resolved and the new route's table
+    /// applies from the next slot; in random mode it is replaced right away.
+    async fn travel(&mut self, assets: &Assets, input: &str) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let Some(idx) = routes::find(&assets.routes, input) else {
+            self.travel_notice(format!("There's no route called '{}'.", input.trim()));
+            return;
+        };
+        let route = &assets.routes[idx];
+        let (caught, _, _) = self.dex_progress(assets);
+        if route.unlock > caught {
+            let message = format!(
+                "Catch {} more Pokemon to reach {}.",
+                route.unlock - caught,
+                route.name
+            );
+            self.travel_notice(message);
+            return;
         }
-        let fields = parse_csv_line(line);
-        if fields.len() < 6 {
-            continue;
+        if self.state != GameState::Idle {
+            self.travel_notice("Finish this encounter before traveling.".to_string());
+            return;
         }
-        let id: usize = match fields[0].trim().parse() {
-            Ok(v) => v,
-            Err(_) => continue,
-        };
-        if id == 0 || id > 151 {
-            continue;
+        let moved = idx != self.route;
+        if moved {
+            self.route = idx;
+            let _ = save_route(&name, &route.id).await;
+            if self.selection_mode == SelectionMode::RandomPerSession {
+                self.pick_random_encounter(assets).await;
+            }
         }
-        let name = fields[1].trim();
-        let form = fields[2].trim();
-        let total: u16 = fields[5].trim().parse().unwrap_or(0);
-        let normalized = normalize_pokemon_name(name, form);
-        names[id - 1] = normalized.clone();
-        if total > 0 {
-            totals_by_name.insert(normalized, total);
+        self.screen = Screen::Game;
+        let mut message = format!("You traveled to {}.", assets.routes[idx].name);
+        if moved && self.selection_mode == SelectionMode::DailyWeighted && !self.encounters_done() {
+            let species = display_pokemon_name(&self.pokemon(assets).name);
+            let _ = write!(message, " The wild {species} followed you.");
         }
+        self.show_caught_message(message, "\x1b[97m");
+        self.announce_shiny(assets);
     }
-    Ok((names, totals_by_name))
-}
 
-fn parse_csv_line(line: &str) -> Vec<String> {
-    let mut out = Vec::new();
-    let mut buf = String::new();
-    let mut in_quotes = false;
-    for ch in line.chars() {
-        match ch {
-            '"' => {
-                in_quotes = !in_quotes;
-            }
-            ',' if !in_quotes => {
-                out.push(buf.clone());
-                buf.clear();
-            }
-            _ => buf.push(ch),
+    /// Travel problems show on the map, or as the catch message when `travel`
+    /// was typed on the catch screen.
+    fn travel_notice(&mut self, message: String) {
+        if matches!(self.screen, Screen::Map) {
+            self.map_notice = Some(message);
+        } else {
+            self.show_caught_message(message, "\x1b[91m");
         }
     }
-    out.push(buf);
-    out
-}
 
-fn normalize_pokemon_name(name: &str, form: &str) -> String {
-    let mut base = name.trim().to_lowercase();
-    base = base.replace('.', "");
-    base = base.replace('\'', "");
-    base = base.replace(' ', "-");
-    let form = form.trim();
-    if !form.is_empty() && form != " " {
-        let form = form.to_lowercase().replace(' ', "-");
-        if base == "nidoran" {
-            if form == "female" {
-                return "nidoran-f".to_string();
-            }
-            if form == "male" {
-                return "nidoran-m".to_string();
-            }
-        }
-        return format!("{}-{}", base, form);
+    /// Time of day and weather by the trainer's clock.
+    fn conditions(&self) -> Conditions {
+        Conditions::at(&self.zone, unix_now())
     }
-    base
-}
+
+    fn show_timezone(&mut self) {
+        let source = if self.custom_zone {
+            ""
+        } else {
+            " (server default)"
+        };
+        let message = format!(
+            "Your day rolls over at midnight {}{source}.",
+            self.zone.describe(unix_now())
+        );
+        self.show_caught_message(message, "\x1b[97m");
+    }
+
+    /// Sets (or with `default`/`server`, clears) the trainer's own zone. It
+    /// can only change once per `TZ_CHANGE_SECS`, so hopping zones can't
+    /// skip ahead to the next day's encounters.
+    async fn set_timezone(&mut self, assets: &Assets, input: &str) {
+        let Some(name) = self.trainer_name.clone() else {
+            return;
+        };
+        let clear = matches!(input.to_lowercase().as_str(), "default" | "server");
+        let zone = if clear {
+            assets.zone.clone()
+        } else {
+            match tz::Zone::load(input) {
+                Ok(zone) => zone,
+                Err(_) => {
+                    self.show_caught_message(
+                        format!("Unknown time zone '{input}'. Try Europe/Berlin or UTC+1."),
+                        "\x1b[91m",
+                    );
+                    return;
+                }
+            }
+        };
+        let now = unix_now();
+        if let Ok((_, changed_at)) = load_timezone(&name).await
+            && changed_at.is_some_and(|at| now - at < TZ_CHANGE_SECS)
+        {
+            self.show_caught_message(
+                "You can only change your time zone once a day.".to_string(),
+                "\x1b[91m",
+            );
+            return;
+        }
+        let stored = (!clear).then_some(zone.name.as_str());
+        if save_timezone(&name, stored, now).await.is_err() {
+            return;
+        }
+        self.zone = zone;
+        self.custom_zone = !clear;
+        self.show_timezone();
+    }
+
+    /// Called when the connection closes.
+    fn leave(&mut self) {
+        self.mirror.send_replace(None);
+        self.cancel_trade(String::new());
+        if let Some(name) = self.trainer_name.as_ref() {
+            sessions::unregister(name, self.session_id);
+        }
+    }
+}
+const POKEDEX_COLS: usize = 15;
+const POKEDEX_ROWS: usize = 11;
+const POKEDEX_CELL_W: usize = 9;
+const POKEDEX_CELL_H: usize = 3;
+const POKEDEX_PAGE_SIZE: usize = POKEDEX_COLS * POKEDEX_ROWS;
+
+#[tokio::main]
+async fn main() -> io::Result<()> {
+    let _ = TRAINER_STORE.set(store::from_env(db_path())?);
+    init_db().await?;
+    let pokedex = dex::load_pokedex_view().unwrap_or_else(|err| {
+        panic!("failed to load pokedex assets: {err}");
+    });
+    let manifest = pokemon::load_manifest().unwrap_or_else(|err| {
+        panic!("failed to load asset manifest: {err}");
+    });
+    rebuild_leaderboard(&pokedex).await?;
+    let problems = manifest.validate(&pokedex.names);
+    if !problems.is_empty() {
+        panic!("invalid asset manifest:\n  {}", problems.join("\n  "));
+    }
+    let routes = routes::load_routes(&pokedex.names).unwrap_or_else(|err| {
+        panic!("failed to load routes: {err}");
+    });
+    let pokemons = load_pokemon_assets(&manifest, &pokedex.names, IMG_CHARSET);
+    let assets = Arc::new(Assets {
+        pokemons,
+        welcome: manifest
+            .welcome
+            .iter()
+            .map(|entry| pokemon::load_welcome_animation(entry, IMG_CHARSET))
+            .collect(),
+        pokedex,
+        zone: timezone_from_env(),
+        routes,
+    });
+
+    let selection_mode = selection_mode_from_env();
+    let port = server_port_from_env(selection_mode);
+    let listener = TcpListener::bind(("0.0.0.0", port)).await?;
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let _ = stream.set_nodelay(true);
+        let assets = Arc::clone(&assets);
+        tokio::spawn(async move {
+            let _ = run_session(stream, assets).await;
+        });
+    }
+}
+
+async fn run_session(stream: TcpStream, assets: Arc<Assets>) -> io::Result<()> {
+    let (read_half, write_half) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+
+    let (cmd_tx, mut cmd_rx) = mpsc::unbounded_channel::<String>();
+    let reader_task = tokio::spawn(async move {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            match reader.read_line(&mut line).await {
+                Ok(0) => {
+                    let _ = cmd_tx.send(String::from("__disconnect__"));
+                    break;
+                }
+                Ok(_) => {
+                    let _ = cmd_tx.send(line.clone());
+                }
+                Err(_) => {
+                    let _ = cmd_tx.send(String::from("__disconnect__"));
+                    break;
+                }
+            }
+        }
+    });
+
+    let width = env_usize("POKESTREAM_WIDTH").unwrap_or(140);
+    let height = env_usize("POKESTREAM_HEIGHT").unwrap_or(40);
+    let color_mode = color_mode_from_env();
+    let selection_mode = selection_mode_from_env();
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel::<SessionEvent>();
+    let mut chat_rx = chat::subscribe();
+    let mut session =
+        SessionState::new(width, height, color_mode, selection_mode, event_tx, &assets);
+    let mut buffers = RenderBuffers::new(width, height);
+
+    let (out_tx, out_rx) = mpsc::channel::<OutputMessage>(2);
+    let writer_task = tokio::spawn(async move {
+        let mut write_half = write_half;
+        let mut out_rx = out_rx;
+        while let Some(msg) = out_rx.recv().await {
+            match msg {
+                OutputMessage::Bytes(bytes) => {
+                    if write_half.write_all(&bytes).await.is_err() {
+                        break;
+                    }
+                }
+                OutputMessage::Close { send_bye } => {
+                    if send_bye {
+                        let _ = close_session(&mut write_half).await;
+                    } else {
+                        let _ = cleanup_terminal(&mut write_half).await;
+                        let _ = write_half.shutdown().await;
+                    }
+                    break;
+                }
+            }
+        }
+    });
+
+    let _ = out_tx
+        .send(OutputMessage::Bytes(
+            b"\x1b[?1049h\x1b[?7l\x1b[2J\x1b[H\x1b[?25l".to_vec(),
+        ))
+        .await;
+    let _ = out_tx
+        .send(OutputMessage::Bytes(b"\x1b[2J\x1b[H\x1b[?25l".to_vec()))
+        .await;
+
+    let frame_interval = frame_interval_from_env();
+    let mut ticker = time::interval(frame_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Skip);
+    let mut last_frame_hash: u64 = 0;
+
+    loop {
+        tokio::select! {
+            maybe_cmd = cmd_rx.recv() => {
+                let cmd = match maybe_cmd {
+                    Some(cmd) => cmd,
+                    None => String::from("__disconnect__"),
+                };
+                match session.handle_command(&cmd, &assets).await {
+                    CommandAction::Exit => {
+                        reader_task.abort();
+                        let _ = reader_task.await;
+                        let _ = out_tx.send(OutputMessage::Close { send_bye: true }).await;
+                        break;
+                    }
+                    CommandAction::Disconnect => {
+                        reader_task.abort();
+                        let _ = reader_task.await;
+                        let _ = out_tx.send(OutputMessage::Close { send_bye: false }).await;
+                        break;
+                    }
+                    CommandAction::None => {}
+                }
+
+                if out_tx.capacity() > 0 {
+                    session.render(&assets, &mut buffers);
+                    let frame = session.compose_frame(&buffers);
+                    let hash = fast_hash(frame.as_bytes());
+                    if hash != last_frame_hash {
+                        last_frame_hash = hash;
+                        let _ = out_tx.try_send(OutputMessage::Bytes(frame.into_bytes()));
+                    }
+                }
+            }
+            Some(event) = event_rx.recv() => {
+                session.handle_event(event, &assets).await;
+            }
+            Ok(message) = chat_rx.recv() => {
+                session.receive_chat(message);
+            }
+            _ = ticker.tick() => {
+                if out_tx.capacity() == 0 {
+                    continue;
+                }
+                session.update(&assets).await;
+                session.render(&assets, &mut buffers);
+                let frame = session.compose_frame(&buffers);
+                let hash = fast_hash(frame.as_bytes());
+                if hash != last_frame_hash {
+                    last_frame_hash = hash;
+                    let _ = out_tx.try_send(OutputMessage::Bytes(frame.into_bytes()));
+                }
+            }
+        }
+    }
+
+    session.leave();
+    let _ = writer_task.await;
+    Ok(())
+}
+
+async fn cleanup_terminal(write_half: &mut OwnedWriteHalf) -> io::Result<()> {
+    write_half
+        .write_all(b"\x1b[0m\x1b[?25h\x1b[?7h\x1b[?1049l")
+        .await
+}
+
+async fn close_session(write_half: &mut OwnedWriteHalf) -> io::Result<()> {
+    cleanup_terminal(write_half).await?;
+    write_half.write_all(b"bye\r\n").await?;
+    write_half.shutdown().await
+}
+
+fn build_stream_particles(
+    image: &ascii::AsciiImage,
+    start_x: usize,
+    start_y: usize,
+    target_x: f32,
+    target_y: f32,
+) -> Vec<StreamParticle> {
+    let mut particles = Vec::new();
+    let mut idx: u16 = 0;
+    for y in 0..image.height {
+        for x in 0..image.width {
+            let src_idx = x + y * image.width;
+            let ch = image.chars[src_idx];
+            if ch == ' ' {
+                continue;
+            }
+            let color = image.colors[src_idx];
+            particles.push(StreamParticle {
+                x0: (start_x + x) as f32,
+                y0: (start_y + y) as f32,
+                x1: target_x,
+                y1: target_y,
+                ch,
+                color,
+                start_frame: idx % 12,
+            });
+            idx = idx.wrapping_add(1);
+        }
+    }
+    particles
+}
+
+/// Draws the absorb stream; `reverse` plays it backwards, from the ball out to the sprite.
+#[allow(clippy::too_many_arguments)]
+fn render_stream(
+    particles: &[StreamParticle],
+    frame: u16,
+    reverse: bool,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    for particle in particles {
+        let t = if reverse {
+            let rewound = (ABSORB_FRAMES + 12).saturating_sub(frame);
+            (rewound.saturating_sub(particle.start_frame) as f32 / ABSORB_FRAMES as f32).min(1.0)
+        } else {
+            if frame < particle.start_frame {
+                continue;
+            }
+            let t = (frame - particle.start_frame) as f32 / ABSORB_FRAMES as f32;
+            t.min(1.0)
+        };
+        let x = particle.x0 + (particle.x1 - particle.x0) * t;
+        let y = particle.y0 + (particle.y1 - particle.y0) * t;
+        let xi = x.round() as i32;
+        let yi = y.round() as i32;
+        if xi < 0 || yi < 0 || xi >= width as i32 || yi >= height as i32 {
+            continue;
+        }
+        let idx = (xi + yi * width as i32) as usize;
+        if 0.6 > zbuffer[idx] {
+            zbuffer[idx] = 0.6;
+            output[idx] = particle.ch;
+            let (r, g, b) = particle.color;
+            color_buf[idx] = CellColor::Rgb(r, g, b);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_starburst(
+    width: i32,
+    height: i32,
+    ball_x: f32,
+    ball_y: f32,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    frame: u16,
+) {
+    let center_x = (width as f32 / 2.0 + ball_x).round() as i32;
+    let center_y = (height as f32 / 2.0 + ball_y).round() as i32 - 10;
+    let t = frame as f32 / STAR_FRAMES as f32;
+    let spread = (1.0 - t) * 11.0;
+    let stars = [
+        (0.0, -1.0),
+        (1.0, 0.0),
+        (-1.0, 0.0),
+        (0.0, 1.0),
+        (0.8, -0.6),
+        (-0.8, -0.6),
+        (0.8, 0.6),
+        (-0.8, 0.6),
+        (1.2, -0.2),
+        (-1.2, -0.2),
+        (1.2, 0.2),
+        (-1.2, 0.2),
+        (0.0, -1.6),
+        (0.0, -2.0),
+        (0.0, -2.4),
+        (0.4, -1.8),
+        (-0.4, -1.8),
+    ];
+    for (dx, dy) in stars {
+        let x = center_x + (dx * spread) as i32;
+        let y = center_y + (dy * spread) as i32;
+        if x < 0 || y < 0 || x >= width || y >= height {
+            continue;
+        }
+        let idx = (x + y * width) as usize;
+        output[idx] = '*';
+        color_buf[idx] = CellColor::Ansi("\x1b[93m");
+        zbuffer[idx] = 0.9;
+    }
+}
+
+fn color_mode_from_env() -> ColorMode {
+    if let Ok(mode) = env::var("POKESTREAM_COLOR") {
+        let mode = mode.to_lowercase();
+        if mode == "truecolor" || mode == "24bit" {
+            return ColorMode::Truecolor;
+        }
+        if mode == "ansi256" || mode == "256" {
+            return ColorMode::Ansi256;
+        }
+        if mode == "mono" || mode == "none" {
+            return ColorMode::Mono;
+        }
+    }
+    if let Ok(ct) = env::var("COLORTERM") {
+        let ct = ct.to_lowercase();
+        if ct.contains("truecolor") || ct.contains("24bit") {
+            return ColorMode::Truecolor;
+        }
+    }
+    if let Ok(term) = env::var("TERM") {
+        let term = term.to_lowercase();
+        if term.contains("truecolor") || term.contains("24bit") || term.contains("direct") {
+            return ColorMode::Truecolor;
+        }
+        if term.contains("256color") {
+            return ColorMode::Ansi256;
+        }
+    }
+    ColorMode::Ansi256
+}
+
+fn env_usize(key: &str) -> Option<usize> {
+    env::var(key).ok().and_then(|val| val.parse::<usize>().ok())
+}
+
+fn selection_mode_from_env() -> SelectionMode {
+    if let Ok(mode) = env::var("POKESTREAM_MODE") {
+        let mode = mode.to_lowercase();
+        if matches!(
+            mode.as_str(),
+            "random" | "dev_random" | "random_per_session"
+        ) {
+            return SelectionMode::RandomPerSession;
+        }
+    }
+    if let Ok(raw) = env::var("POKESTREAM_RANDOM") {
+        let raw = raw.to_lowercase();
+        if matches!(raw.as_str(), "1" | "true" | "yes") {
+            return SelectionMode::RandomPerSession;
+        }
+    }
+    SelectionMode::DailyWeighted
+}
+
+fn server_port_from_env(selection_mode: SelectionMode) -> u16 {
+    if let Ok(raw) = env::var("POKESTREAM_PORT")
+        && let Ok(port) = raw.parse::<u16>()
+    {
+        return port;
+    }
+    match selection_mode {
+        SelectionMode::RandomPerSession => 8081,
+        SelectionMode::DailyWeighted => 8080,
+    }
+}
+
+fn frame_interval_from_env() -> Duration {
+    if let Ok(raw) = env::var("POKESTREAM_FPS")
+        && let Ok(fps) = raw.parse::<u64>()
+        && fps > 0
+    {
+        let ms = (1000 / fps).max(10);
+        return Duration::from_millis(ms);
+    }
+    if let Ok(raw) = env::var("POKESTREAM_FRAME_MS")
+        && let Ok(ms) = raw.parse::<u64>()
+        && ms > 0
+    {
+        return Duration::from_millis(ms);
+    }
+    Duration::from_millis(30)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64
+}
+
+/// Zone for the daily rollover: `POKESTREAM_TZ` (an IANA name such as
+/// `Europe/London`, or a fixed offset such as `UTC-5`), defaulting to
+/// US Eastern time.
+fn timezone_from_env() -> tz::Zone {
+    match env::var("POKESTREAM_TZ") {
+        Ok(name) => tz::Zone::load(&name).unwrap_or_else(|err| {
+            panic!("invalid POKESTREAM_TZ '{name}': {err}");
+        }),
+        Err(_) => tz::Zone::load("America/New_York").unwrap_or_else(|_| tz::Zone::utc()),
+    }
+}
+
+fn daily_encounters_from_env() -> usize {
+    env_usize("POKESTREAM_DAILY_ENCOUNTERS")
+        .filter(|count| *count > 0)
+        .unwrap_or(5)
+}
+
+fn daily_seed(trainer: &str, day_index: i64) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in trainer.as_bytes() {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    for b in day_index.to_le_bytes() {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn shiny_odds_from_env() -> u64 {
+    env_usize("POKESTREAM_SHINY_ODDS")
+        .filter(|odds| *odds > 0)
+        .unwrap_or(128) as u64
+}
+
+/// One in `odds` encounters is shiny. The seed is salted so the roll does not
+/// track the species pick made from the same seed.
+fn shiny_roll(seed: u64, odds: u64) -> bool {
+    let mut rng = seed ^ 0x5348_494E_5953_4545;
+    next_u64(&mut rng).is_multiple_of(odds)
+}
+
+fn level_roll(seed: u64) -> u64 {
+    let mut rng = seed ^ 0x4C45_5645_4C53_0000;
+    next_u64(&mut rng)
+}
+
+fn encounter_seed(daily: u64, slot: usize) -> u64 {
+    if slot == 0 {
+        return daily;
+    }
+    let mut rng = daily ^ (slot as u64).wrapping_mul(0x9E3779B97F4A7C15);
+    next_u64(&mut rng)
+}
+
+/// Caught species that are in the loaded dex, the dex size, and how many of
+/// the caught species are legendary.
+fn dex_counts(pokedex: &HashSet<String>, view: &PokedexView) -> (usize, usize, usize) {
+    let names = view.names.iter().filter(|name| !name.is_empty());
+    let total = names.clone().count();
+    let caught = names.filter(|name| pokedex.contains(*name)).count();
+    let legendaries = view
+        .legendaries
+        .iter()
+        .filter(|name| pokedex.contains(*name))
+        .count();
+    (caught, total, legendaries)
+}
+
+fn legendaries_unlocked(pokedex: &HashSet<String>, view: &PokedexView) -> bool {
+    for name in &view.names {
+        if name.is_empty() || view.is_legendary(name) {
+            continue;
+        }
+        if !pokedex.contains(name) {
+            return false;
+        }
+    }
+    true
+}
+
+fn pokemon_weight(
+    view: &PokedexView,
+    name: &str,
+    total: Option<u16>,
+    legendary_unlocked: bool,
+    conditions: Conditions,
+) -> u32 {
+    if view.is_legendary(name) && !legendary_unlocked {
+        return 0;
+    }
+    let total = total.unwrap_or(350) as i32;
+    let mut weight = (600 - total).clamp(20, 500) as f32;
+
+    if total >= 500 {
+        weight *= 0.35;
+    } else if total >= 450 {
+        weight *= 0.55;
+    } else if total >= 400 {
+        weight *= 0.75;
+    } else if total <= 300 {
+        weight *= 1.25;
+    }
+
+    if view.is_starter(name) {
+        weight *= 0.8;
+    }
+
+    if view.is_legendary(name) {
+        weight *= 0.05;
+    }
+
+    if let Some(types) = view.types_by_name.get(name) {
+        weight *= conditions.type_multiplier(types);
+    }
+
+    weight.max(1.0) as u32
+}
+
+fn pick_weighted_pokemon(
+    pokemons: &[PokemonAsset],
+    pokedex_view: &PokedexView,
+    seed: u64,
+    legendary_unlocked: bool,
+    conditions: Conditions,
+    route: &routes::Route,
+) -> usize {
+    let mut weights = Vec::with_capacity(pokemons.len());
+    let on_route = pokemons.iter().any(|pokemon| route.allows(&pokemon.name));
+    for (idx, pokemon) in pokemons.iter().enumerate() {
+        if on_route && !route.allows(&pokemon.name) {
+            continue;
+        }
+        let total = pokedex_view.totals_by_name.get(&pokemon.name).copied();
+        let weight = pokemon_weight(
+            pokedex_view,
+            &pokemon.name,
+            total,
+            legendary_unlocked,
+            conditions,
+        );
+        if weight > 0 {
+            weights.push((idx, weight));
+        }
+    }
+    if weights.is_empty() {
+        return 0;
+    }
+
+    let total_weight: u64 = weights.iter().map(|(_, w)| *w as u64).sum();
+    let mut rng = seed;
+    let roll = next_u64(&mut rng) % total_weight;
+    let mut acc = 0u64;
+    for (idx, weight) in weights {
+        acc += weight as u64;
+        if roll < acc {
+            return idx;
+        }
+    }
+    0
+}
+
+/// Overall odds of a capture: weak species (low base stat total) are easy,
+/// legendaries rarely stay in the ball. `ball_modifier` scales the result.
+fn capture_chance(total: Option<u16>, ball_modifier: f32) -> f32 {
+    let total = total.unwrap_or(350) as f32;
+    let base = (1.0 - (total - 250.0) / 500.0).clamp(0.05, 0.95);
+    (base * ball_modifier).clamp(0.0, 1.0)
+}
+
+/// Per-shake odds such that surviving every shake matches `capture`.
+fn shake_chance(capture: f32) -> f32 {
+    capture.powf(1.0 / SHAKE_COUNT as f32)
+}
+
+fn session_rng_seed() -> u64 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    nanos ^ 0x9E3779B97F4A7C15
+}
+
+fn roll_unit(rng: &mut u64) -> f32 {
+    (next_u64(rng) % 10_000) as f32 / 10_000.0
+}
+
+/// A uniformly random Pokemon from the route, or from everything if none of
+/// the route's species are loaded.
+fn pick_pokemon_index(pokemons: &[PokemonAsset], route: &routes::Route) -> usize {
+    if pokemons.is_empty() {
+        panic!("no pokemon assets loaded");
+    }
+    let mut candidates: Vec<usize> = (0..pokemons.len())
+        .filter(|idx| route.allows(&pokemons[*idx].name))
+        .collect();
+    if candidates.is_empty() {
+        candidates = (0..pokemons.len()).collect();
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    candidates[(nanos % candidates.len() as u128) as usize]
+}
+
+fn pick_welcome_index(welcome: &[pokemon::WelcomeAnimation]) -> usize {
+    if welcome.len() <= 1 {
+        return 0;
+    }
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    (nanos % welcome.len() as u128) as usize
+}
+
+fn load_pokemon_assets(
+    manifest: &pokemon::AssetManifest,
+    names: &[String],
+    charset: &str,
+) -> Vec<PokemonAsset> {
+    let mut assets = Vec::new();
+    for name in names {
+        if name.is_empty() {
+            continue;
+        }
+        let image = pokemon::load_named_pokemon(manifest, name, charset);
+        let shiny = pokemon::load_shiny_pokemon(manifest, name, charset, &image);
+        assets.push(PokemonAsset {
+            name: name.clone(),
+            image,
+            shiny,
+            offset: manifest.offset(name),
+        });
+    }
+    assets
+}
 
 fn display_pokemon_name(name: &str) -> String {
     let mut out = String::new();
-    for (i, part) in name.split('-').enumerate() {
-        if i > 0 {
-            out.push(' ');
+    for (i, part) in name.split('-').enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            out.push(first.to_ascii_uppercase());
+            out.extend(chars);
+        }
+    }
+    out
+}
+
+fn is_agent_stats_query(phrase: &str) -> bool {
+    let phrase = phrase.trim();
+    phrase.contains("stats")
+        || phrase.contains("base stat")
+        || (phrase.contains("hp") && phrase.contains("atk"))
+        || phrase.contains("how strong")
+        || phrase.contains("what are its stats")
+        || phrase.contains("show me stats")
+}
+
+fn is_agent_pokemon_query(phrase: &str) -> bool {
+    let phrase = phrase.trim();
+    phrase.contains("what pokemon is this")
+        || phrase.contains("who is this pokemon")
+        || phrase.contains("what is this creature")
+        || phrase.contains("what is this pokemon")
+        || phrase.contains("identify this")
+        || phrase.contains("tell me about this")
+        || phrase.contains("what the hell is this")
+}
+
+fn is_agent_left_to_catch_query(phrase: &str) -> bool {
+    let phrase = phrase.trim();
+    phrase.contains("how many")
+        && (phrase.contains("left")
+            || phrase.contains("remaining")
+            || phrase.contains("to catch")
+            || phrase.contains("missing"))
+}
+
+fn is_agent_caught_count_query(phrase: &str) -> bool {
+    let phrase = phrase.trim();
+    (phrase.contains("how many") || phrase.contains("count"))
+        && (phrase.contains("caught") || phrase.contains("have i"))
+}
+
+fn is_agent_missing_list_query(phrase: &str) -> bool {
+    let phrase = phrase.trim();
+    phrase.contains("which pokemon am i missing")
+        || phrase.contains("what am i missing")
+        || (phrase.contains("show") && phrase.contains("missing"))
+}
+
+fn looks_like_agent_query_candidate(text: &str) -> bool {
+    let text = text.trim().to_lowercase();
+    if text.is_empty() {
+        return false;
+    }
+    if matches!(
+        text.as_str(),
+        "catch" | "pokedex" | "dex" | "back" | "q" | "quit" | "exit"
+    ) {
+        return false;
+    }
+    if text.chars().all(|ch| ch.is_ascii_digit()) {
+        return false;
+    }
+    text.ends_with('?')
+        || text.contains(' ')
+        || matches!(text.as_str(), "help" | "status" | "progress" | "missing")
+}
+
+fn word_wrap(text: &str, max_width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if current.is_empty() {
+            current = word.to_string();
+        } else if current.len() + 1 + word.len() <= max_width {
+            current.push(' ');
+            current.push_str(word);
+        } else {
+            lines.push(current);
+            current = word.to_string();
+        }
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+fn fast_hash(data: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for &b in data {
+        hash ^= b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+fn normalize_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+fn clip_text(text: &str, max_chars: usize) -> String {
+    let len = text.chars().count();
+    if len <= max_chars {
+        return text.to_string();
+    }
+    if max_chars <= 3 {
+        return text.chars().take(max_chars).collect();
+    }
+    let mut out = String::new();
+    for ch in text.chars().take(max_chars - 3) {
+        out.push(ch);
+    }
+    out.push_str("...");
+    out
+}
+
+fn missing_pokemon_preview(caught: &HashSet<String>, assets: &Assets, limit: usize) -> Vec<String> {
+    assets
+        .pokedex
+        .names
+        .iter()
+        .filter(|name| !name.is_empty() && !caught.contains(*name))
+        .take(limit)
+        .map(|name| display_pokemon_name(name))
+        .collect()
+}
+
+fn ollama_url() -> String {
+    env::var("OLLAMA_URL").unwrap_or_else(|_| "http://127.0.0.1:11434".to_string())
+}
+
+fn ollama_model() -> String {
+    env::var("OLLAMA_MODEL").unwrap_or_else(|_| "qwen2.5:1.5b".to_string())
+}
+
+async fn ask_llm_brief(
+    user_query: &str,
+    screen: &str,
+    trainer_name: Option<&str>,
+    current_pokemon: Option<&str>,
+    caught_count: usize,
+    total_count: usize,
+    left_count: usize,
+) -> Option<String> {
+    let prompt = format!(
+        "You are Pokestream Agent inside a telnet Pokemon game. \
+        Keep responses concise, under 200 characters. Only answer in-game questions. \
+        Context: screen={screen}, trainer={}, pokemon={}, caught={caught_count}/{total_count}, left={left_count}.\n\
+        User: {user_query}",
+        trainer_name.unwrap_or("unknown"),
+        current_pokemon.unwrap_or("unknown"),
+    );
+
+    let payload = serde_json::json!({
+        "model": ollama_model(),
+        "prompt": prompt,
+        "stream": false,
+        "keep_alive": "10m",
+        "options": {
+            "temperature": 0.3,
+            "num_predict": 40,
+        }
+    });
+
+    let url = format!("{}/api/generate", ollama_url());
+    let client = shared_http_client();
+    let response = match tokio::time::timeout(
+        Duration::from_secs(8),
+        client.post(&url).json(&payload).send(),
+    )
+    .await
+    {
+        Ok(Ok(resp)) => resp.error_for_status().ok()?,
+        _ => return None,
+    };
+    let body: serde_json::Value = response.json().await.ok()?;
+
+    let text = body.get("response").and_then(|v| v.as_str())?;
+    let cleaned = normalize_whitespace(text);
+    if cleaned.is_empty() {
+        return None;
+    }
+
+    let answer = format!("Agent: {}", cleaned);
+    Some(clip_text(&normalize_whitespace(&answer), 220))
+}
+
+async fn fetch_pokemon_brief(name: &str) -> Option<String> {
+    if let Ok(cache) = pokemon_cache().lock()
+        && let Some(cached) = cache.get(name)
+    {
+        return Some(cached.clone());
+    }
+
+    let client = shared_http_client();
+    let url = format!("https://pokeapi.co/api/v2/pokemon/{name}");
+    let pokemon: serde_json::Value = client
+        .get(url)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+
+    let mut type_slots: Vec<(i64, String)> = Vec::new();
+    if let Some(types) = pokemon.get("types").and_then(|v| v.as_array()) {
+        for entry in types {
+            let slot = entry.get("slot").and_then(|v| v.as_i64()).unwrap_or(99);
+            if let Some(type_name) = entry
+                .get("type")
+                .and_then(|v| v.get("name"))
+                .and_then(|v| v.as_str())
+            {
+                type_slots.push((slot, type_name.to_string()));
+            }
+        }
+    }
+    type_slots.sort_by_key(|(slot, _)| *slot);
+    let type_text = if type_slots.is_empty() {
+        "unknown".to_string()
+    } else {
+        type_slots
+            .into_iter()
+            .map(|(_, ty)| ty)
+            .collect::<Vec<_>>()
+            .join("/")
+    };
+
+    let flavor = if let Some(species_url) = pokemon
+        .get("species")
+        .and_then(|v| v.get("url"))
+        .and_then(|v| v.as_str())
+    {
+        fetch_species_flavor(client, species_url).await
+    } else {
+        None
+    };
+
+    let message = if let Some(flavor) = flavor {
+        format!(
+            "Agent: {} is a {}-type Pokemon. {}",
+            display_pokemon_name(name),
+            type_text,
+            flavor
+        )
+    } else {
+        format!(
+            "Agent: {} is a {}-type Pokemon.",
+            display_pokemon_name(name),
+            type_text
+        )
+    };
+    let result = normalize_whitespace(&message);
+    if let Ok(mut cache) = pokemon_cache().lock() {
+        cache.insert(name.to_string(), result.clone());
+    }
+    Some(result)
+}
+
+async fn fetch_pokemon_stats(name: &str) -> Option<String> {
+    let client = shared_http_client();
+    let url = format!("https://pokeapi.co/api/v2/pokemon/{name}");
+    let pokemon: serde_json::Value = client
+        .get(url)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+
+    let mut stats_list = Vec::new();
+    if let Some(stats) = pokemon.get("stats").and_then(|v| v.as_array()) {
+        for stat in stats {
+            let stat_name = stat
+                .get("stat")
+                .and_then(|v| v.get("name"))
+                .and_then(|v| v.as_str())
+                .unwrap_or("?");
+            let value = stat.get("base_stat").and_then(|v| v.as_i64()).unwrap_or(0);
+            let label = match stat_name {
+                "hp" => "HP",
+                "attack" => "ATK",
+                "defense" => "DEF",
+                "special-attack" => "SP.ATK",
+                "special-defense" => "SP.DEF",
+                "speed" => "SPD",
+                other => other,
+            };
+            stats_list.push(format!("{label} {value}"));
+        }
+    }
+    let message = format!(
+        "Agent: {} stats: {}.",
+        display_pokemon_name(name),
+        stats_list.join(", ")
+    );
+    Some(clip_text(&normalize_whitespace(&message), 400))
+}
+
+async fn fetch_species_flavor(client: &reqwest::Client, species_url: &str) -> Option<String> {
+    let species: serde_json::Value = client
+        .get(species_url)
+        .send()
+        .await
+        .ok()?
+        .error_for_status()
+        .ok()?
+        .json()
+        .await
+        .ok()?;
+    let entries = species.get("flavor_text_entries")?.as_array()?;
+    for entry in entries {
+        if entry
+            .get("language")
+            .and_then(|v| v.get("name"))
+            .and_then(|v| v.as_str())
+            != Some("en")
+        {
+            continue;
+        }
+        let Some(raw) = entry.get("flavor_text").and_then(|v| v.as_str()) else {
+            continue;
+        };
+        let cleaned = raw.replace(['\n', '\r', '\u{000c}'], " ");
+        let cleaned = normalize_whitespace(&cleaned);
+        if !cleaned.is_empty() {
+            return Some(cleaned);
+        }
+    }
+    None
+}
+
+fn find_pokemon_asset<'a>(assets: &'a Assets, name: &str) -> Option<&'a PokemonAsset> {
+    assets.pokemons.iter().find(|asset| asset.name == name)
+}
+
+fn battler_for(view: &PokedexView, name: &str, level: u16) -> Option<Battler> {
+    let stats = view.stats_by_name.get(name)?;
+    let types = view.types_by_name.get(name).cloned().unwrap_or_default();
+    Some(Battler::new(name, &types, stats, level))
+}
+
+/// First the old sprite collapses into the new sprite's center, then the new
+/// sprite streams out of it and holds until the scene ends.
+#[allow(clippy::too_many_arguments)]
+fn render_evolution(
+    scene: &EvolutionScene,
+    assets: &Assets,
+    origin: (usize, usize),
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    if scene.frame < EVOLVE_STREAM_FRAMES {
+        render_stream(
+            &scene.old_particles,
+            scene.frame,
+            false,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    } else if scene.frame < EVOLVE_STREAM_FRAMES * 2 {
+        render_stream(
+            &scene.new_particles,
+            scene.frame - EVOLVE_STREAM_FRAMES,
+            true,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    } else if let Some(asset) = find_pokemon_asset(assets, &scene.into) {
+        let image = &asset.image;
+        let (start_x, start_y) = origin;
+        for y in 0..image.height {
+            for x in 0..image.width {
+                let target_x = start_x + x;
+                let target_y = start_y + y;
+                if target_x >= width || target_y >= height {
+                    continue;
+                }
+                let src_idx = x + y * image.width;
+                let ch = image.chars[src_idx];
+                if ch == ' ' {
+                    continue;
+                }
+                let idx = target_x + target_y * width;
+                output[idx] = ch;
+                let (r, g, b) = image.colors[src_idx];
+                color_buf[idx] = CellColor::Rgb(r, g, b);
+                zbuffer[idx] = 0.4;
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn draw_text(
+    text: &str,
+    x: usize,
+    row: usize,
+    color: &'static str,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    if row >= height {
+        return;
+    }
+    for (i, ch) in text.chars().enumerate() {
+        let col = x + i;
+        if col >= width {
+            break;
+        }
+        let idx = col + row * width;
+        output[idx] = ch;
+        color_buf[idx] = CellColor::Ansi(color);
+        zbuffer[idx] = 0.5;
+    }
+}
+
+/// Draws every `step`th cell of a sprite so two full-size sprites fit on the
+/// battle screen.
+#[allow(clippy::too_many_arguments)]
+fn draw_sprite_sampled(
+    image: &ascii::AsciiImage,
+    start_x: usize,
+    start_y: usize,
+    step: usize,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    for y in 0..image.height / step {
+        for x in 0..image.width / step {
+            let target_x = start_x + x;
+            let target_y = start_y + y;
+            if target_x >= width || target_y >= height {
+                continue;
+            }
+            let src_idx = x * step + y * step * image.width;
+            let ch = image.chars[src_idx];
+            if ch == ' ' {
+                continue;
+            }
+            let idx = target_x + target_y * width;
+            output[idx] = ch;
+            let (r, g, b) = image.colors[src_idx];
+            color_buf[idx] = CellColor::Rgb(r, g, b);
+            zbuffer[idx] = 0.4;
+        }
+    }
+}
+
+fn hp_bar(battler: &Battler) -> (String, &'static str) {
+    const BAR_W: usize = 20;
+    let ratio = battler.hp_ratio();
+    let filled = ((ratio * BAR_W as f32).ceil() as usize).min(BAR_W);
+    let bar = format!(
+        "HP [{}{}] {}/{}",
+        "#".repeat(filled),
+        ".".repeat(BAR_W - filled),
+        battler.hp,
+        battler.max_hp
+    );
+    let color = if ratio > 0.5 {
+        "\x1b[92m"
+    } else if ratio > 0.2 {
+        "\x1b[93m"
+    } else {
+        "\x1b[91m"
+    };
+    (bar, color)
+}
+
+/// Wild Pokemon top-right with its HUD top-left; the trainer's Pokemon
+/// bottom-left with its HUD on the right, then the battle log and move list.
+/// `hidden` blinks the side that was just hit.
+#[allow(clippy::too_many_arguments)]
+fn render_battle(
+    battle: &Battle,
+    player_sprite: Option<&ascii::AsciiImage>,
+    wild_sprite: &ascii::AsciiImage,
+    hidden: Option<Side>,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    const STEP: usize = 2;
+    if hidden != Some(Side::Wild) {
+        let wild_x = width.saturating_sub(wild_sprite.width / STEP + 8);
+        draw_sprite_sampled(
+            wild_sprite,
+            wild_x,
+            1,
+            STEP,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+    if hidden != Some(Side::Player)
+        && let Some(sprite) = player_sprite
+    {
+        let player_y = height.saturating_sub(sprite.height / STEP + 6);
+        draw_sprite_sampled(
+            sprite, 8, player_y, STEP, output, color_buf, zbuffer, width, height,
+        );
+    }
+
+    let wild = &battle.wild;
+    let wild_title = format!(
+        "WILD {}  Lv{}",
+        display_pokemon_name(&wild.name).to_uppercase(),
+        wild.level
+    );
+    draw_text(
+        &wild_title,
+        4,
+        3,
+        "\x1b[97m",
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+    let (bar, color) = hp_bar(wild);
+    draw_text(&bar, 4, 4, color, output, color_buf, zbuffer, width, height);
+
+    let player = &battle.player;
+    let player_x = width.saturating_sub(44);
+    let player_row = height.saturating_sub(11);
+    let player_title = format!(
+        "{}  Lv{}",
+        display_pokemon_name(&player.name).to_uppercase(),
+        player.level
+    );
+    draw_text(
+        &player_title,
+        player_x,
+        player_row,
+        "\x1b[97m",
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+    let (bar, color) = hp_bar(player);
+    draw_text(
+        &bar,
+        player_x,
+        player_row + 1,
+        color,
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+
+    let log_row = height.saturating_sub(5);
+    for (i, line) in battle.log.iter().enumerate() {
+        draw_text(
+            line,
+            4,
+            log_row + i,
+            "\x1b[97m",
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+    let moves = player
+        .moves
+        .iter()
+        .enumerate()
+        .map(|(i, m)| format!("{} {}", i + 1, m.name.to_uppercase()))
+        .collect::<Vec<_>>()
+        .join("   ");
+    draw_text(
+        &moves,
+        4,
+        height.saturating_sub(2),
+        "\x1b[96m",
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+}
+
+/// Copies a watched session's frame into the spectator's buffers, centered
+/// (or cropped around the center) to fit. The watched trainer's prompt row
+/// is left out so the spectator's own prompt takes its place.
+#[allow(clippy::too_many_arguments)]
+fn render_mirror(
+    frame: Option<&MirrorFrame>,
+    trainer: &str,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let Some(frame) = frame else {
+        let waiting = format!("Waiting for {trainer}...");
+        draw_text(
+            &waiting,
+            width.saturating_sub(waiting.len()) / 2,
+            height / 3,
+            "\x1b[96m",
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+        return;
+    };
+    let fit = |src: usize, dst: usize| {
+        if dst >= src {
+            ((dst - src) / 2, 0, src)
+        } else {
+            (0, (src - dst) / 2, dst)
+        }
+    };
+    let (dst_x, src_x, cols) = fit(frame.width, width);
+    let (dst_y, src_y, rows) = fit(frame.height.saturating_sub(1), height.saturating_sub(1));
+    for y in 0..rows {
+        for x in 0..cols {
+            let src = (src_x + x) + (src_y + y) * frame.width;
+            let idx = (dst_x + x) + (dst_y + y) * width;
+            output[idx] = frame.output[src];
+            color_buf[idx] = frame.color_buf[src];
+            zbuffer[idx] = 0.4;
+        }
+    }
+    let title = format!(" WATCHING {} ", trainer.to_uppercase());
+    draw_text(
+        &title, 2, 0, "\x1b[95m", output, color_buf, zbuffer, width, height,
+    );
+}
+
+/// Ranked trainers with their dex count, shinies, legendaries, and the date
+/// they completed the dex. Ranks come from the full list, so a friends-only
+/// view keeps everyone's global position.
+#[allow(clippy::too_many_arguments)]
+fn render_leaderboard(
+    entries: &[leaderboard::Entry],
+    sort: SortKey,
+    total: usize,
+    me: Option<&str>,
+    friends: Option<&HashSet<String>>,
+    notice: Option<&str>,
+    zone: &tz::Zone,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let scope = if friends.is_some() {
+        "FRIENDS"
+    } else {
+        "ALL TRAINERS"
+    };
+    let title = format!("LEADERBOARD - {scope} - BY {}", sort.label());
+    draw_text(
+        &title, 4, 2, "\x1b[97m", output, color_buf, zbuffer, width, height,
+    );
+    let header = format!(
+        "{:>4}  {:<16}  {:>9}  {:>7}  {:>11}  {}",
+        "RANK", "TRAINER", "DEX", "SHINIES", "LEGENDARIES", "COMPLETED"
+    );
+    draw_text(
+        &header, 4, 4, "\x1b[90m", output, color_buf, zbuffer, width, height,
+    );
+    let visible = entries.iter().enumerate().filter(|(_, entry)| {
+        Some(entry.trainer.as_str()) == me
+            || friends.is_none_or(|friends| friends.contains(&entry.trainer))
+    });
+    let rows = height.saturating_sub(10);
+    for (row, (rank, entry)) in visible.take(rows).enumerate() {
+        let complete = total > 0 && entry.caught >= total;
+        let completed = match entry.completed_at {
+            Some(at) => zone.format_date(at),
+            None if complete => "unknown".to_string(),
+            None => "-".to_string(),
+        };
+        let line = format!(
+            "{:>4}  {:<16}  {:>9}  {:>7}  {:>11}  {}",
+            rank + 1,
+            entry.trainer,
+            format!("{}/{}", entry.caught, total),
+            entry.shinies,
+            entry.legendaries,
+            completed
+        );
+        let color = if Some(entry.trainer.as_str()) == me {
+            "\x1b[96m"
+        } else if complete {
+            "\x1b[93m"
+        } else {
+            "\x1b[97m"
+        };
+        draw_text(
+            &line,
+            4,
+            5 + row,
+            color,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+    if let Some(notice) = notice {
+        draw_text(
+            notice,
+            4,
+            height.saturating_sub(3),
+            "\x1b[96m",
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+}
+
+/// The overworld map: links between routes as dotted paths, then each route
+/// as a numbered marker. The current route is `@`, locked ones `x` with the
+/// Pokedex count they need.
+#[allow(clippy::too_many_arguments)]
+fn render_map(
+    routes: &[routes::Route],
+    current: usize,
+    caught: usize,
+    notice: Option<&str>,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let title = format!("OVERWORLD MAP - {}", routes[current].name.to_uppercase());
+    draw_text(
+        &title, 4, 2, "\x1b[97m", output, color_buf, zbuffer, width, height,
+    );
+    let map_width = routes
+        .iter()
+        .map(|route| route.map.0 + 20)
+        .max()
+        .unwrap_or(0);
+    let map_height = routes
+        .iter()
+        .map(|route| route.map.1 + 1)
+        .max()
+        .unwrap_or(0);
+    let left = width.saturating_sub(map_width) / 2;
+    let top = 4 + height.saturating_sub(map_height + 8) / 2;
+    let mut plot = |x: usize, y: usize, ch: char, color: &'static str| {
+        let (x, y) = (left + x, top + y);
+        if x < width && y < height.saturating_sub(3) {
+            let idx = x + y * width;
+            output[idx] = ch;
+            color_buf[idx] = CellColor::Ansi(color);
+            zbuffer[idx] = 0.5;
+        }
+    };
+    for route in routes {
+        for link in &route.links {
+            let Some(other) = routes.iter().find(|other| other.id == *link) else {
+                continue;
+            };
+            // Down (or up) to the row halfway between, across, then on to the
+            // other route, so the path stays clear of both labels.
+            let ((x0, y0), (x1, y1)) = (route.map, other.map);
+            let mid = (y0 + y1) / 2;
+            for y in y0.min(mid)..=y0.max(mid) {
+                plot(x0, y, ':', "\x1b[90m");
+            }
+            for x in x0.min(x1)..=x0.max(x1) {
+                plot(x, mid, '.', "\x1b[90m");
+            }
+            for y in y1.min(mid)..=y1.max(mid) {
+                plot(x1, y, ':', "\x1b[90m");
+            }
+        }
+    }
+    for (idx, route) in routes.iter().enumerate() {
+        let locked = route.unlock > caught;
+        let (marker, color) = if idx == current {
+            ('@', "\x1b[96m")
+        } else if locked {
+            ('x', "\x1b[90m")
+        } else {
+            ('o', "\x1b[92m")
+        };
+        let label = if locked {
+            format!("{} {} ({})", idx + 1, route.name, route.unlock)
+        } else {
+            format!("{} {}", idx + 1, route.name)
+        };
+        let (x, y) = route.map;
+        plot(x, y, marker, color);
+        plot(x + 1, y, ' ', color);
+        for (i, ch) in label.chars().enumerate() {
+            plot(x + 2 + i, y, ch, color);
+        }
+    }
+    let notice = notice.map(str::to_string).unwrap_or_else(|| {
+        format!("{caught} caught. Locked routes show the Pokedex count they need.")
+    });
+    draw_text(
+        &notice,
+        4,
+        height.saturating_sub(3),
+        "\x1b[96m",
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+}
+
+/// Weather (and stars at night) drawn into empty cells behind the wild
+/// Pokemon. `frame` moves the rain, snow, sand and fog along.
+fn render_backdrop(
+    conditions: Conditions,
+    frame: u32,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let noise = |x: i64, y: i64, salt: u64| {
+        let mut state = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+            ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+            ^ salt;
+        next_u64(&mut state) % 100
+    };
+    let t = frame as i64;
+    for y in 2..height.saturating_sub(2) {
+        for x in 0..width {
+            let idx = x + y * width;
+            if output[idx] != ' ' {
+                continue;
+            }
+            let (xi, yi) = (x as i64, y as i64);
+            let cell = match conditions.weather {
+                Weather::Rain => {
+                    (noise(xi + yi - t, yi - 2 * t, 1) < 4).then_some(('/', "\x1b[94m"))
+                }
+                Weather::Snow => {
+                    let roll = noise(xi, yi - t / 4, 2);
+                    (roll < 3).then_some((if roll == 0 { '*' } else { '.' }, "\x1b[97m"))
+                }
+                Weather::Sandstorm => {
+                    let roll = noise(xi + 3 * t, yi, 3);
+                    (roll < 5).then_some((if roll < 2 { ',' } else { '.' }, "\x1b[33m"))
+                }
+                Weather::Fog => ((yi + t / 12) % 5 == 0 && noise(xi - t / 3, yi, 4) < 35)
+                    .then_some(('~', "\x1b[90m")),
+                Weather::Sun => (y < height / 3 && noise(xi, yi, 5) < 2 && (t / 10 + xi) % 4 != 0)
+                    .then_some(('`', "\x1b[93m")),
+                Weather::Clear => None,
+            };
+            let cell = cell.or_else(|| {
+                (conditions.time == TimeOfDay::Night
+                    && y < height / 3
+                    && noise(xi, yi, 6) < 2
+                    && (t / 15 + xi + yi) % 7 != 0)
+                    .then_some(('.', "\x1b[37m"))
+            });
+            if let Some((ch, color)) = cell {
+                output[idx] = ch;
+                color_buf[idx] = CellColor::Ansi(color);
+                zbuffer[idx] = -50.0;
+            }
         }
-        let mut chars = part.chars();
-        if let Some(first) = chars.next() {
-            out.push(first.to_ascii_uppercase());
-            out.extend(chars);
+    }
+}
+
+/// Today's quests in the top-left corner of the catch screen, under the ball
+/// counts.
+fn render_quests(
+    quests: &[quests::Quest],
+    streak: u32,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let title = format!("DAILY QUESTS - {streak} DAY STREAK",);
+    draw_text(
+        &title, 2, 3, "\x1b[90m", output, color_buf, zbuffer, width, height,
+    );
+    for (row, quest) in quests.iter().enumerate() {
+        let (mark, color) = if quest.done() {
+            ("[x]", "\x1b[92m")
+        } else {
+            ("[ ]", "\x1b[97m")
+        };
+        let line = format!(
+            "{mark} {} {}/{}  ({})",
+            quest.describe(),
+            quest.progress.min(quest.target),
+            quest.target,
+            quest.reward.describe()
+        );
+        draw_text(
+            &line,
+            2,
+            4 + row,
+            color,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+}
+
+/// Every badge in the catalog, earned ones first with the date they were
+/// unlocked. Returns the page actually shown, clamped to the last page.
+#[allow(clippy::too_many_arguments)]
+fn render_badges(
+    catalog: &[achievements::Badge],
+    unlocked: &HashMap<String, i64>,
+    page: usize,
+    streak: u32,
+    zone: &tz::Zone,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) -> usize {
+    let mut badges: Vec<(&achievements::Badge, Option<i64>)> = catalog
+        .iter()
+        .map(|badge| (badge, unlocked.get(&badge.id).copied()))
+        .collect();
+    badges.sort_by_key(|(_, at)| at.map_or(i64::MAX, |at| at));
+    let per_page = height.saturating_sub(10).max(1);
+    let pages = badges.len().div_ceil(per_page).max(1);
+    let page = page.min(pages - 1);
+    let title = format!(
+        "BADGES - {}/{} EARNED - STREAK {} DAY{} - PAGE {}/{}",
+        unlocked.len(),
+        catalog.len(),
+        streak,
+        if streak == 1 { "" } else { "S" },
+        page + 1,
+        pages
+    );
+    draw_text(
+        &title, 4, 2, "\x1b[97m", output, color_buf, zbuffer, width, height,
+    );
+    for (row, (badge, at)) in badges
+        .iter()
+        .skip(page * per_page)
+        .take(per_page)
+        .enumerate()
+    {
+        let (line, color) = match at {
+            Some(at) => (
+                format!(
+                    "[*] {:<20} {:<36} {}",
+                    badge.title,
+                    badge.description,
+                    zone.format_date(*at)
+                ),
+                "\x1b[93m",
+            ),
+            None => (
+                format!("[ ] {:<20} {}", badge.title, badge.description),
+                "\x1b[90m",
+            ),
+        };
+        draw_text(
+            &line,
+            4,
+            4 + row,
+            color,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+    page
+}
+
+fn activity_text(label: &str) -> &str {
+    match label {
+        "game" => "catching Pokemon",
+        "battle" => "in a battle",
+        "trade" => "trading",
+        "pokedex" | "pokedex_detail" => "browsing their Pokedex",
+        "lobby" => "in the lobby",
+        other => other,
+    }
+}
+
+/// One page of the trainer's captures, numbered across the whole filtered
+/// list. Returns the page clamped to the pages there are.
+#[allow(clippy::too_many_arguments)]
+fn render_box(
+    entries: &[&Capture],
+    owned: usize,
+    sort: BoxSort,
+    filter: &BoxFilter,
+    page: usize,
+    releasing: Option<i64>,
+    notice: Option<&str>,
+    zone: &tz::Zone,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) -> usize {
+    let per_page = height.saturating_sub(10).max(1);
+    let pages = entries.len().div_ceil(per_page).max(1);
+    let page = page.min(pages - 1);
+    let title = format!(
+        "PC BOX - {} OF {} POKEMON - {} - BY {} - PAGE {}/{}",
+        entries.len(),
+        owned,
+        filter.label(),
+        sort.label(),
+        page + 1,
+        pages
+    );
+    draw_text(
+        &title, 4, 2, "\x1b[97m", output, color_buf, zbuffer, width, height,
+    );
+    let header = format!(
+        "{:>4}  {:<12}  {:<12}  {:>3}  {:<5}  {}",
+        "#", "NAME", "SPECIES", "LV", "SHINY", "CAUGHT"
+    );
+    draw_text(
+        &header, 4, 4, "\x1b[90m", output, color_buf, zbuffer, width, height,
+    );
+    if entries.is_empty() {
+        let empty = if owned == 0 {
+            "Your box is empty. Go catch some Pokemon!"
+        } else {
+            "No Pokemon match that filter. Type 'filter all' to see everyone."
+        };
+        draw_text(
+            empty, 4, 5, "\x1b[90m", output, color_buf, zbuffer, width, height,
+        );
+    }
+    let start = page * per_page;
+    for (row, capture) in entries.iter().skip(start).take(per_page).enumerate() {
+        let caught = if capture.caught_at > 0 {
+            zone.format_date(capture.caught_at)
+        } else {
+            "-".to_string()
+        };
+        let line = format!(
+            "{:>4}  {:<12}  {:<12}  {:>3}  {:<5}  {}",
+            start + row + 1,
+            capture.nickname.as_deref().unwrap_or("-"),
+            display_pokemon_name(&capture.species),
+            capture.level,
+            if capture.shiny { "  *" } else { "" },
+            caught
+        );
+        let color = if releasing == Some(capture.id) {
+            "\x1b[91m"
+        } else if capture.shiny {
+            "\x1b[93m"
+        } else {
+            "\x1b[97m"
+        };
+        draw_text(
+            &line,
+            4,
+            5 + row,
+            color,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+    if let Some(notice) = notice {
+        draw_text(
+            notice,
+            4,
+            height.saturating_sub(3),
+            "\x1b[96m",
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+    page
+}
+
+/// Everyone connected under a trainer name and the screen they are on.
+fn render_lobby(
+    online: &[(String, &'static str)],
+    me: Option<&str>,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let title = format!("TRAINERS ONLINE ({})", online.len());
+    draw_text(
+        &title, 4, 2, "\x1b[97m", output, color_buf, zbuffer, width, height,
+    );
+    let rows = height.saturating_sub(8);
+    for (i, (name, activity)) in online.iter().enumerate().take(rows) {
+        let row = 4 + i;
+        if i + 1 == rows && online.len() > rows {
+            let more = format!("... and {} more", online.len() - i);
+            draw_text(
+                &more, 4, row, "\x1b[90m", output, color_buf, zbuffer, width, height,
+            );
+            break;
+        }
+        let (marker, color) = if Some(name.as_str()) == me {
+            (" (you)", "\x1b[96m")
+        } else {
+            ("", "\x1b[97m")
+        };
+        let line = format!(
+            "{:<24}{}",
+            format!("{name}{marker}"),
+            activity_text(activity)
+        );
+        draw_text(
+            &line, 4, row, color, output, color_buf, zbuffer, width, height,
+        );
+    }
+}
+
+/// The trainer's captures down the left, numbered for `offer`; both offers
+/// side by side on the right with the trade status underneath.
+#[allow(clippy::too_many_arguments)]
+fn render_trade(
+    trade: &Trade,
+    candidates: &[&Capture],
+    assets: &Assets,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    const STEP: usize = 2;
+    let title = format!("TRADING WITH {}", trade.partner.to_uppercase());
+    draw_text(
+        &title, 4, 2, "\x1b[97m", output, color_buf, zbuffer, width, height,
+    );
+    if trade.stage == Stage::Requested {
+        let waiting = format!("Waiting for {} to accept...", trade.partner);
+        draw_text(
+            &waiting,
+            width.saturating_sub(waiting.len()) / 2,
+            height / 3,
+            "\x1b[96m",
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+        return;
+    }
+
+    let list_rows = height.saturating_sub(10);
+    let offered = trade.mine.as_ref().map(|offer| offer.capture_id);
+    for (i, capture) in candidates.iter().enumerate().take(list_rows) {
+        let row = 4 + i;
+        if i + 1 == list_rows && candidates.len() > list_rows {
+            let more = format!("... and {} more", candidates.len() - i);
+            draw_text(
+                &more, 4, row, "\x1b[90m", output, color_buf, zbuffer, width, height,
+            );
+            break;
+        }
+        let line = format!(
+            "{:>3}  {:<14} Lv{}",
+            i + 1,
+            display_pokemon_name(&capture.species),
+            capture.level
+        );
+        let color = if offered == Some(capture.id) {
+            "\x1b[96m"
+        } else {
+            "\x1b[97m"
+        };
+        draw_text(
+            &line, 4, row, color, output, color_buf, zbuffer, width, height,
+        );
+    }
+
+    let columns = [
+        ("YOU OFFER".to_string(), trade.mine.as_ref(), 40),
+        (
+            format!("{} OFFERS", trade.partner.to_uppercase()),
+            trade.theirs.as_ref(),
+            40 + width.saturating_sub(40) / 2,
+        ),
+    ];
+    for (label, offer, x) in columns {
+        draw_text(
+            &label, x, 4, "\x1b[97m", output, color_buf, zbuffer, width, height,
+        );
+        let Some(offer) = offer else {
+            draw_text(
+                "(nothing yet)",
+                x,
+                5,
+                "\x1b[90m",
+                output,
+                color_buf,
+                zbuffer,
+                width,
+                height,
+            );
+            continue;
+        };
+        let name = format!(
+            "{}  Lv{}",
+            display_pokemon_name(&offer.species).to_uppercase(),
+            offer.level
+        );
+        draw_text(
+            &name, x, 5, "\x1b[96m", output, color_buf, zbuffer, width, height,
+        );
+        if let Some(asset) = find_pokemon_asset(assets, &offer.species) {
+            draw_sprite_sampled(
+                &asset.image,
+                x,
+                7,
+                STEP,
+                output,
+                color_buf,
+                zbuffer,
+                width,
+                height,
+            );
+        }
+    }
+
+    let (status, color) = if let Some(notice) = trade.notice.as_ref() {
+        (notice.clone(), "\x1b[91m")
+    } else if trade.mine.is_none() {
+        (
+            "Pick a Pokemon to offer by its number.".to_string(),
+            "\x1b[97m",
+        )
+    } else if trade.theirs.is_none() {
+        (
+            format!("Waiting for {} to offer...", trade.partner),
+            "\x1b[97m",
+        )
+    } else if trade.confirmed {
+        (
+            format!("Waiting for {} to confirm...", trade.partner),
+            "\x1b[97m",
+        )
+    } else if trade.partner_confirmed {
+        (
+            format!("{} confirmed. Type 'confirm' to trade!", trade.partner),
+            "\x1b[92m",
+        )
+    } else {
+        (
+            "Type 'confirm' when you're happy with the trade.".to_string(),
+            "\x1b[97m",
+        )
+    };
+    draw_text(
+        &status,
+        40,
+        height.saturating_sub(4),
+        color,
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_pokedex(
+    view: &PokedexView,
+    caught: &HashSet<String>,
+    seen: &HashMap<String, i64>,
+    region_idx: usize,
+    page: usize,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let Some(region) = view.regions.get(region_idx) else {
+        return;
+    };
+    let grid_w = POKEDEX_COLS * POKEDEX_CELL_W;
+    let grid_h = POKEDEX_ROWS * POKEDEX_CELL_H;
+    let start_x = (width.saturating_sub(grid_w)) / 2;
+    let start_y = (height.saturating_sub(grid_h)) / 2;
+
+    let pages = region.len().div_ceil(POKEDEX_PAGE_SIZE).max(1);
+    let header = format!(
+        "{}  #{}-{}  page {}/{}",
+        region.name.to_uppercase(),
+        region.first,
+        region.last,
+        page + 1,
+        pages
+    );
+    let header_row = start_y.saturating_sub(2);
+    for (i, ch) in header.chars().enumerate() {
+        let x = start_x + i;
+        if x >= width || header_row >= height {
+            continue;
+        }
+        let idx = x + header_row * width;
+        output[idx] = ch;
+        color_buf[idx] = CellColor::Ansi("\x1b[93m");
+        zbuffer[idx] = 0.35;
+    }
+
+    let first = region.first + page * POKEDEX_PAGE_SIZE;
+    let last = (first + POKEDEX_PAGE_SIZE - 1).min(region.last);
+    for (slot, number) in (first..=last).enumerate() {
+        let row = slot / POKEDEX_COLS;
+        let col = slot % POKEDEX_COLS;
+        let base_x = start_x + col * POKEDEX_CELL_W;
+        let base_y = start_y + row * POKEDEX_CELL_H;
+        let digits: Vec<char> = number.to_string().chars().collect();
+        let number_w = digits.len();
+        let offset_x = base_x + (POKEDEX_CELL_W.saturating_sub(number_w)) / 2;
+        let offset_y = base_y;
+
+        let name = view.names.get(number - 1).map(|s| s.as_str()).unwrap_or("");
+        let main = if name.is_empty() {
+            "\x1b[90m"
+        } else if caught.contains(name) {
+            "\x1b[91m"
+        } else if seen.contains_key(name) {
+            "\x1b[37m"
+        } else {
+            "\x1b[90m"
+        };
+
+        for (d, digit) in digits.iter().enumerate() {
+            let target_x = offset_x + d;
+            let target_y = offset_y;
+            if target_x >= width || target_y >= height {
+                continue;
+            }
+            let idx = target_x + target_y * width;
+            output[idx] = *digit;
+            color_buf[idx] = CellColor::Ansi(main);
+            zbuffer[idx] = 0.35;
+        }
+    }
+
+    let legend_row = start_y + grid_h;
+    let mut x = start_x;
+    for (label, color) in [
+        ("CAUGHT", "\x1b[91m"),
+        ("SEEN", "\x1b[37m"),
+        ("UNSEEN", "\x1b[90m"),
+    ] {
+        draw_text(
+            label, x, legend_row, color, output, color_buf, zbuffer, width, height,
+        );
+        x += label.len() + 3;
+    }
+}
+
+/// Twinkles a handful of stars around the sprite's bounding box; each star
+/// moves to a new spot every few frames.
+#[allow(clippy::too_many_arguments)]
+fn render_sparkles(
+    image: &ascii::AsciiImage,
+    start_x: usize,
+    start_y: usize,
+    frame: u32,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    const SPARKLES: u64 = 8;
+    const PHASES: [char; 4] = ['.', '+', '*', '+'];
+    let box_w = image.width + 4;
+    let box_h = image.height + 2;
+    for i in 0..SPARKLES {
+        let cycle = (frame / 16) as u64 + i * 3;
+        let mut rng = (cycle << 8 | i).wrapping_mul(0x9E3779B97F4A7C15) | 1;
+        let step = ((frame / 4) as u64 + i) % PHASES.len() as u64;
+        let x = start_x as i64 - 2 + (next_u64(&mut rng) % box_w as u64) as i64;
+        let y = start_y as i64 - 1 + (next_u64(&mut rng) % box_h as u64) as i64;
+        if x < 0 || y < 0 || x as usize >= width || y as usize >= height {
+            continue;
+        }
+        let idx = x as usize + y as usize * width;
+        output[idx] = PHASES[step as usize];
+        color_buf[idx] = CellColor::Ansi(if i % 2 == 0 { "\x1b[93m" } else { "\x1b[97m" });
+        zbuffer[idx] = 0.45;
+    }
+}
+
+/// A Pokedex entry: the sprite on the left with the catch record under its
+/// name, and the species' field notes on the right. Entries that have only
+/// been seen show a silhouette and their name.
+#[allow(clippy::too_many_arguments)]
+fn render_pokedex_detail(
+    assets: &Assets,
+    idx: usize,
+    caught: bool,
+    shiny: bool,
+    record: Option<&str>,
+    output: &mut [char],
+    color_buf: &mut [CellColor],
+    zbuffer: &mut [f32],
+    width: usize,
+    height: usize,
+) {
+    let name = assets
+        .pokedex
+        .names
+        .get(idx)
+        .map(|s| s.as_str())
+        .unwrap_or("");
+    let mut display_name = if name.is_empty() {
+        "Unknown Pokemon".to_string()
+    } else {
+        display_pokemon_name(name)
+    };
+    if shiny {
+        display_name.push_str("  * SHINY *");
+    }
+    let half = width / 2;
+    let name_color = if shiny {
+        "\x1b[93m"
+    } else if caught {
+        "\x1b[92m"
+    } else {
+        "\x1b[37m"
+    };
+
+    let name_row = if let Some(asset) = find_pokemon_asset(assets, name) {
+        let image = if shiny { &asset.shiny } else { &asset.image };
+        let step = if image.width + 4 <= half && image.height + 6 <= height {
+            1
+        } else {
+            2
+        };
+        let (sprite_w, sprite_h) = (image.width / step, image.height / step);
+        let start_x = half.saturating_sub(sprite_w) / 2;
+        let start_y = (height.saturating_sub(sprite_h + 4) / 2).max(1);
+        for y in 0..sprite_h {
+            for x in 0..sprite_w {
+                let target_y = start_y + y;
+                let target_x = start_x + x;
+                if target_y >= height || target_x >= width {
+                    continue;
+                }
+                let src_idx = x * step + y * step * image.width;
+                let ch = image.chars[src_idx];
+                if ch == ' ' {
+                    continue;
+                }
+                let idx = target_x + target_y * width;
+                if caught {
+                    output[idx] = ch;
+                    let (r, g, b) = image.colors[src_idx];
+                    color_buf[idx] = CellColor::Rgb(r, g, b);
+                } else {
+                    output[idx] = '#';
+                    color_buf[idx] = CellColor::Ansi("\x1b[90m");
+                }
+                zbuffer[idx] = 0.4;
+            }
+        }
+        (start_y + sprite_h + 1).min(height.saturating_sub(3))
+    } else {
+        let notice = "Sprite not available yet";
+        let row = height / 2;
+        draw_text(
+            notice,
+            half.saturating_sub(notice.len()) / 2,
+            row,
+            "\x1b[97m",
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+        row + 2
+    };
+    draw_text(
+        &display_name,
+        half.saturating_sub(display_name.len()) / 2,
+        name_row,
+        name_color,
+        output,
+        color_buf,
+        zbuffer,
+        width,
+        height,
+    );
+    if let Some(record) = record {
+        let lines = word_wrap(record, half.saturating_sub(4).max(20));
+        for (i, line) in lines.iter().take(2).enumerate() {
+            draw_text(
+                line,
+                half.saturating_sub(line.len()) / 2,
+                name_row + 1 + i,
+                "\x1b[97m",
+                output,
+                color_buf,
+                zbuffer,
+                width,
+                height,
+            );
+        }
+    }
+
+    let panel_x = half + 2;
+    let panel_w = width.saturating_sub(panel_x + 2).max(20);
+    let mut lines: Vec<(String, &'static str)> = vec![(
+        format!(
+            "#{:03}  {}",
+            idx + 1,
+            display_pokemon_name(name).to_uppercase()
+        ),
+        "\x1b[97m",
+    )];
+    let info = assets.pokedex.info.get(name);
+    if let Some(genus) = info.and_then(|info| info.genus.as_ref()) {
+        lines.push((genus.to_uppercase(), "\x1b[90m"));
+    }
+    lines.push((String::new(), ""));
+    if caught {
+        pokedex_detail_notes(assets, name, panel_w, &mut lines);
+    } else {
+        lines.push(("Catch it to fill in this entry.".to_string(), "\x1b[90m"));
+    }
+    for (row, (text, color)) in lines.iter().enumerate() {
+        draw_text(
+            text,
+            panel_x,
+            2 + row,
+            color,
+            output,
+            color_buf,
+            zbuffer,
+            width,
+            height,
+        );
+    }
+}
+
+/// Types, size, base stat bars, flavor text, and evolution line for a caught
+/// species, as lines of the detail page's right-hand panel.
+fn pokedex_detail_notes(
+    assets: &Assets,
+    name: &str,
+    panel_w: usize,
+    lines: &mut Vec<(String, &'static str)>,
+) {
+    let info = assets.pokedex.info.get(name);
+    if let Some(types) = assets.pokedex.types_by_name.get(name) {
+        lines.push((types.join(" / ").to_uppercase(), "\x1b[93m"));
+    }
+    let measure = |value: Option<f32>, unit: &str| {
+        value.map_or("?".to_string(), |value| format!("{value:.1} {unit}"))
+    };
+    lines.push((
+        format!(
+            "HEIGHT {}   WEIGHT {}",
+            measure(info.and_then(|info| info.height_m), "m"),
+            measure(info.and_then(|info| info.weight_kg), "kg")
+        ),
+        "\x1b[97m",
+    ));
+    lines.push((String::new(), ""));
+
+    if let Some(stats) = assets.pokedex.stats_by_name.get(name) {
+        let bar_w = panel_w.saturating_sub(12).clamp(5, 30);
+        for (label, value) in [
+            ("HP", stats.hp),
+            ("ATK", stats.attack),
+            ("DEF", stats.defense),
+            ("SP.ATK", stats.sp_attack),
+            ("SP.DEF", stats.sp_defense),
+            ("SPD", stats.speed),
+        ] {
+            let filled = (value as usize * bar_w).div_ceil(255).min(bar_w);
+            let color = match value {
+                0..=59 => "\x1b[91m",
+                60..=89 => "\x1b[93m",
+                _ => "\x1b[92m",
+            };
+            lines.push((
+                format!(
+                    "{label:<6} {value:>3} {}{}",
+                    "#".repeat(filled),
+                    ".".repeat(bar_w - filled)
+                ),
+                color,
+            ));
+        }
+        if let Some(total) = assets.pokedex.totals_by_name.get(name) {
+            lines.push((format!("{:<6} {total:>3}", "TOTAL"), "\x1b[97m"));
+        }
+        lines.push((String::new(), ""));
+    }
+
+    match info.and_then(|info| info.flavor.as_ref()) {
+        Some(flavor) => {
+            for text in word_wrap(flavor, panel_w).into_iter().take(4) {
+                lines.push((text, "\x1b[96m"));
+            }
+        }
+        None => lines.push((
+            "No field notes for this species yet.".to_string(),
+            "\x1b[90m",
+        )),
+    }
+    lines.push((String::new(), ""));
+
+    let chain = assets.pokedex.evolution_chain(name);
+    if chain.len() > 1 {
+        let stages: Vec<String> = chain
+            .iter()
+            .map(|(stage, trigger)| {
+                let mut label = display_pokemon_name(stage);
+                if *stage == name {
+                    label = format!("[{label}]");
+                }
+                match trigger {
+                    Some(dex::EvolutionTrigger::Level(level)) => format!("{label} (Lv{level})"),
+                    Some(dex::EvolutionTrigger::Trade) => format!("{label} (trade)"),
+                    None => label,
+                }
+            })
+            .collect();
+        lines.push(("EVOLUTION".to_string(), "\x1b[90m"));
+        for text in word_wrap(&stages.join(" > "), panel_w).into_iter().take(3) {
+            lines.push((text, "\x1b[97m"));
         }
+    } else {
+        lines.push(("Does not evolve.".to_string(), "\x1b[90m"));
     }
-    out
 }
 
-fn is_agent_stats_query(phrase: &str) -> bool {
-    let phrase = phrase.trim();
-    phrase.contains("stats")
-        || phrase.contains("base stat")
-        || (phrase.contains("hp") && phrase.contains("atk"))
-        || phrase.contains("how strong")
-        || phrase.contains("what are its stats")
-        || phrase.contains("show me stats")
+fn sanitize_trainer_name(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        return None;
+    }
+    if trimmed.len() > 16 {
+        return None;
+    }
+    if !trimmed
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    {
+        return None;
+    }
+    Some(trimmed.to_lowercase())
 }
 
-fn is_agent_pokemon_query(phrase: &str) -> bool {
-    let phrase = phrase.trim();
-    phrase.contains("what pokemon is this")
-        || phrase.contains("who is this pokemon")
-        || phrase.contains("what is this creature")
-        || phrase.contains("what is this pokemon")
-        || phrase.contains("identify this")
-        || phrase.contains("tell me about this")
-        || phrase.contains("what the hell is this")
+/// Adds a column to a table created by an earlier version, if it is missing.
+fn add_column(conn: &Connection, table: &str, column: &str, decl: &str) -> io::Result<()> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA table_info({table})"))
+        .map_err(io::Error::other)?;
+    let exists = stmt
+        .query_map([], |row| row.get::<_, String>(1))
+        .map_err(io::Error::other)?
+        .filter_map(Result::ok)
+        .any(|name| name == column);
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE {table} ADD COLUMN {column} {decl}"),
+            [],
+        )
+        .map_err(io::Error::other)?;
+    }
+    Ok(())
 }
 
-fn is_agent_left_to_catch_query(phrase: &str) -> bool {
-    let phrase = phrase.trim();
-    phrase.contains("how many")
-        && (phrase.contains("left")
-            || phrase.contains("remaining")
-            || phrase.contains("to catch")
-            || phrase.contains("missing"))
+async fn init_db() -> io::Result<()> {
+    task::spawn_blocking(|| -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(io::Error::other)?;
+        trainer_store().init()?;
+        store::adopt_sqlite_trainers(db_path(), trainer_store())?;
+        create_schema(&conn)
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn is_agent_caught_count_query(phrase: &str) -> bool {
-    let phrase = phrase.trim();
-    (phrase.contains("how many") || phrase.contains("count"))
-        && (phrase.contains("caught") || phrase.contains("have i"))
+fn create_schema(conn: &Connection) -> io::Result<()> {
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS captures (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            trainer TEXT NOT NULL,
+            species TEXT NOT NULL,
+            level INTEGER NOT NULL,
+            xp INTEGER NOT NULL,
+            caught_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    add_column(conn, "captures", "shiny", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column(conn, "captures", "nickname", "TEXT")?;
+    add_column(conn, "captures", "ball", "TEXT")?;
+    add_column(conn, "captures", "session_id", "INTEGER")?;
+    let has_play_sessions: bool = conn
+        .query_row(
+            "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'play_sessions')",
+            [],
+            |row| row.get(0),
+        )
+        .map_err(io::Error::other)?;
+    if !has_play_sessions {
+        // Session ids stored before this table existed were per-process counters.
+        conn.execute("UPDATE captures SET session_id = NULL", [])
+            .map_err(io::Error::other)?;
+    }
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS play_sessions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            trainer TEXT NOT NULL,
+            started_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS captures_trainer ON captures (trainer)",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    // Trainers whose Pokedex has been copied into captures. Anyone who
+    // already has captures needs no copy.
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS capture_backfill (
+            trainer TEXT PRIMARY KEY,
+            backfilled_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "INSERT OR IGNORE INTO capture_backfill (trainer, backfilled_at)
+         SELECT DISTINCT trainer, 0 FROM captures",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS shinies (
+            trainer TEXT NOT NULL,
+            species TEXT NOT NULL,
+            PRIMARY KEY (trainer, species)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS seen (
+            trainer TEXT NOT NULL,
+            species TEXT NOT NULL,
+            seen_at INTEGER NOT NULL,
+            PRIMARY KEY (trainer, species)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS encounters (
+            trainer TEXT NOT NULL,
+            day INTEGER NOT NULL,
+            slot INTEGER NOT NULL,
+            species TEXT NOT NULL,
+            outcome TEXT NOT NULL,
+            PRIMARY KEY (trainer, day, slot)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trainer_settings (
+            trainer TEXT PRIMARY KEY,
+            allow_spectators INTEGER NOT NULL DEFAULT 1
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS encounter_reveals (
+            trainer TEXT NOT NULL,
+            day INTEGER NOT NULL,
+            slot INTEGER NOT NULL,
+            revealed_at INTEGER NOT NULL,
+            PRIMARY KEY (trainer, day, slot)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    add_column(conn, "encounter_reveals", "route", "TEXT")?;
+    add_column(conn, "trainer_settings", "timezone", "TEXT")?;
+    add_column(conn, "trainer_settings", "timezone_changed_at", "INTEGER")?;
+    add_column(conn, "trainer_settings", "route", "TEXT")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS leaderboard (
+            trainer TEXT PRIMARY KEY,
+            caught INTEGER NOT NULL,
+            shinies INTEGER NOT NULL,
+            legendaries INTEGER NOT NULL,
+            completed_at INTEGER,
+            updated_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS achievements (
+            trainer TEXT NOT NULL,
+            badge TEXT NOT NULL,
+            unlocked_at INTEGER NOT NULL,
+            PRIMARY KEY (trainer, badge)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS logins (
+            trainer TEXT PRIMARY KEY,
+            last_day INTEGER NOT NULL,
+            streak INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS quests (
+            trainer TEXT NOT NULL,
+            day INTEGER NOT NULL,
+            slot INTEGER NOT NULL,
+            goal TEXT NOT NULL,
+            target INTEGER NOT NULL,
+            progress INTEGER NOT NULL,
+            reward TEXT NOT NULL,
+            PRIMARY KEY (trainer, day, slot)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    add_column(conn, "quests", "asked", "TEXT NOT NULL DEFAULT ''")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS friends (
+            trainer TEXT NOT NULL,
+            friend TEXT NOT NULL,
+            PRIMARY KEY (trainer, friend)
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS trades (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            trainer_a TEXT NOT NULL,
+            capture_a INTEGER NOT NULL,
+            species_a TEXT NOT NULL,
+            trainer_b TEXT NOT NULL,
+            capture_b INTEGER NOT NULL,
+            species_b TEXT NOT NULL,
+            traded_at INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS inventories (
+            trainer TEXT PRIMARY KEY,
+            poke INTEGER NOT NULL,
+            great INTEGER NOT NULL,
+            ultra INTEGER NOT NULL,
+            master INTEGER NOT NULL,
+            restock_day INTEGER NOT NULL
+        )",
+        [],
+    )
+    .map_err(io::Error::other)?;
+    Ok(())
 }
 
-fn is_agent_missing_list_query(phrase: &str) -> bool {
-    let phrase = phrase.trim();
-    phrase.contains("which pokemon am i missing")
-        || phrase.contains("what am i missing")
-        || (phrase.contains("show") && phrase.contains("missing"))
+async fn load_pokedex(name: &str) -> io::Result<HashSet<String>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || trainer_store().load_pokedex(&name))
+        .await
+        .map_err(io::Error::other)?
 }
 
-fn looks_like_agent_query_candidate(text: &str) -> bool {
-    let text = text.trim().to_lowercase();
-    if text.is_empty() {
-        return false;
-    }
-    if matches!(
-        text.as_str(),
-        "catch" | "pokedex" | "dex" | "back" | "q" | "quit" | "exit"
-    ) {
-        return false;
-    }
-    if text.chars().all(|ch| ch.is_ascii_digit()) {
-        return false;
-    }
-    text.ends_with('?')
-        || text.contains(' ')
-        || matches!(text.as_str(), "help" | "status" | "progress" | "missing")
+async fn save_pokedex(name: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+    let name = name.to_string();
+    let pokedex = pokedex.clone();
+    task::spawn_blocking(move || trainer_store().save_pokedex(&name, &pokedex))
+        .await
+        .map_err(io::Error::other)?
 }
 
-fn word_wrap(text: &str, max_width: usize) -> Vec<String> {
-    let mut lines = Vec::new();
-    let mut current = String::new();
-    for word in text.split_whitespace() {
-        if current.is_empty() {
-            current = word.to_string();
-        } else if current.len() + 1 + word.len() <= max_width {
-            current.push(' ');
-            current.push_str(word);
-        } else {
-            lines.push(current);
-            current = word.to_string();
-        }
-    }
-    if !current.is_empty() {
-        lines.push(current);
-    }
-    lines
+/// Loads a trainer's captures. Trainers whose Pokedex predates captures get
+/// one capture per caught species at `LEGACY_LEVEL`, once.
+async fn load_captures(name: &str, pokedex: &HashSet<String>) -> io::Result<Vec<Capture>> {
+    let name = name.to_string();
+    let mut legacy: Vec<String> = pokedex.iter().cloned().collect();
+    legacy.sort();
+    task::spawn_blocking(move || -> io::Result<Vec<Capture>> {
+        let mut conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        read_captures(&mut conn, &name, &legacy)
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn fast_hash(data: &[u8]) -> u64 {
-    let mut hash: u64 = 0xcbf29ce484222325;
-    for &b in data {
-        hash ^= b as u64;
-        hash = hash.wrapping_mul(0x100000001b3);
+/// The backfill runs only for trainers not yet in `capture_backfill`, and
+/// marks them in the same transaction, so releasing every capture later does
+/// not bring the Pokedex back as new captures.
+fn read_captures(conn: &mut Connection, name: &str, legacy: &[String]) -> io::Result<Vec<Capture>> {
+    let tx = conn.transaction().map_err(io::Error::other)?;
+    let marked = tx
+        .execute(
+            "INSERT OR IGNORE INTO capture_backfill (trainer, backfilled_at) VALUES (?1, ?2)",
+            (name, unix_now()),
+        )
+        .map_err(io::Error::other)?
+        > 0;
+    if marked {
+        let existing: i64 = tx
+            .query_row(
+                "SELECT COUNT(*) FROM captures WHERE trainer = ?1",
+                [name],
+                |row| row.get(0),
+            )
+            .map_err(io::Error::other)?;
+        if existing == 0 {
+            for species in legacy {
+                tx.execute(
+                    "INSERT INTO captures (trainer, species, level, xp, caught_at)
+                     VALUES (?1, ?2, ?3, ?4, 0)",
+                    (
+                        name,
+                        species,
+                        levels::LEGACY_LEVEL,
+                        levels::xp_for_level(levels::LEGACY_LEVEL),
+                    ),
+                )
+                .map_err(io::Error::other)?;
+            }
+        }
     }
-    hash
+    tx.commit().map_err(io::Error::other)?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, species, level, xp, caught_at, shiny, nickname, ball, session_id
+             FROM captures WHERE trainer = ?1 ORDER BY id",
+        )
+        .map_err(io::Error::other)?;
+    let rows = stmt
+        .query_map([name], |row| {
+            Ok(Capture {
+                id: row.get(0)?,
+                species: row.get(1)?,
+                level: row.get(2)?,
+                xp: row.get(3)?,
+                caught_at: row.get(4)?,
+                shiny: row.get(5)?,
+                nickname: row.get(6)?,
+                ball: row
+                    .get::<_, Option<String>>(7)?
+                    .as_deref()
+                    .and_then(BallKind::parse),
+                session_id: row.get(8)?,
+            })
+        })
+        .map_err(io::Error::other)?;
+    rows.collect::<Result<_, _>>().map_err(io::Error::other)
 }
 
-fn normalize_whitespace(text: &str) -> String {
-    text.split_whitespace().collect::<Vec<_>>().join(" ")
+/// Records a login and returns its id, which captures made during it carry.
+async fn start_play_session(name: &str) -> io::Result<i64> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<i64> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT INTO play_sessions (trainer, started_at) VALUES (?1, ?2)",
+            (&name, unix_now()),
+        )
+        .map_err(io::Error::other)?;
+        Ok(conn.last_insert_rowid())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn clip_text(text: &str, max_chars: usize) -> String {
-    let len = text.chars().count();
-    if len <= max_chars {
-        return text.to_string();
-    }
-    if max_chars <= 3 {
-        return text.chars().take(max_chars).collect();
-    }
-    let mut out = String::new();
-    for ch in text.chars().take(max_chars - 3) {
-        out.push(ch);
-    }
-    out.push_str("...");
-    out
+async fn insert_capture(name: &str, capture: &Capture) -> io::Result<i64> {
+    let name = name.to_string();
+    let capture = capture.clone();
+    task::spawn_blocking(move || -> io::Result<i64> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT INTO captures
+                (trainer, species, level, xp, caught_at, shiny, ball, session_id)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            (
+                &name,
+                &capture.species,
+                capture.level,
+                capture.xp,
+                capture.caught_at,
+                capture.shiny,
+                capture.ball.map(BallKind::key),
+                capture.session_id,
+            ),
+        )
+        .map_err(io::Error::other)?;
+        Ok(conn.last_insert_rowid())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn missing_pokemon_preview(caught: &HashSet<String>, assets: &Assets, limit: usize) -> Vec<String> {
-    assets
-        .pokedex
-        .names
-        .iter()
-        .filter(|name| !name.is_empty() && !caught.contains(*name))
-        .take(limit)
-        .map(|name| display_pokemon_name(name))
-        .collect()
+async fn update_capture(name: &str, capture: &Capture) -> io::Result<()> {
+    let name = name.to_string();
+    let capture = capture.clone();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "UPDATE captures SET species = ?1, level = ?2, xp = ?3 WHERE id = ?4 AND trainer = ?5",
+            (
+                &capture.species,
+                capture.level,
+                capture.xp,
+                capture.id,
+                &name,
+            ),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn ollama_url() -> String {
-    env::var("OLLAMA_URL").unwrap_or_else(|_| "http://127.0.0.1:11434".to_string())
+async fn save_nickname(name: &str, id: i64, nickname: Option<&str>) -> io::Result<()> {
+    let name = name.to_string();
+    let nickname = nickname.map(str::to_string);
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "UPDATE captures SET nickname = ?1 WHERE id = ?2 AND trainer = ?3",
+            (&nickname, id, &name),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn ollama_model() -> String {
-    env::var("OLLAMA_MODEL").unwrap_or_else(|_| "qwen2.5:1.5b".to_string())
+/// Removes a capture, returning false if the trainer no longer owned it. The
+/// Pokedex is left alone so the species still counts as caught.
+async fn delete_capture(name: &str, id: i64) -> io::Result<bool> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<bool> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        remove_capture(&conn, &name, id)
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-async fn ask_llm_brief(
-    user_query: &str,
-    screen: &str,
-    trainer_name: Option<&str>,
-    current_pokemon: Option<&str>,
-    caught_count: usize,
-    total_count: usize,
-    left_count: usize,
-) -> Option<String> {
-    let prompt = format!(
-        "You are Pokestream Agent inside a telnet Pokemon game. \
-        Keep responses concise, under 200 characters. Only answer in-game questions. \
-        Context: screen={screen}, trainer={}, pokemon={}, caught={caught_count}/{total_count}, left={left_count}.\n\
-        User: {user_query}",
-        trainer_name.unwrap_or("unknown"),
-        current_pokemon.unwrap_or("unknown"),
-    );
-
-    let payload = serde_json::json!({
-        "model": ollama_model(),
-        "prompt": prompt,
-        "stream": false,
-        "keep_alive": "10m",
-        "options": {
-            "temperature": 0.3,
-            "num_predict": 40,
-        }
-    });
+fn remove_capture(conn: &Connection, name: &str, id: i64) -> io::Result<bool> {
+    let deleted = conn
+        .execute(
+            "DELETE FROM captures WHERE id = ?1 AND trainer = ?2",
+            (id, name),
+        )
+        .map_err(io::Error::other)?;
+    Ok(deleted > 0)
+}
 
-    let url = format!("{}/api/generate", ollama_url());
-    let client = shared_http_client();
-    let response = match tokio::time::timeout(
-        Duration::from_secs(8),
-        client.post(&url).json(&payload).send(),
-    )
+/// One side of a trade: who offered which capture, who receives it, and
+/// what it evolves into on arrival.
+type TradeSide = (String, TradeOffer, String, Option<String>);
+
+/// Swaps two captures between trainers: both must still belong to the trainer
+/// that offered them, and the trade is logged. Received species (and their
+/// trade evolutions) go into the receiver's Pokedex through the trainer store,
+/// and a shiny capture counts toward the receiver's shinies.
+async fn save_trade(
+    trainer_a: &str,
+    offer_a: &TradeOffer,
+    into_a: Option<&str>,
+    trainer_b: &str,
+    offer_b: &TradeOffer,
+    into_b: Option<&str>,
+) -> io::Result<()> {
+    let sides = [
+        (
+            trainer_a.to_string(),
+            offer_a.clone(),
+            trainer_b.to_string(),
+            into_a.map(str::to_string),
+        ),
+        (
+            trainer_b.to_string(),
+            offer_b.clone(),
+            trainer_a.to_string(),
+            into_b.map(str::to_string),
+        ),
+    ];
+    task::spawn_blocking(move || -> io::Result<()> {
+        let mut conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        write_trade(&mut conn, trainer_store(), &sides, unix_now())
+    })
     .await
-    {
-        Ok(Ok(resp)) => resp.error_for_status().ok()?,
-        _ => return None,
-    };
-    let body: serde_json::Value = response.json().await.ok()?;
-
-    let text = body.get("response").and_then(|v| v.as_str())?;
-    let cleaned = normalize_whitespace(text);
-    if cleaned.is_empty() {
-        return None;
-    }
-
-    let answer = format!("Agent: {}", cleaned);
-    Some(clip_text(&normalize_whitespace(&answer), 220))
+    .map_err(io::Error::other)?
 }
 
-async fn fetch_pokemon_brief(name: &str) -> Option<String> {
-    if let Ok(cache) = pokemon_cache().lock() {
-        if let Some(cached) = cache.get(name) {
-            return Some(cached.clone());
+/// The receivers' Pokedexes are saved before the commit, through the
+/// transaction when the store lives in the same database. Other stores can't
+/// join it, so their saves are put back if a later save or the commit fails.
+/// An error therefore always means the swap did not happen.
+fn write_trade(
+    conn: &mut Connection,
+    store: &dyn TrainerStore,
+    sides: &[TradeSide; 2],
+    traded_at: i64,
+) -> io::Result<()> {
+    let tx = conn.transaction().map_err(io::Error::other)?;
+    let mut shiny = [false; 2];
+    for ((owner, offer, _, _), shiny) in sides.iter().zip(&mut shiny) {
+        let held: Option<(String, bool)> = tx
+            .query_row(
+                "SELECT species, shiny FROM captures WHERE id = ?1 AND trainer = ?2",
+                (offer.capture_id, owner),
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(io::Error::other)?;
+        match held {
+            Some((species, held_shiny)) if species == offer.species => *shiny = held_shiny,
+            _ => {
+                return Err(io::Error::other(format!(
+                    "{owner} no longer has capture {}",
+                    offer.capture_id
+                )));
+            }
         }
     }
-
-    let client = shared_http_client();
-    let url = format!("https://pokeapi.co/api/v2/pokemon/{name}");
-    let pokemon: serde_json::Value = client
-        .get(url)
-        .send()
-        .await
-        .ok()?
-        .error_for_status()
-        .ok()?
-        .json()
-        .await
-        .ok()?;
-
-    let mut type_slots: Vec<(i64, String)> = Vec::new();
-    if let Some(types) = pokemon.get("types").and_then(|v| v.as_array()) {
-        for entry in types {
-            let slot = entry.get("slot").and_then(|v| v.as_i64()).unwrap_or(99);
-            if let Some(type_name) = entry
-                .get("type")
-                .and_then(|v| v.get("name"))
-                .and_then(|v| v.as_str())
-            {
-                type_slots.push((slot, type_name.to_string()));
+    for ((_, offer, receiver, into), shiny) in sides.iter().zip(shiny) {
+        let species = into.as_ref().unwrap_or(&offer.species);
+        tx.execute(
+            "UPDATE captures SET trainer = ?1, species = ?2 WHERE id = ?3",
+            (receiver, species, offer.capture_id),
+        )
+        .map_err(io::Error::other)?;
+        if shiny {
+            for species in [Some(&offer.species), into.as_ref()].into_iter().flatten() {
+                tx.execute(
+                    "INSERT OR IGNORE INTO shinies (trainer, species) VALUES (?1, ?2)",
+                    (receiver, species),
+                )
+                .map_err(io::Error::other)?;
             }
         }
     }
-    type_slots.sort_by_key(|(slot, _)| *slot);
-    let type_text = if type_slots.is_empty() {
-        "unknown".to_string()
-    } else {
-        type_slots
-            .into_iter()
-            .map(|(_, ty)| ty)
-            .collect::<Vec<_>>()
-            .join("/")
+    let [(a, offer_a, _, _), (b, offer_b, _, _)] = sides;
+    tx.execute(
+        "INSERT INTO trades
+            (trainer_a, capture_a, species_a, trainer_b, capture_b, species_b, traded_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        (
+            a,
+            offer_a.capture_id,
+            &offer_a.species,
+            b,
+            offer_b.capture_id,
+            &offer_b.species,
+            traded_at,
+        ),
+    )
+    .map_err(io::Error::other)?;
+    let mut previous = Vec::new();
+    let saved = sides.iter().try_for_each(|(_, offer, receiver, into)| {
+        let before = store.load_pokedex_in(&tx, receiver)?;
+        let mut after = before.clone();
+        after.insert(offer.species.clone());
+        after.extend(into.clone());
+        store.save_pokedex_in(&tx, receiver, &after)?;
+        previous.push((receiver, before));
+        Ok(())
+    });
+    let result = match saved {
+        Ok(()) => tx.commit().map_err(io::Error::other),
+        Err(err) => {
+            drop(tx);
+            Err(err)
+        }
     };
-
-    let mut hp = None;
-    let mut atk = None;
-    let mut def = None;
-    if let Some(stats) = pokemon.get("stats").and_then(|v| v.as_array()) {
-        for stat in stats {
-            let Some(stat_name) = stat
-                .get("stat")
-                .and_then(|v| v.get("name"))
-                .and_then(|v| v.as_str())
-            else {
-                continue;
-            };
-            let Some(value) = stat.get("base_stat").and_then(|v| v.as_i64()) else {
-                continue;
-            };
-            match stat_name {
-                "hp" => hp = Some(value),
-                "attack" => atk = Some(value),
-                "defense" => def = Some(value),
-                _ => {}
-            }
+    if result.is_err() {
+        for (receiver, before) in previous.iter().rev() {
+            let _ = store.save_pokedex(receiver, before);
         }
     }
+    result
+}
 
-    let flavor = if let Some(species_url) = pokemon
-        .get("species")
-        .and_then(|v| v.get("url"))
-        .and_then(|v| v.as_str())
-    {
-        fetch_species_flavor(&client, species_url).await
-    } else {
-        None
-    };
+async fn load_shinies(name: &str) -> io::Result<HashSet<String>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<HashSet<String>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare("SELECT species FROM shinies WHERE trainer = ?1")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([&name], |row| row.get::<_, String>(0))
+            .map_err(io::Error::other)?;
+        rows.collect::<Result<_, _>>().map_err(io::Error::other)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-    let message = if let Some(flavor) = flavor {
-        format!(
-            "Agent: {} is a {}-type Pokemon. {}",
-            display_pokemon_name(name),
-            type_text,
-            flavor
-        )
-    } else {
-        format!(
-            "Agent: {} is a {}-type Pokemon.",
-            display_pokemon_name(name),
-            type_text
+async fn load_seen(name: &str) -> io::Result<HashMap<String, i64>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<HashMap<String, i64>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare("SELECT species, seen_at FROM seen WHERE trainer = ?1")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([&name], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(io::Error::other)?;
+        rows.collect::<Result<_, _>>().map_err(io::Error::other)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn save_seen(name: &str, species: &str, seen_at: i64) -> io::Result<()> {
+    let name = name.to_string();
+    let species = species.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO seen (trainer, species, seen_at) VALUES (?1, ?2, ?3)",
+            (&name, &species, seen_at),
         )
-    };
-    let result = normalize_whitespace(&message);
-    if let Ok(mut cache) = pokemon_cache().lock() {
-        cache.insert(name.to_string(), result.clone());
-    }
-    Some(result)
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-async fn fetch_pokemon_stats(name: &str) -> Option<String> {
-    let client = shared_http_client();
-    let url = format!("https://pokeapi.co/api/v2/pokemon/{name}");
-    let pokemon: serde_json::Value = client
-        .get(url)
-        .send()
-        .await
-        .ok()?
-        .error_for_status()
-        .ok()?
-        .json()
-        .await
-        .ok()?;
+async fn save_shiny(name: &str, species: &str) -> io::Result<()> {
+    let name = name.to_string();
+    let species = species.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO shinies (trainer, species) VALUES (?1, ?2)",
+            (&name, &species),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-    let mut stats_list = Vec::new();
-    if let Some(stats) = pokemon.get("stats").and_then(|v| v.as_array()) {
-        for stat in stats {
-            let stat_name = stat
-                .get("stat")
-                .and_then(|v| v.get("name"))
-                .and_then(|v| v.as_str())
-                .unwrap_or("?");
-            let value = stat.get("base_stat").and_then(|v| v.as_i64()).unwrap_or(0);
-            let label = match stat_name {
-                "hp" => "HP",
-                "attack" => "ATK",
-                "defense" => "DEF",
-                "special-attack" => "SP.ATK",
-                "special-defense" => "SP.DEF",
-                "speed" => "SPD",
-                other => other,
-            };
-            stats_list.push(format!("{label} {value}"));
+/// Every trainer's Pokedex with their shiny count, for rebuilding the
+/// leaderboard at startup.
+async fn load_trainer_dexes() -> io::Result<Vec<(String, HashSet<String>, usize)>> {
+    task::spawn_blocking(|| -> io::Result<Vec<(String, HashSet<String>, usize)>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare("SELECT COUNT(*) FROM shinies WHERE trainer = ?1")
+            .map_err(io::Error::other)?;
+        let mut dexes = Vec::new();
+        for (name, pokedex) in trainer_store().trainers()? {
+            let shinies: i64 = stmt
+                .query_row([&name], |row| row.get(0))
+                .map_err(io::Error::other)?;
+            dexes.push((name, pokedex, shinies as usize));
         }
-    }
-    let message = format!(
-        "Agent: {} stats: {}.",
-        display_pokemon_name(name),
-        stats_list.join(", ")
-    );
-    Some(clip_text(&normalize_whitespace(&message), 400))
+        Ok(dexes)
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-async fn fetch_species_flavor(client: &reqwest::Client, species_url: &str) -> Option<String> {
-    let species: serde_json::Value = client
-        .get(species_url)
-        .send()
-        .await
-        .ok()?
-        .error_for_status()
-        .ok()?
-        .json()
-        .await
-        .ok()?;
-    let entries = species.get("flavor_text_entries")?.as_array()?;
-    for entry in entries {
-        if entry
-            .get("language")
-            .and_then(|v| v.get("name"))
-            .and_then(|v| v.as_str())
-            != Some("en")
-        {
-            continue;
-        }
-        let Some(raw) = entry.get("flavor_text").and_then(|v| v.as_str()) else {
-            continue;
-        };
-        let cleaned = raw.replace(['\n', '\r', '\u{000c}'], " ");
-        let cleaned = normalize_whitespace(&cleaned);
-        if !cleaned.is_empty() {
-            return Some(cleaned);
+/// Recomputes every trainer's leaderboard row, so the table matches the
+/// loaded dex after regions are added or trainers predate it. Completion
+/// times come from each species' first catch, or stay unknown when a species
+/// has no dated capture.
+async fn rebuild_leaderboard(view: &PokedexView) -> io::Result<()> {
+    let dexes = load_trainer_dexes().await?;
+    let names = dex_species(view);
+    let counts: Vec<_> = dexes
+        .into_iter()
+        .map(|(name, pokedex, shinies)| {
+            let (caught, total, legendaries) = dex_counts(&pokedex, view);
+            (
+                name,
+                caught,
+                shinies,
+                legendaries,
+                total > 0 && caught >= total,
+            )
+        })
+        .collect();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let now = unix_now();
+        for (name, caught, shinies, legendaries, complete) in counts {
+            let completion = completion(&conn, &name, complete, &names)?;
+            write_leaderboard_entry(
+                &conn,
+                &name,
+                (caught, shinies, legendaries),
+                completion,
+                now,
+            )?;
         }
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Whether a trainer's dex is complete and, if known, since when.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Completion {
+    Incomplete,
+    Complete(Option<i64>),
+}
+
+fn dex_species(view: &PokedexView) -> Vec<String> {
+    view.names
+        .iter()
+        .filter(|name| !name.is_empty())
+        .cloned()
+        .collect()
+}
+
+fn completion(
+    conn: &Connection,
+    name: &str,
+    complete: bool,
+    species: &[String],
+) -> io::Result<Completion> {
+    if !complete {
+        return Ok(Completion::Incomplete);
     }
-    None
+    Ok(Completion::Complete(completion_time(conn, name, species)?))
 }
 
-fn find_pokemon_asset<'a>(assets: &'a Assets, name: &str) -> Option<&'a PokemonAsset> {
-    assets.pokemons.iter().find(|asset| asset.name == name)
+/// When the last of `species` was first caught, from dated captures.
+fn completion_time(conn: &Connection, name: &str, species: &[String]) -> io::Result<Option<i64>> {
+    let mut stmt = conn
+        .prepare(
+            "SELECT species, MIN(caught_at) FROM captures
+             WHERE trainer = ?1 AND caught_at > 0 GROUP BY species",
+        )
+        .map_err(io::Error::other)?;
+    let first_caught: HashMap<String, i64> = stmt
+        .query_map([name], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(io::Error::other)?
+        .collect::<Result<_, _>>()
+        .map_err(io::Error::other)?;
+    Ok(species
+        .iter()
+        .map(|species| first_caught.get(species).copied())
+        .collect::<Option<Vec<i64>>>()
+        .and_then(|times| times.into_iter().max()))
 }
 
-fn render_pokedex(
-    view: &PokedexView,
-    caught: &HashSet<String>,
-    output: &mut [char],
-    color_buf: &mut [CellColor],
-    zbuffer: &mut [f32],
-    width: usize,
-    height: usize,
-) {
-    let grid_w = POKEDEX_COLS * POKEDEX_CELL_W;
-    let grid_h = POKEDEX_ROWS * POKEDEX_CELL_H;
-    let start_x = (width.saturating_sub(grid_w)) / 2;
-    let start_y = (height.saturating_sub(grid_h)) / 2;
+/// Saves (caught, shinies, legendaries) for one trainer; `species` is the
+/// loaded dex, used to date a completion.
+async fn save_leaderboard_entry(
+    name: &str,
+    counts: (usize, usize, usize),
+    complete: bool,
+    species: Vec<String>,
+) -> io::Result<()> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let completion = completion(&conn, &name, complete, &species)?;
+        write_leaderboard_entry(&conn, &name, counts, completion, unix_now())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-    for idx in 0..151 {
-        let row = idx / POKEDEX_COLS;
-        let col = idx % POKEDEX_COLS;
-        let base_x = start_x + col * POKEDEX_CELL_W;
-        let base_y = start_y + row * POKEDEX_CELL_H;
-        let number = idx + 1;
-        let digits: Vec<char> = number.to_string().chars().collect();
-        let number_w = digits.len();
-        let offset_x = base_x + (POKEDEX_CELL_W.saturating_sub(number_w)) / 2;
-        let offset_y = base_y;
+/// Upserts a trainer's leaderboard row from (caught, shinies, legendaries).
+/// A complete dex keeps the earliest known completion time; an incomplete one
+/// (say, after new regions load) clears it.
+fn write_leaderboard_entry(
+    conn: &Connection,
+    name: &str,
+    (caught, shinies, legendaries): (usize, usize, usize),
+    completion: Completion,
+    now: i64,
+) -> io::Result<()> {
+    let (complete, completed_at) = match completion {
+        Completion::Incomplete => (false, None),
+        Completion::Complete(at) => (true, at),
+    };
+    conn.execute(
+        "INSERT INTO leaderboard
+            (trainer, caught, shinies, legendaries, completed_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+         ON CONFLICT(trainer) DO UPDATE SET
+            caught = excluded.caught,
+            shinies = excluded.shinies,
+            legendaries = excluded.legendaries,
+            completed_at = CASE WHEN ?7 THEN COALESCE(
+                MIN(leaderboard.completed_at, excluded.completed_at),
+                leaderboard.completed_at,
+                excluded.completed_at
+            ) END,
+            updated_at = excluded.updated_at",
+        (
+            name,
+            caught as i64,
+            shinies as i64,
+            legendaries as i64,
+            completed_at,
+            now,
+            complete,
+        ),
+    )
+    .map_err(io::Error::other)?;
+    Ok(())
+}
+
+async fn load_leaderboard() -> io::Result<Vec<leaderboard::Entry>> {
+    task::spawn_blocking(|| -> io::Result<Vec<leaderboard::Entry>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare("SELECT trainer, caught, shinies, legendaries, completed_at FROM leaderboard")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok(leaderboard::Entry {
+                    trainer: row.get(0)?,
+                    caught: row.get::<_, i64>(1)? as usize,
+                    shinies: row.get::<_, i64>(2)? as usize,
+                    legendaries: row.get::<_, i64>(3)? as usize,
+                    completed_at: row.get(4)?,
+                })
+            })
+            .map_err(io::Error::other)?;
+        rows.collect::<Result<_, _>>().map_err(io::Error::other)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-        let name = view.names.get(idx).map(|s| s.as_str()).unwrap_or("");
-        let caught_entry = !name.is_empty() && caught.contains(name);
-        let main = if caught_entry { "\x1b[91m" } else { "\x1b[97m" };
+async fn load_friends(name: &str) -> io::Result<HashSet<String>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<HashSet<String>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare("SELECT friend FROM friends WHERE trainer = ?1")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([&name], |row| row.get::<_, String>(0))
+            .map_err(io::Error::other)?;
+        rows.collect::<Result<_, _>>().map_err(io::Error::other)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-        for (d, digit) in digits.iter().enumerate() {
-            let target_x = offset_x + d;
-            let target_y = offset_y;
-            if target_x >= width || target_y >= height {
-                continue;
-            }
-            let idx = target_x + target_y * width;
-            output[idx] = *digit;
-            color_buf[idx] = CellColor::Ansi(main);
-            zbuffer[idx] = 0.35;
-        }
-    }
+async fn save_friend(name: &str, friend: &str, add: bool) -> io::Result<()> {
+    let name = name.to_string();
+    let friend = friend.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let sql = if add {
+            "INSERT OR IGNORE INTO friends (trainer, friend) VALUES (?1, ?2)"
+        } else {
+            "DELETE FROM friends WHERE trainer = ?1 AND friend = ?2"
+        };
+        conn.execute(sql, (&name, &friend))
+            .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn load_badges(name: &str) -> io::Result<HashMap<String, i64>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<HashMap<String, i64>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare("SELECT badge, unlocked_at FROM achievements WHERE trainer = ?1")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([&name], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(io::Error::other)?;
+        rows.collect::<Result<_, _>>().map_err(io::Error::other)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn save_badge(name: &str, badge: &str, unlocked_at: i64) -> io::Result<()> {
+    let name = name.to_string();
+    let badge = badge.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO achievements (trainer, badge, unlocked_at) VALUES (?1, ?2, ?3)",
+            (&name, &badge, unlocked_at),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn render_pokedex_detail(
-    assets: &Assets,
-    idx: usize,
-    output: &mut [char],
-    color_buf: &mut [CellColor],
-    zbuffer: &mut [f32],
-    width: usize,
-    height: usize,
-) {
-    let name = assets
-        .pokedex
-        .names
-        .get(idx)
-        .map(|s| s.as_str())
-        .unwrap_or("");
-    let display_name = if name.is_empty() {
-        "Unknown Pokemon".to_string()
-    } else {
-        display_pokemon_name(name)
-    };
+/// Records a login on `day` and returns the streak of consecutive days,
+/// including today.
+/// Records a login on `day` and returns the trainer's day and streak. Days
+/// before the stored one are ignored, keeping the stored day.
+async fn save_login(name: &str, day: i64) -> io::Result<(i64, u32)> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<(i64, u32)> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        write_login(&conn, &name, day)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-    if let Some(asset) = find_pokemon_asset(assets, name) {
-        let image = &asset.image;
-        let start_x = (width.saturating_sub(image.width)) / 2;
-        let start_y = (height.saturating_sub(image.height + 2)) / 2;
+fn write_login(conn: &Connection, name: &str, day: i64) -> io::Result<(i64, u32)> {
+    let last = conn
+        .query_row(
+            "SELECT last_day, streak FROM logins WHERE trainer = ?1",
+            [name],
+            |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?)),
+        )
+        .optional()
+        .map_err(io::Error::other)?;
+    let streak = match last {
+        Some((last_day, streak)) if last_day >= day => return Ok((last_day, streak as u32)),
+        Some((last_day, streak)) if last_day == day - 1 => streak + 1,
+        _ => 1,
+    };
+    conn.execute(
+        "INSERT INTO logins (trainer, last_day, streak) VALUES (?1, ?2, ?3)
+         ON CONFLICT(trainer) DO UPDATE SET
+            last_day = excluded.last_day,
+            streak = excluded.streak",
+        (name, day, streak),
+    )
+    .map_err(io::Error::other)?;
+    Ok((day, streak as u32))
+}
 
-        for y in 0..image.height {
-            for x in 0..image.width {
-                let target_y = start_y + y;
-                let target_x = start_x + x;
-                if target_y >= height || target_x >= width {
-                    continue;
-                }
-                let src_idx = x + y * image.width;
-                let ch = image.chars[src_idx];
-                if ch == ' ' {
-                    continue;
-                }
-                let idx = target_x + target_y * width;
-                output[idx] = ch;
-                let (r, g, b) = image.colors[src_idx];
-                color_buf[idx] = CellColor::Rgb(r, g, b);
-                zbuffer[idx] = 0.4;
+/// Today's quests, skipping rows whose goal or reward no longer parses.
+async fn load_quests(name: &str, day: i64) -> io::Result<Vec<quests::Quest>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<Vec<quests::Quest>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let mut stmt = conn
+            .prepare(
+                "SELECT slot, goal, target, progress, reward, asked FROM quests
+                 WHERE trainer = ?1 AND day = ?2 ORDER BY slot",
+            )
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map((&name, day), |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, i64>(2)?,
+                    row.get::<_, i64>(3)?,
+                    row.get::<_, String>(4)?,
+                    row.get::<_, String>(5)?,
+                ))
+            })
+            .map_err(io::Error::other)?;
+        let mut quests = Vec::new();
+        for row in rows {
+            let (slot, goal, target, progress, reward, asked) = row.map_err(io::Error::other)?;
+            if let (Some(goal), Some(reward)) =
+                (quests::Goal::parse(&goal), quests::Reward::parse(&reward))
+            {
+                quests.push(quests::Quest {
+                    slot: slot as usize,
+                    goal,
+                    target: target as u32,
+                    progress: progress as u32,
+                    reward,
+                    asked: quests::parse_asked(&asked),
+                });
             }
         }
+        Ok(quests)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-        let name_row = (start_y + image.height + 1).min(height.saturating_sub(2));
-        let name_start = (width.saturating_sub(display_name.len())) / 2;
-        for (i, ch) in display_name.chars().enumerate() {
-            let x = name_start + i;
-            if x >= width || name_row >= height {
-                continue;
-            }
-            let idx = x + name_row * width;
-            output[idx] = ch;
-            color_buf[idx] = CellColor::Ansi("\x1b[92m");
-            zbuffer[idx] = 0.4;
-        }
-    } else {
-        let notice = "Sprite not available yet";
-        let notice_row = height.saturating_sub(3);
-        let notice_start = (width.saturating_sub(notice.len())) / 2;
-        for (i, ch) in notice.chars().enumerate() {
-            let x = notice_start + i;
-            if x >= width || notice_row >= height {
-                continue;
-            }
-            let idx = x + notice_row * width;
-            output[idx] = ch;
-            color_buf[idx] = CellColor::Ansi("\x1b[97m");
-            zbuffer[idx] = 0.4;
+async fn save_quests(name: &str, day: i64, quests: &[quests::Quest]) -> io::Result<()> {
+    let name = name.to_string();
+    let rows: Vec<(i64, String, i64, i64, String, String)> = quests
+        .iter()
+        .map(|quest| {
+            (
+                quest.slot as i64,
+                quest.goal.key(),
+                quest.target as i64,
+                quest.progress as i64,
+                quest.reward.key(),
+                quest.asked.join(","),
+            )
+        })
+        .collect();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let mut conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let tx = conn.transaction().map_err(io::Error::other)?;
+        for (slot, goal, target, progress, reward, asked) in rows {
+            tx.execute(
+                "INSERT OR REPLACE INTO quests
+                    (trainer, day, slot, goal, target, progress, reward, asked)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                (&name, day, slot, &goal, target, progress, &reward, &asked),
+            )
+            .map_err(io::Error::other)?;
         }
+        tx.commit().map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
 
-        let name_row = notice_row.saturating_sub(2);
-        let name_start = (width.saturating_sub(display_name.len())) / 2;
-        for (i, ch) in display_name.chars().enumerate() {
-            let x = name_start + i;
-            if x >= width || name_row >= height {
-                continue;
-            }
-            let idx = x + name_row * width;
-            output[idx] = ch;
-            color_buf[idx] = CellColor::Ansi("\x1b[92m");
-            zbuffer[idx] = 0.4;
-        }
-    }
+async fn load_route(name: &str) -> io::Result<Option<String>> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<Option<String>> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let route = conn
+            .query_row(
+                "SELECT route FROM trainer_settings WHERE trainer = ?1",
+                [&name],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(io::Error::other)?;
+        Ok(route.flatten())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-fn sanitize_trainer_name(input: &str) -> Option<String> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        return None;
-    }
-    if trimmed.len() > 16 {
-        return None;
-    }
-    if !trimmed
-        .chars()
-        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
-    {
-        return None;
-    }
-    Some(trimmed.to_lowercase())
+async fn save_route(name: &str, route: &str) -> io::Result<()> {
+    let name = name.to_string();
+    let route = route.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT INTO trainer_settings (trainer, route) VALUES (?1, ?2)
+             ON CONFLICT(trainer) DO UPDATE SET route = excluded.route",
+            (&name, &route),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
 }
 
-async fn init_db() -> io::Result<()> {
-    task::spawn_blocking(|| -> io::Result<()> {
-        let conn =
-            Connection::open(DB_PATH).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-        conn.pragma_update(None, "journal_mode", "WAL")
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+/// The trainer's own zone name, if set, and when it last changed.
+async fn load_timezone(name: &str) -> io::Result<(Option<String>, Option<i64>)> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<(Option<String>, Option<i64>)> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let row = conn
+            .query_row(
+                "SELECT timezone, timezone_changed_at FROM trainer_settings WHERE trainer = ?1",
+                [&name],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()
+            .map_err(io::Error::other)?;
+        Ok(row.unwrap_or_default())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+/// Stores the trainer's zone; `None` goes back to the server's.
+async fn save_timezone(name: &str, zone: Option<&str>, changed_at: i64) -> io::Result<()> {
+    let name = name.to_string();
+    let zone = zone.map(str::to_string);
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
         conn.execute(
-            "CREATE TABLE IF NOT EXISTS trainers (
-                name TEXT PRIMARY KEY,
-                pokedex TEXT NOT NULL
-            )",
-            [],
+            "INSERT INTO trainer_settings (trainer, timezone, timezone_changed_at)
+             VALUES (?1, ?2, ?3)
+             ON CONFLICT(trainer) DO UPDATE SET
+                timezone = excluded.timezone,
+                timezone_changed_at = excluded.timezone_changed_at",
+            (&name, &zone, changed_at),
         )
-        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        .map_err(io::Error::other)?;
         Ok(())
     })
     .await
-    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    .map_err(io::Error::other)?
 }
 
-async fn load_pokedex(name: &str) -> io::Result<HashSet<String>> {
+async fn load_allow_spectators(name: &str) -> io::Result<bool> {
     let name = name.to_string();
-    task::spawn_blocking(move || -> io::Result<HashSet<String>> {
-        let conn =
-            Connection::open(DB_PATH).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-        let json: Option<String> = conn
+    task::spawn_blocking(move || -> io::Result<bool> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let allow: Option<bool> = conn
             .query_row(
-                "SELECT pokedex FROM trainers WHERE name = ?1",
+                "SELECT allow_spectators FROM trainer_settings WHERE trainer = ?1",
                 [&name],
                 |row| row.get(0),
             )
             .optional()
-            .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
-        let list: Vec<String> = match json {
-            Some(text) => serde_json::from_str(&text)
-                .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?,
-            None => Vec::new(),
-        };
-        Ok(list.into_iter().collect())
+            .map_err(io::Error::other)?;
+        Ok(allow.unwrap_or(true))
     })
     .await
-    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    .map_err(io::Error::other)?
 }
 
-async fn save_pokedex(name: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+async fn save_allow_spectators(name: &str, allow: bool) -> io::Result<()> {
     let name = name.to_string();
-    let mut list: Vec<String> = pokedex.iter().cloned().collect();
-    list.sort();
-    let payload =
-        serde_json::to_string(&list).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
     task::spawn_blocking(move || -> io::Result<()> {
-        let conn =
-            Connection::open(DB_PATH).map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
         conn.execute(
-            "INSERT INTO trainers (name, pokedex)
+            "INSERT INTO trainer_settings (trainer, allow_spectators)
              VALUES (?1, ?2)
-             ON CONFLICT(name) DO UPDATE SET pokedex = excluded.pokedex",
-            (&name, &payload),
+             ON CONFLICT(trainer) DO UPDATE SET allow_spectators = excluded.allow_spectators",
+            (&name, allow),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn load_inventory(name: &str) -> io::Result<Inventory> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<Inventory> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let inventory = conn
+            .query_row(
+                "SELECT poke, great, ultra, master, restock_day FROM inventories WHERE trainer = ?1",
+                [&name],
+                |row| {
+                    Ok(Inventory {
+                        counts: [row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?],
+                        restock_day: row.get(4)?,
+                    })
+                },
+            )
+            .optional()
+            .map_err(io::Error::other)?;
+        Ok(inventory.unwrap_or_default())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn save_inventory(name: &str, inventory: &Inventory) -> io::Result<()> {
+    let name = name.to_string();
+    let [poke, great, ultra, master] = inventory.counts;
+    let restock_day = inventory.restock_day;
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT INTO inventories (trainer, poke, great, ultra, master, restock_day)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)
+             ON CONFLICT(trainer) DO UPDATE SET
+                poke = excluded.poke,
+                great = excluded.great,
+                ultra = excluded.ultra,
+                master = excluded.master,
+                restock_day = excluded.restock_day",
+            (&name, poke, great, ultra, master, restock_day),
+        )
+        .map_err(io::Error::other)?;
+        Ok(())
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn load_encounter_count(name: &str, day: i64) -> io::Result<usize> {
+    let name = name.to_string();
+    task::spawn_blocking(move || -> io::Result<usize> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        let count: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM encounters WHERE trainer = ?1 AND day = ?2",
+                (&name, day),
+                |row| row.get(0),
+            )
+            .map_err(io::Error::other)?;
+        Ok(count as usize)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+async fn save_encounter(
+    name: &str,
+    day: i64,
+    slot: usize,
+    species: &str,
+    outcome: &str,
+) -> io::Result<()> {
+    let name = name.to_string();
+    let species = species.to_string();
+    let outcome = outcome.to_string();
+    task::spawn_blocking(move || -> io::Result<()> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        conn.execute(
+            "INSERT OR IGNORE INTO encounters (trainer, day, slot, species, outcome)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            (&name, day, slot as i64, &species, &outcome),
         )
-        .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?;
+        .map_err(io::Error::other)?;
         Ok(())
     })
     .await
-    .map_err(|err| io::Error::new(io::ErrorKind::Other, err))?
+    .map_err(io::Error::other)?
+}
+
+/// When and on which route the trainer first saw this daily slot. The first
+/// call records `now` and `route`; later ones return the stored values.
+fn reveal_slot(
+    conn: &Connection,
+    name: &str,
+    day: i64,
+    slot: usize,
+    now: i64,
+    route: &str,
+) -> io::Result<(i64, String)> {
+    conn.execute(
+        "INSERT OR IGNORE INTO encounter_reveals (trainer, day, slot, revealed_at, route)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        (name, day, slot as i64, now, route),
+    )
+    .map_err(io::Error::other)?;
+    conn.query_row(
+        "SELECT revealed_at, COALESCE(route, ?4) FROM encounter_reveals
+         WHERE trainer = ?1 AND day = ?2 AND slot = ?3",
+        (name, day, slot as i64, route),
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .map_err(io::Error::other)
+}
+
+async fn reveal_encounter(
+    name: &str,
+    day: i64,
+    slot: usize,
+    route: &str,
+) -> io::Result<(i64, String)> {
+    let name = name.to_string();
+    let route = route.to_string();
+    task::spawn_blocking(move || -> io::Result<(i64, String)> {
+        let conn = Connection::open(db_path()).map_err(io::Error::other)?;
+        reveal_slot(&conn, &name, day, slot, unix_now(), &route)
+    })
+    .await
+    .map_err(io::Error::other)?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn
+    }
+
+    fn legacy(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn legacy_pokedex_is_backfilled_once() {
+        let mut conn = test_db();
+        let dex = legacy(&["bulbasaur", "charmander"]);
+        let captures = read_captures(&mut conn, "ash", &dex).unwrap();
+        assert_eq!(captures.len(), 2);
+        assert!(captures.iter().all(|c| c.level == levels::LEGACY_LEVEL));
+        assert_eq!(read_captures(&mut conn, "ash", &dex).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn trainers_with_captures_before_the_marker_are_not_backfilled() {
+        let mut conn = Connection::open_in_memory().unwrap();
+        conn.execute(
+            "CREATE TABLE captures (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                trainer TEXT NOT NULL,
+                species TEXT NOT NULL,
+                level INTEGER NOT NULL,
+                xp INTEGER NOT NULL,
+                caught_at INTEGER NOT NULL
+            )",
+            [],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO captures (trainer, species, level, xp, caught_at)
+             VALUES ('ash', 'pikachu', 12, 1728, 1)",
+            [],
+        )
+        .unwrap();
+        create_schema(&conn).unwrap();
+        let captures = read_captures(&mut conn, "ash", &legacy(&["bulbasaur"])).unwrap();
+        let id = captures[0].id;
+        assert_eq!(captures.len(), 1);
+        remove_capture(&conn, "ash", id).unwrap();
+        assert!(
+            read_captures(&mut conn, "ash", &legacy(&["bulbasaur"]))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn releasing_the_last_capture_survives_a_reload() {
+        let mut conn = test_db();
+        let dex = legacy(&["bulbasaur", "charmander"]);
+        for capture in read_captures(&mut conn, "ash", &dex).unwrap() {
+            assert!(remove_capture(&conn, "ash", capture.id).unwrap());
+        }
+        assert!(read_captures(&mut conn, "ash", &dex).unwrap().is_empty());
+    }
+
+    #[test]
+    fn per_process_session_ids_are_cleared_once() {
+        let conn = test_db();
+        conn.execute("DROP TABLE play_sessions", []).unwrap();
+        conn.execute(
+            "INSERT INTO captures (trainer, species, level, xp, caught_at, session_id)
+             VALUES ('ash', 'pikachu', 5, 125, 1, 3)",
+            [],
+        )
+        .unwrap();
+        let session = |conn: &Connection| -> Option<i64> {
+            conn.query_row("SELECT session_id FROM captures", [], |row| row.get(0))
+                .unwrap()
+        };
+        create_schema(&conn).unwrap();
+        assert_eq!(session(&conn), None);
+        conn.execute("UPDATE captures SET session_id = 1", [])
+            .unwrap();
+        create_schema(&conn).unwrap();
+        assert_eq!(session(&conn), Some(1));
+    }
+
+    /// A memory store whose saves fail for one trainer.
+    struct FlakyStore {
+        inner: store::MemoryStore,
+        fail_for: &'static str,
+    }
+
+    impl TrainerStore for FlakyStore {
+        fn kind(&self) -> &'static str {
+            "memory"
+        }
+
+        fn init(&self) -> io::Result<()> {
+            Ok(())
+        }
+
+        fn load_pokedex(&self, trainer: &str) -> io::Result<HashSet<String>> {
+            self.inner.load_pokedex(trainer)
+        }
+
+        fn save_pokedex(&self, trainer: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+            if trainer == self.fail_for {
+                return Err(io::Error::other("store down"));
+            }
+            self.inner.save_pokedex(trainer, pokedex)
+        }
+
+        fn trainers(&self) -> io::Result<Vec<(String, HashSet<String>)>> {
+            self.inner.trainers()
+        }
+    }
+
+    fn trade_setup(store: &dyn TrainerStore) -> (Connection, [TradeSide; 2]) {
+        trade_sides(test_db(), store)
+    }
+
+    fn trade_sides(conn: Connection, store: &dyn TrainerStore) -> (Connection, [TradeSide; 2]) {
+        conn.execute(
+            "INSERT INTO captures (id, trainer, species, level, xp, caught_at, shiny)
+             VALUES (1, 'ash', 'pikachu', 5, 125, 1, 1), (2, 'misty', 'staryu', 5, 125, 1, 0)",
+            [],
+        )
+        .unwrap();
+        store
+            .save_pokedex("misty", &legacy(&["staryu"]).into_iter().collect())
+            .unwrap();
+        let offer = |capture_id, species: &str| TradeOffer {
+            capture_id,
+            species: species.to_string(),
+            level: 5,
+        };
+        let sides = [
+            (
+                "ash".to_string(),
+                offer(1, "pikachu"),
+                "misty".to_string(),
+                None,
+            ),
+            (
+                "misty".to_string(),
+                offer(2, "staryu"),
+                "ash".to_string(),
+                None,
+            ),
+        ];
+        (conn, sides)
+    }
+
+    fn owners(conn: &Connection) -> Vec<String> {
+        let mut stmt = conn
+            .prepare("SELECT trainer FROM captures ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn trades_swap_captures_and_fill_both_pokedexes() {
+        let store = store::MemoryStore::default();
+        let (mut conn, sides) = trade_setup(&store);
+        write_trade(&mut conn, &store, &sides, 100).unwrap();
+        assert_eq!(owners(&conn), ["misty", "ash"]);
+        assert!(store.load_pokedex("misty").unwrap().contains("pikachu"));
+        assert!(store.load_pokedex("ash").unwrap().contains("staryu"));
+        let shinies: Vec<(String, String)> = conn
+            .prepare("SELECT trainer, species FROM shinies")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(shinies, [("misty".to_string(), "pikachu".to_string())]);
+    }
+
+    #[test]
+    fn trades_save_sqlite_pokedexes_through_the_open_transaction() {
+        let path = env::temp_dir().join(format!("pokestream-trade-{}.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let path = path.to_string_lossy().into_owned();
+        let store = store::SqliteStore::new(&path);
+        store.init().unwrap();
+        let conn = Connection::open(&path).unwrap();
+        create_schema(&conn).unwrap();
+        let (mut conn, sides) = trade_sides(conn, &store);
+        let started = Instant::now();
+        write_trade(&mut conn, &store, &sides, 100).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(owners(&conn), ["misty", "ash"]);
+        assert!(store.load_pokedex("misty").unwrap().contains("pikachu"));
+        assert!(store.load_pokedex("ash").unwrap().contains("staryu"));
+        drop(conn);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn a_store_failure_undoes_the_whole_trade() {
+        let store = FlakyStore {
+            inner: store::MemoryStore::default(),
+            fail_for: "ash",
+        };
+        let (mut conn, sides) = trade_setup(&store);
+        assert!(write_trade(&mut conn, &store, &sides, 100).is_err());
+        assert_eq!(owners(&conn), ["ash", "misty"]);
+        assert_eq!(
+            store.load_pokedex("misty").unwrap(),
+            legacy(&["staryu"]).into_iter().collect()
+        );
+        let trades: i64 = conn
+            .query_row("SELECT COUNT(*) FROM trades", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(trades, 0);
+    }
+
+    fn insert_dated(conn: &Connection, trainer: &str, species: &str, caught_at: i64) {
+        conn.execute(
+            "INSERT INTO captures (trainer, species, level, xp, caught_at)
+             VALUES (?1, ?2, 5, 125, ?3)",
+            (trainer, species, caught_at),
+        )
+        .unwrap();
+    }
+
+    fn completed_at(conn: &Connection, trainer: &str) -> Option<i64> {
+        conn.query_row(
+            "SELECT completed_at FROM leaderboard WHERE trainer = ?1",
+            [trainer],
+            |row| row.get(0),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn completion_is_dated_by_the_last_first_catch() {
+        let conn = test_db();
+        let dex = legacy(&["bulbasaur", "charmander"]);
+        insert_dated(&conn, "ash", "bulbasaur", 100);
+        insert_dated(&conn, "ash", "charmander", 300);
+        insert_dated(&conn, "ash", "charmander", 200);
+        assert_eq!(completion_time(&conn, "ash", &dex).unwrap(), Some(200));
+        insert_dated(&conn, "misty", "bulbasaur", 0);
+        insert_dated(&conn, "misty", "charmander", 50);
+        assert_eq!(completion_time(&conn, "misty", &dex).unwrap(), None);
+    }
+
+    #[test]
+    fn completion_keeps_the_earliest_time_and_clears_when_incomplete() {
+        let conn = test_db();
+        write_leaderboard_entry(&conn, "ash", (2, 0, 0), Completion::Complete(None), 900).unwrap();
+        assert_eq!(completed_at(&conn, "ash"), None);
+        write_leaderboard_entry(
+            &conn,
+            "ash",
+            (2, 0, 0),
+            Completion::Complete(Some(500)),
+            900,
+        )
+        .unwrap();
+        assert_eq!(completed_at(&conn, "ash"), Some(500));
+        write_leaderboard_entry(
+            &conn,
+            "ash",
+            (2, 0, 0),
+            Completion::Complete(Some(700)),
+            900,
+        )
+        .unwrap();
+        write_leaderboard_entry(&conn, "ash", (2, 0, 0), Completion::Complete(None), 900).unwrap();
+        assert_eq!(completed_at(&conn, "ash"), Some(500));
+        write_leaderboard_entry(&conn, "ash", (2, 0, 0), Completion::Incomplete, 900).unwrap();
+        assert_eq!(completed_at(&conn, "ash"), None);
+    }
+
+    #[test]
+    fn login_streak_counts_consecutive_days() {
+        let conn = test_db();
+        assert_eq!(write_login(&conn, "ash", 100).unwrap(), (100, 1));
+        assert_eq!(write_login(&conn, "ash", 100).unwrap(), (100, 1));
+        assert_eq!(write_login(&conn, "ash", 101).unwrap(), (101, 2));
+        assert_eq!(write_login(&conn, "ash", 103).unwrap(), (103, 1));
+    }
+
+    #[test]
+    fn earlier_login_days_keep_the_streak_and_day() {
+        let conn = test_db();
+        write_login(&conn, "ash", 100).unwrap();
+        write_login(&conn, "ash", 101).unwrap();
+        assert_eq!(write_login(&conn, "ash", 100).unwrap(), (101, 2));
+        assert_eq!(write_login(&conn, "ash", 102).unwrap(), (102, 3));
+    }
+
+    #[test]
+    fn a_slot_keeps_its_first_reveal_time_and_route() {
+        let conn = test_db();
+        let reveal =
+            |name, slot, now, route| reveal_slot(&conn, name, 100, slot, now, route).unwrap();
+        assert_eq!(
+            reveal("ash", 0, 1_000, "pallet-town"),
+            (1_000, "pallet-town".to_string())
+        );
+        assert_eq!(
+            reveal("ash", 0, 50_000, "mt-moon"),
+            (1_000, "pallet-town".to_string())
+        );
+        assert_eq!(
+            reveal("ash", 1, 50_000, "mt-moon"),
+            (50_000, "mt-moon".to_string())
+        );
+        assert_eq!(
+            reveal("misty", 0, 7_000, "mt-moon"),
+            (7_000, "mt-moon".to_string())
+        );
+    }
 }
@@ -0,0 +1,172 @@
+use crate::levels::Capture;
+use crate::tz::Zone;
+
+/// Longest nickname a trainer can give a capture.
+pub const NICKNAME_LEN: usize = 12;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum BoxSort {
+    /// Most recent catches first.
+    Date,
+    Level,
+    Species,
+    /// By nickname, falling back to the species name.
+    Name,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum BoxFilter {
+    All,
+    Shiny,
+    Species(String),
+}
+
+impl BoxSort {
+    pub fn parse(input: &str) -> Option<Self> {
+        match input.trim() {
+            "date" | "recent" | "caught" => Some(Self::Date),
+            "level" | "lvl" => Some(Self::Level),
+            "species" | "pokemon" => Some(Self::Species),
+            "name" | "nickname" => Some(Self::Name),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Date => "DATE",
+            Self::Level => "LEVEL",
+            Self::Species => "SPECIES",
+            Self::Name => "NAME",
+        }
+    }
+}
+
+impl BoxFilter {
+    /// `all`, `shiny`, or a species name with spaces as dashes.
+    pub fn parse(input: &str) -> Self {
+        match input.trim() {
+            "" | "all" | "none" | "off" => Self::All,
+            "shiny" | "shinies" => Self::Shiny,
+            species => Self::Species(species.replace(' ', "-")),
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self {
+            Self::All => "ALL".to_string(),
+            Self::Shiny => "SHINY".to_string(),
+            Self::Species(species) => species.to_uppercase(),
+        }
+    }
+
+    fn keeps(&self, capture: &Capture) -> bool {
+        match self {
+            Self::All => true,
+            Self::Shiny => capture.shiny,
+            Self::Species(species) => capture.species == *species,
+        }
+    }
+}
+
+/// Name shown for a capture: its nickname if it has one.
+pub fn display_name(capture: &Capture) -> String {
+    capture
+        .nickname
+        .clone()
+        .unwrap_or_else(|| crate::display_pokemon_name(&capture.species))
+}
+
+/// When and how a capture was caught, e.g. "Caught 2026-10-02 with a Great Ball".
+/// The date is by the trainer's calendar.
+pub fn catch_record(capture: &Capture, zone: &Zone) -> String {
+    if capture.caught_at <= 0 {
+        return "Caught before catch records were kept".to_string();
+    }
+    let date = zone.format_date(capture.caught_at);
+    match capture.ball {
+        Some(ball) => {
+            let label = ball.label();
+            let article = if label.starts_with(['A', 'E', 'I', 'O', 'U']) {
+                "an"
+            } else {
+                "a"
+            };
+            format!("Caught {date} with {article} {label}")
+        }
+        None => format!("Caught {date}"),
+    }
+}
+
+/// Captures in the order the box screen numbers them.
+pub fn arrange<'a>(captures: &'a [Capture], sort: BoxSort, filter: &BoxFilter) -> Vec<&'a Capture> {
+    let mut list: Vec<&Capture> = captures.iter().filter(|c| filter.keeps(c)).collect();
+    list.sort_by(|a, b| {
+        let primary = match sort {
+            BoxSort::Date => b.caught_at.cmp(&a.caught_at),
+            BoxSort::Level => b.level.cmp(&a.level),
+            BoxSort::Species => a.species.cmp(&b.species),
+            BoxSort::Name => display_name(a)
+                .to_lowercase()
+                .cmp(&display_name(b).to_lowercase()),
+        };
+        primary.then(b.id.cmp(&a.id))
+    });
+    list
+}
+
+/// Trims a nickname and keeps it to printable ASCII. Returns `None` when
+/// nothing usable is left or it is too long.
+pub fn sanitize_nickname(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    if trimmed.is_empty()
+        || trimmed.chars().count() > NICKNAME_LEN
+        || !trimmed.chars().all(|c| c.is_ascii_graphic() || c == ' ')
+    {
+        return None;
+    }
+    Some(trimmed.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::inventory::BallKind;
+
+    fn capture(caught_at: i64, ball: Option<BallKind>) -> Capture {
+        Capture {
+            id: 1,
+            species: "pikachu".to_string(),
+            level: 5,
+            xp: 0,
+            caught_at,
+            shiny: false,
+            nickname: None,
+            ball,
+            session_id: None,
+        }
+    }
+
+    #[test]
+    fn catch_records_use_the_trainers_calendar() {
+        // 2026-10-18 23:00 UTC.
+        let late = 1_792_364_400;
+        let berlin = Zone::load("Europe/Berlin").unwrap();
+        assert_eq!(
+            catch_record(&capture(late, Some(BallKind::Ultra)), &berlin),
+            "Caught 2026-10-19 with an Ultra Ball"
+        );
+        assert_eq!(
+            catch_record(&capture(late, Some(BallKind::Great)), &berlin),
+            "Caught 2026-10-19 with a Great Ball"
+        );
+        assert_eq!(
+            catch_record(&capture(late, None), &Zone::utc()),
+            "Caught 2026-10-18"
+        );
+        assert_eq!(
+            catch_record(&capture(0, None), &berlin),
+            "Caught before catch records were kept"
+        );
+    }
+}
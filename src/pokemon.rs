@@ -1,25 +1,442 @@
-use crate::ascii::{AsciiImage, load_ascii_animation, load_ascii_image};
+use crate::ascii::{
+    AsciiImage, ConvertConfig, ResizeFilter, load_ascii_animation, load_ascii_image,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
 use std::path::Path;
 
+const MANIFEST_PATH: &str = "assets/manifest.json";
+const SPRITE_DIR: &str = "assets/pokemon";
+
 const ARCANINE_PATH: &str = "assets/pokemon/arcanine.gif";
 const ARCANINE_WIDTH: usize = 96;
 const ARCANINE_HEIGHT: usize = 24;
+const WELCOME_FRAME_MS: u64 = 1000 / 12;
 
 const POKEMON_WIDTH: usize = 72;
 const POKEMON_HEIGHT: usize = 34;
 
-pub fn load_named_pokemon(name: &str, charset: &str) -> AsciiImage {
-    let jpg_path = format!("assets/pokemon/{name}.jpg");
-    if Path::new(&jpg_path).exists() {
-        return load_ascii_image(&jpg_path, POKEMON_WIDTH, POKEMON_HEIGHT, charset);
+/// Sprite layout and metadata, read from `assets/manifest.json` when present.
+/// Species without an entry fall back to `assets/pokemon/{name}.jpg|png` at the
+/// default size, so the manifest only needs to list exceptions.
+pub struct AssetManifest {
+    pub sprite_dir: String,
+    pub defaults: SpriteOptions,
+    pub species: HashMap<String, SpeciesEntry>,
+    pub welcome: Vec<WelcomeEntry>,
+}
+
+pub struct SpriteOptions {
+    pub width: usize,
+    pub height: usize,
+    pub offset: (i32, i32),
+    pub charset: Option<String>,
+    pub filter: Option<ResizeFilter>,
+    pub preserve_aspect: Option<bool>,
+}
+
+pub struct SpeciesEntry {
+    pub sprites: Vec<String>,
+    pub shiny: Option<String>,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    pub offset: Option<(i32, i32)>,
+    pub charset: Option<String>,
+    pub filter: Option<ResizeFilter>,
+    pub preserve_aspect: Option<bool>,
+}
+
+pub struct WelcomeEntry {
+    pub path: String,
+    pub width: usize,
+    pub height: usize,
+    pub frame_ms: u64,
+    pub charset: Option<String>,
+}
+
+pub struct WelcomeAnimation {
+    pub frames: Vec<AsciiImage>,
+    pub frame_ms: u64,
+}
+
+impl Default for AssetManifest {
+    fn default() -> Self {
+        Self {
+            sprite_dir: SPRITE_DIR.to_string(),
+            defaults: SpriteOptions {
+                width: POKEMON_WIDTH,
+                height: POKEMON_HEIGHT,
+                offset: (0, 0),
+                charset: None,
+                filter: None,
+                preserve_aspect: None,
+            },
+            species: HashMap::new(),
+            welcome: vec![WelcomeEntry {
+                path: ARCANINE_PATH.to_string(),
+                width: ARCANINE_WIDTH,
+                height: ARCANINE_HEIGHT,
+                frame_ms: WELCOME_FRAME_MS,
+                charset: None,
+            }],
+        }
+    }
+}
+
+impl AssetManifest {
+    pub fn sprite_paths(&self, name: &str) -> Vec<String> {
+        match self.species.get(name) {
+            Some(entry) if !entry.sprites.is_empty() => entry.sprites.clone(),
+            _ => vec![
+                format!("{}/{name}.jpg", self.sprite_dir),
+                format!("{}/{name}.png", self.sprite_dir),
+            ],
+        }
+    }
+
+    pub fn resolve_sprite(&self, name: &str) -> Option<String> {
+        self.sprite_paths(name)
+            .into_iter()
+            .find(|path| Path::new(path).exists())
+    }
+
+    pub fn sprite_size(&self, name: &str) -> (usize, usize) {
+        let entry = self.species.get(name);
+        (
+            entry.and_then(|e| e.width).unwrap_or(self.defaults.width),
+            entry.and_then(|e| e.height).unwrap_or(self.defaults.height),
+        )
+    }
+
+    pub fn offset(&self, name: &str) -> (i32, i32) {
+        self.species
+            .get(name)
+            .and_then(|e| e.offset)
+            .unwrap_or(self.defaults.offset)
+    }
+
+    fn convert_config(&self, name: &str) -> ConvertConfig {
+        let (width, height) = self.sprite_size(name);
+        let mut config = convert_config_from_env(width, height);
+        if let Some(filter) = self
+            .species
+            .get(name)
+            .and_then(|e| e.filter)
+            .or(self.defaults.filter)
+        {
+            config = config.filter(filter);
+        }
+        if let Some(preserve) = self
+            .species
+            .get(name)
+            .and_then(|e| e.preserve_aspect)
+            .or(self.defaults.preserve_aspect)
+        {
+            config = config.preserve_aspect(preserve);
+        }
+        config
+    }
+
+    fn charset<'a>(&'a self, name: &str, fallback: &'a str) -> &'a str {
+        self.species
+            .get(name)
+            .and_then(|e| e.charset.as_deref())
+            .or(self.defaults.charset.as_deref())
+            .unwrap_or(fallback)
+    }
+
+    /// Checks every dex species and welcome entry, returning one line per problem.
+    pub fn validate(&self, names: &[String]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for name in names.iter().filter(|name| !name.is_empty()) {
+            if self.resolve_sprite(name).is_none() {
+                problems.push(format!(
+                    "{name}: no sprite found (tried {})",
+                    self.sprite_paths(name).join(", ")
+                ));
+            }
+            let (width, height) = self.sprite_size(name);
+            if width == 0 || height == 0 {
+                problems.push(format!("{name}: sprite size must be non-zero"));
+            }
+        }
+        for (name, entry) in &self.species {
+            if !names.iter().any(|known| known == name) {
+                problems.push(format!("{name}: manifest entry for unknown species"));
+            }
+            if let Some(shiny) = entry.shiny.as_deref()
+                && !Path::new(shiny).exists()
+            {
+                problems.push(format!("{name}: missing shiny sprite {shiny}"));
+            }
+            if short_charset(entry.charset.as_deref()) {
+                problems.push(format!("{name}: charset needs at least two characters"));
+            }
+        }
+        if short_charset(self.defaults.charset.as_deref()) {
+            problems.push("defaults: charset needs at least two characters".to_string());
+        }
+        if self.welcome.is_empty() {
+            problems.push("welcome: at least one candidate is required".to_string());
+        }
+        for entry in &self.welcome {
+            if !Path::new(&entry.path).exists() {
+                problems.push(format!("welcome: missing animation {}", entry.path));
+            }
+            if entry.width == 0 || entry.height == 0 || entry.frame_ms == 0 {
+                problems.push(format!(
+                    "welcome: {} needs non-zero size and frame_ms",
+                    entry.path
+                ));
+            }
+            if short_charset(entry.charset.as_deref()) {
+                problems.push(format!(
+                    "welcome: {} charset needs at least two characters",
+                    entry.path
+                ));
+            }
+        }
+        problems.sort();
+        problems
+    }
+}
+
+/// The converter maps brightness onto the charset, so it needs two characters.
+fn short_charset(charset: Option<&str>) -> bool {
+    charset.is_some_and(|c| c.chars().count() < 2)
+}
+
+pub fn load_manifest() -> io::Result<AssetManifest> {
+    let path = env::var("POKESTREAM_MANIFEST").unwrap_or_else(|_| MANIFEST_PATH.to_string());
+    if !Path::new(&path).exists() {
+        return Ok(AssetManifest::default());
+    }
+    let text = fs::read_to_string(&path)?;
+    let root: Value = serde_json::from_str(&text).map_err(io::Error::other)?;
+    parse_manifest(&root).map_err(|err| io::Error::other(format!("{path}: {err}")))
+}
+
+fn parse_manifest(root: &Value) -> Result<AssetManifest, String> {
+    let mut manifest = AssetManifest::default();
+    if let Some(dir) = root.get("sprite_dir").and_then(|v| v.as_str()) {
+        manifest.sprite_dir = dir.trim_end_matches('/').to_string();
+    }
+
+    if let Some(defaults) = root.get("defaults") {
+        let d = &mut manifest.defaults;
+        d.width = read_usize(defaults, "width")?.unwrap_or(d.width);
+        d.height = read_usize(defaults, "height")?.unwrap_or(d.height);
+        d.offset = read_offset(defaults)?.unwrap_or(d.offset);
+        d.charset = read_string(defaults, "charset");
+        d.filter = read_filter(defaults)?;
+        d.preserve_aspect = read_fit(defaults).map_err(|err| format!("defaults.{err}"))?;
     }
-    let png_path = format!("assets/pokemon/{name}.png");
-    if Path::new(&png_path).exists() {
-        return load_ascii_image(&png_path, POKEMON_WIDTH, POKEMON_HEIGHT, charset);
+
+    if let Some(species) = root.get("species") {
+        let species = species
+            .as_object()
+            .ok_or("species must be an object keyed by name")?;
+        for (name, raw) in species {
+            let entry = parse_species(raw).map_err(|err| format!("species.{name}: {err}"))?;
+            manifest.species.insert(name.to_lowercase(), entry);
+        }
+    }
+
+    if let Some(welcome) = root.get("welcome") {
+        let welcome = welcome.as_array().ok_or("welcome must be an array")?;
+        manifest.welcome.clear();
+        for (i, raw) in welcome.iter().enumerate() {
+            let path = read_string(raw, "path").ok_or(format!("welcome[{i}]: missing path"))?;
+            manifest.welcome.push(WelcomeEntry {
+                path,
+                width: read_usize(raw, "width")?.unwrap_or(ARCANINE_WIDTH),
+                height: read_usize(raw, "height")?.unwrap_or(ARCANINE_HEIGHT),
+                frame_ms: read_usize(raw, "frame_ms")?
+                    .map(|ms| ms as u64)
+                    .unwrap_or(WELCOME_FRAME_MS),
+                charset: read_string(raw, "charset"),
+            });
+        }
+    }
+    Ok(manifest)
+}
+
+fn parse_species(raw: &Value) -> Result<SpeciesEntry, String> {
+    let sprites = match raw.get("sprites").or_else(|| raw.get("sprite")) {
+        None => Vec::new(),
+        Some(Value::String(path)) => vec![path.clone()],
+        Some(Value::Array(list)) => list
+            .iter()
+            .map(|v| {
+                v.as_str()
+                    .map(str::to_string)
+                    .ok_or("sprites must be strings")
+            })
+            .collect::<Result<_, _>>()?,
+        Some(_) => return Err("sprites must be a string or list of strings".to_string()),
+    };
+    Ok(SpeciesEntry {
+        sprites,
+        shiny: read_string(raw, "shiny"),
+        width: read_usize(raw, "width")?,
+        height: read_usize(raw, "height")?,
+        offset: read_offset(raw)?,
+        charset: read_string(raw, "charset"),
+        filter: read_filter(raw)?,
+        preserve_aspect: read_fit(raw)?,
+    })
+}
+
+fn read_string(value: &Value, key: &str) -> Option<String> {
+    value.get(key).and_then(|v| v.as_str()).map(str::to_string)
+}
+
+fn read_usize(value: &Value, key: &str) -> Result<Option<usize>, String> {
+    match value.get(key) {
+        None => Ok(None),
+        Some(v) => v
+            .as_u64()
+            .map(|n| Some(n as usize))
+            .ok_or(format!("{key} must be a non-negative integer")),
+    }
+}
+
+fn read_offset(value: &Value) -> Result<Option<(i32, i32)>, String> {
+    let Some(raw) = value.get("offset") else {
+        return Ok(None);
+    };
+    let pair = raw
+        .as_array()
+        .filter(|pair| pair.len() == 2)
+        .ok_or("offset must be [x, y]")?;
+    let x = pair[0].as_i64().ok_or("offset must be [x, y]")?;
+    let y = pair[1].as_i64().ok_or("offset must be [x, y]")?;
+    Ok(Some((x as i32, y as i32)))
+}
+
+fn read_fit(value: &Value) -> Result<Option<bool>, String> {
+    match read_string(value, "fit").as_deref() {
+        None => Ok(None),
+        Some("stretch") => Ok(Some(false)),
+        Some("aspect") => Ok(Some(true)),
+        Some(other) => Err(format!("fit: unknown mode '{other}'")),
+    }
+}
+
+fn read_filter(value: &Value) -> Result<Option<ResizeFilter>, String> {
+    match read_string(value, "filter") {
+        None => Ok(None),
+        Some(raw) => ResizeFilter::parse(&raw)
+            .map(Some)
+            .ok_or(format!("unknown filter '{raw}'")),
     }
-    panic!("missing pokemon image for {name}");
 }
 
-pub fn load_arcanine_frames(charset: &str) -> Vec<AsciiImage> {
-    load_ascii_animation(ARCANINE_PATH, ARCANINE_WIDTH, ARCANINE_HEIGHT, charset)
+pub fn load_named_pokemon(manifest: &AssetManifest, name: &str, charset: &str) -> AsciiImage {
+    let Some(path) = manifest.resolve_sprite(name) else {
+        panic!("missing pokemon image for {name}");
+    };
+    load_ascii_image(
+        &path,
+        &manifest.convert_config(name),
+        manifest.charset(name, charset),
+    )
+}
+
+/// Hue rotation applied to the regular sprite when a species has no shiny asset.
+const SHINY_HUE_SHIFT: f32 = 150.0;
+
+pub fn load_shiny_pokemon(
+    manifest: &AssetManifest,
+    name: &str,
+    charset: &str,
+    regular: &AsciiImage,
+) -> AsciiImage {
+    match manifest
+        .species
+        .get(name)
+        .and_then(|entry| entry.shiny.as_deref())
+    {
+        Some(path) => load_ascii_image(
+            path,
+            &manifest.convert_config(name),
+            manifest.charset(name, charset),
+        ),
+        None => regular.hue_shifted(SHINY_HUE_SHIFT),
+    }
+}
+
+pub fn load_welcome_animation(entry: &WelcomeEntry, charset: &str) -> WelcomeAnimation {
+    let config = convert_config_from_env(entry.width, entry.height);
+    let charset = entry.charset.as_deref().unwrap_or(charset);
+    let frames = if entry.path.to_lowercase().ends_with(".gif") {
+        load_ascii_animation(&entry.path, &config, charset)
+    } else {
+        vec![load_ascii_image(&entry.path, &config, charset)]
+    };
+    WelcomeAnimation {
+        frames,
+        frame_ms: entry.frame_ms,
+    }
+}
+
+fn convert_config_from_env(width: usize, height: usize) -> ConvertConfig {
+    let mut config = ConvertConfig::new(width, height);
+    if let Ok(raw) = env::var("POKESTREAM_RESIZE_FILTER")
+        && let Some(filter) = ResizeFilter::parse(&raw)
+    {
+        config = config.filter(filter);
+    }
+    if let Ok(raw) = env::var("POKESTREAM_FIT") {
+        match raw.to_lowercase().as_str() {
+            "aspect" | "contain" => config = config.preserve_aspect(true),
+            "aspect_nopad" | "contain_nopad" => {
+                config = config.preserve_aspect(true).letterbox(false)
+            }
+            _ => {}
+        }
+    }
+    if let Ok(raw) = env::var("POKESTREAM_CELL_ASPECT")
+        && let Ok(cell_aspect) = raw.parse::<f32>()
+    {
+        config = config.cell_aspect(cell_aspect);
+    }
+    config
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn short_charsets_are_reported_everywhere() {
+        let manifest = parse_manifest(&json!({
+            "defaults": { "charset": "" },
+            "welcome": [{ "path": "missing.gif", "charset": "#" }]
+        }))
+        .unwrap();
+        let problems = manifest.validate(&[]);
+        assert!(problems.contains(&"defaults: charset needs at least two characters".to_string()));
+        assert!(
+            problems.contains(
+                &"welcome: missing.gif charset needs at least two characters".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn species_fit_overrides_defaults() {
+        let manifest = parse_manifest(&json!({
+            "defaults": { "fit": "aspect" },
+            "species": { "onix": { "fit": "stretch" } }
+        }))
+        .unwrap();
+        assert_eq!(manifest.species["onix"].preserve_aspect, Some(false));
+        assert_eq!(manifest.defaults.preserve_aspect, Some(true));
+        assert!(parse_manifest(&json!({ "species": { "onix": { "fit": "zoom" } } })).is_err());
+    }
 }
@@ -0,0 +1,274 @@
+use crate::inventory::BallKind;
+
+/// Types common enough in every generation to ask for two of in a day.
+const QUEST_TYPES: &[&str] = &[
+    "water", "normal", "grass", "bug", "fire", "poison", "flying", "psychic",
+];
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Goal {
+    Catch,
+    /// Catch Pokemon with this (lowercase) type.
+    CatchType(String),
+    /// Ask the agent to identify or show stats for different Pokemon.
+    AskAgent,
+    /// Knock out wild Pokemon in battle.
+    WinBattle,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Reward {
+    Balls(BallKind, u32),
+    /// Extra daily encounters on top of `POKESTREAM_DAILY_ENCOUNTERS`.
+    Encounters(usize),
+}
+
+/// Something a trainer did that may count toward a quest.
+pub enum Event<'a> {
+    Caught { types: &'a [String] },
+    AskedAgent { species: &'a str },
+    WonBattle,
+}
+
+#[derive(Clone, Debug)]
+pub struct Quest {
+    pub slot: usize,
+    pub goal: Goal,
+    pub target: u32,
+    pub progress: u32,
+    pub reward: Reward,
+    /// Species already counted toward an `AskAgent` quest, so asking about
+    /// the same one again (even after reconnecting) doesn't count twice.
+    pub asked: Vec<String>,
+}
+
+impl Goal {
+    /// Stored form in the `quests` table.
+    pub fn key(&self) -> String {
+        match self {
+            Self::Catch => "catch".to_string(),
+            Self::CatchType(kind) => format!("catch:{kind}"),
+            Self::AskAgent => "ask".to_string(),
+            Self::WinBattle => "battle".to_string(),
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        match key {
+            "catch" => Some(Self::Catch),
+            "ask" => Some(Self::AskAgent),
+            "battle" => Some(Self::WinBattle),
+            _ => key
+                .strip_prefix("catch:")
+                .map(|kind| Self::CatchType(kind.to_string())),
+        }
+    }
+}
+
+impl Reward {
+    pub fn key(self) -> String {
+        match self {
+            Self::Balls(ball, count) => format!("balls:{}:{count}", ball.key()),
+            Self::Encounters(count) => format!("encounters:{count}"),
+        }
+    }
+
+    pub fn parse(key: &str) -> Option<Self> {
+        let mut parts = key.split(':');
+        match parts.next()? {
+            "balls" => {
+                let ball = BallKind::parse(parts.next()?)?;
+                Some(Self::Balls(ball, parts.next()?.parse().ok()?))
+            }
+            "encounters" => Some(Self::Encounters(parts.next()?.parse().ok()?)),
+            _ => None,
+        }
+    }
+
+    pub fn describe(self) -> String {
+        match self {
+            Self::Balls(ball, 1) => format!("1 {}", ball.label()),
+            Self::Balls(ball, count) => format!("{count} {}s", ball.label()),
+            Self::Encounters(1) => "1 extra encounter".to_string(),
+            Self::Encounters(count) => format!("{count} extra encounters"),
+        }
+    }
+}
+
+impl Quest {
+    pub fn done(&self) -> bool {
+        self.progress >= self.target
+    }
+
+    pub fn describe(&self) -> String {
+        let n = self.target;
+        let plural = if n == 1 { "" } else { "s" };
+        match &self.goal {
+            Goal::Catch => format!("Catch {n} Pokemon"),
+            Goal::CatchType(kind) => {
+                let mut kind = kind.clone();
+                if let Some(first) = kind.get_mut(0..1) {
+                    first.make_ascii_uppercase();
+                }
+                format!("Catch {n} {kind} type{plural}")
+            }
+            Goal::AskAgent => format!("Ask the agent about {n} Pokemon"),
+            Goal::WinBattle => format!("Win {n} battle{plural}"),
+        }
+    }
+
+    /// Counts `event` toward the quest. Returns true when it completes the quest.
+    pub fn record(&mut self, event: &Event) -> bool {
+        if self.done() {
+            return false;
+        }
+        let counts = match (&self.goal, event) {
+            (Goal::Catch, Event::Caught { .. }) => true,
+            (Goal::CatchType(kind), Event::Caught { types }) => types.contains(kind),
+            (Goal::AskAgent, Event::AskedAgent { species }) => {
+                let new = !self.asked.iter().any(|asked| asked == species);
+                if new {
+                    self.asked.push(species.to_string());
+                }
+                new
+            }
+            (Goal::WinBattle, Event::WonBattle) => true,
+            _ => false,
+        };
+        if counts {
+            self.progress += 1;
+        }
+        counts && self.done()
+    }
+}
+
+/// Reads the stored `asked` column, a comma-separated species list.
+pub fn parse_asked(stored: &str) -> Vec<String> {
+    stored
+        .split(',')
+        .filter(|species| !species.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The day's quests from a per-trainer daily seed. `has_type` says whether
+/// the loaded dex has any Pokemon of a type. Extra encounters are only offered
+/// when encounters are limited per day.
+pub fn daily(seed: u64, has_type: impl Fn(&str) -> bool, limited_encounters: bool) -> Vec<Quest> {
+    let mut rng = seed ^ 0x5155_4553_5453_0000;
+    let mut roll = |n: usize| (crate::rng::next_u64(&mut rng) % n as u64) as usize;
+    let kinds: Vec<&str> = QUEST_TYPES
+        .iter()
+        .copied()
+        .filter(|kind| has_type(kind))
+        .collect();
+    let first = if kinds.is_empty() {
+        (Goal::Catch, 2)
+    } else {
+        (Goal::CatchType(kinds[roll(kinds.len())].to_string()), 2)
+    };
+    let goals = vec![
+        first,
+        (Goal::AskAgent, 3),
+        if roll(2) == 0 {
+            (Goal::Catch, 3)
+        } else {
+            (Goal::WinBattle, 1)
+        },
+    ];
+    goals
+        .into_iter()
+        .enumerate()
+        .map(|(slot, (goal, target))| {
+            let reward = match roll(3) {
+                0 if limited_encounters => Reward::Encounters(2),
+                0 | 1 => Reward::Balls(BallKind::Great, 3),
+                _ => Reward::Balls(BallKind::Ultra, 1),
+            };
+            Quest {
+                slot,
+                goal,
+                target,
+                progress: 0,
+                reward,
+                asked: Vec::new(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ask_quest() -> Quest {
+        Quest {
+            slot: 1,
+            goal: Goal::AskAgent,
+            target: 2,
+            progress: 0,
+            reward: Reward::Balls(BallKind::Great, 3),
+            asked: parse_asked("pikachu"),
+        }
+    }
+
+    #[test]
+    fn asking_about_the_same_species_counts_once() {
+        let mut quest = ask_quest();
+        quest.progress = 1;
+        assert!(!quest.record(&Event::AskedAgent { species: "pikachu" }));
+        assert_eq!(quest.progress, 1);
+        assert!(quest.record(&Event::AskedAgent { species: "onix" }));
+        assert_eq!(quest.asked.join(","), "pikachu,onix");
+    }
+
+    #[test]
+    fn type_quests_only_count_matching_catches() {
+        let mut quest = Quest {
+            goal: Goal::CatchType("water".to_string()),
+            ..ask_quest()
+        };
+        let grass = ["grass".to_string()];
+        let water = ["water".to_string(), "ice".to_string()];
+        assert!(!quest.record(&Event::Caught { types: &grass }));
+        assert!(!quest.record(&Event::Caught { types: &water }));
+        assert!(quest.record(&Event::Caught { types: &water }));
+        assert!(!quest.record(&Event::Caught { types: &water }));
+        assert_eq!(quest.progress, 2);
+    }
+
+    #[test]
+    fn keys_round_trip() {
+        for goal in [
+            Goal::Catch,
+            Goal::CatchType("fire".to_string()),
+            Goal::AskAgent,
+            Goal::WinBattle,
+        ] {
+            assert_eq!(Goal::parse(&goal.key()), Some(goal));
+        }
+        for reward in [Reward::Balls(BallKind::Ultra, 1), Reward::Encounters(2)] {
+            assert_eq!(Reward::parse(&reward.key()), Some(reward));
+        }
+        assert_eq!(Reward::parse("balls:golden:1"), None);
+        assert!(parse_asked("").is_empty());
+    }
+
+    #[test]
+    fn daily_quests_follow_the_seed() {
+        let a = daily(42, |_| true, true);
+        let b = daily(42, |_| true, true);
+        assert_eq!(a.len(), 3);
+        for (a, b) in a.iter().zip(&b) {
+            assert_eq!((&a.goal, a.target, a.reward), (&b.goal, b.target, b.reward));
+        }
+        assert_eq!(a[1].goal, Goal::AskAgent);
+        let no_types = daily(42, |_| false, false);
+        assert_eq!(no_types[0].goal, Goal::Catch);
+        assert!(
+            no_types
+                .iter()
+                .all(|quest| !matches!(quest.reward, Reward::Encounters(_)))
+        );
+    }
+}
@@ -0,0 +1,10 @@
+/// Xorshift step shared by every seeded roll (encounters, shinies, weather,
+/// quests). Deterministic so a seed always gives the same result.
+pub fn next_u64(state: &mut u64) -> u64 {
+    let mut x = *state;
+    x ^= x >> 12;
+    x ^= x << 25;
+    x ^= x >> 27;
+    *state = x;
+    x.wrapping_mul(0x2545F4914F6CDD1D)
+}
@@ -0,0 +1,203 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde_json::Value;
+
+const ROUTES_PATH: &str = "assets/routes.json";
+
+/// A place to look for wild Pokemon, from `assets/routes.json`.
+pub struct Route {
+    pub id: String,
+    pub name: String,
+    /// Column and row on the overworld map.
+    pub map: (usize, usize),
+    /// Pokedex entries needed before a trainer can travel here.
+    pub unlock: usize,
+    pub links: Vec<String>,
+    /// `None` for routes where any species can appear.
+    pub species: Option<HashSet<String>>,
+}
+
+impl Route {
+    pub fn allows(&self, name: &str) -> bool {
+        self.species
+            .as_ref()
+            .is_none_or(|species| species.contains(name))
+    }
+
+    fn anywhere() -> Self {
+        Self {
+            id: "wilds".to_string(),
+            name: "The Wilds".to_string(),
+            map: (0, 0),
+            unlock: 0,
+            links: Vec::new(),
+            species: None,
+        }
+    }
+}
+
+/// Loads the route list. The first route is where trainers start, so it is
+/// always unlocked. Species missing from `names` are dropped, and routes left
+/// with no species are skipped. Without the file there is a single route where
+/// anything can appear.
+pub fn load_routes(names: &[String]) -> io::Result<Vec<Route>> {
+    load_routes_from(Path::new(ROUTES_PATH), names)
+}
+
+fn load_routes_from(path: &Path, names: &[String]) -> io::Result<Vec<Route>> {
+    if !path.exists() {
+        return Ok(vec![Route::anywhere()]);
+    }
+    let text = fs::read_to_string(path)?;
+    let root: Value = serde_json::from_str(&text).map_err(io::Error::other)?;
+    Ok(parse_routes(&root, names))
+}
+
+fn parse_routes(root: &Value, names: &[String]) -> Vec<Route> {
+    let known: HashSet<&str> = names.iter().map(String::as_str).collect();
+    let mut routes = Vec::new();
+    for entry in root
+        .get("routes")
+        .and_then(|v| v.as_array())
+        .into_iter()
+        .flatten()
+    {
+        let read = |key: &str| entry.get(key).and_then(|v| v.as_str()).map(str::trim);
+        let (Some(id), Some(name)) = (read("id"), read("name")) else {
+            continue;
+        };
+        let species = match entry.get("species") {
+            Some(Value::Array(list)) => {
+                let species: HashSet<String> = list
+                    .iter()
+                    .filter_map(|v| v.as_str())
+                    .map(|s| s.trim().to_lowercase())
+                    .filter(|s| known.contains(s.as_str()))
+                    .collect();
+                if species.is_empty() {
+                    continue;
+                }
+                Some(species)
+            }
+            _ => None,
+        };
+        let coord = |index: usize| {
+            entry
+                .get("map")
+                .and_then(|v| v.get(index))
+                .and_then(|v| v.as_u64())
+                .unwrap_or(0) as usize
+        };
+        routes.push(Route {
+            id: id.to_lowercase(),
+            name: name.to_string(),
+            map: (coord(0), coord(1)),
+            unlock: entry.get("unlock").and_then(|v| v.as_u64()).unwrap_or(0) as usize,
+            links: entry
+                .get("links")
+                .and_then(|v| v.as_array())
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v.as_str())
+                .map(|s| s.trim().to_lowercase())
+                .collect(),
+            species,
+        });
+    }
+    match routes.first_mut() {
+        Some(start) => start.unlock = 0,
+        None => routes.push(Route::anywhere()),
+    }
+    routes
+}
+
+/// Finds a route by number on the map (from 1), id, or name, ignoring case.
+pub fn find(routes: &[Route], input: &str) -> Option<usize> {
+    let input = input.trim().to_lowercase();
+    if let Ok(number) = input.parse::<usize>() {
+        return (1..=routes.len()).contains(&number).then(|| number - 1);
+    }
+    let dashed = input.replace(' ', "-");
+    routes.iter().position(|route| {
+        route.id == dashed
+            || route.name.to_lowercase() == input
+            || route.name.to_lowercase().replace('.', "") == input
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn names() -> Vec<String> {
+        ["pidgey", "rattata", "caterpie", "zubat"]
+            .map(String::from)
+            .to_vec()
+    }
+
+    fn ids(routes: &[Route]) -> Vec<&str> {
+        routes.iter().map(|route| route.id.as_str()).collect()
+    }
+
+    fn sample() -> Vec<Route> {
+        let root = json!({ "routes": [
+            { "id": "Route-1", "name": "Route 1", "map": [4, 2], "unlock": 5,
+              "links": [" Viridian-Forest "], "species": ["Pidgey", "rattata", "mew"] },
+            { "id": "seafoam", "name": "Seafoam Islands", "species": ["seel", "dewgong"] },
+            { "name": "No Id" },
+            { "id": "mt-moon", "name": "Mt. Moon", "unlock": 15, "species": "*" },
+        ]});
+        parse_routes(&root, &names())
+    }
+
+    #[test]
+    fn a_missing_file_gives_one_open_route() {
+        let routes = load_routes_from(Path::new("assets/no-such-routes.json"), &names()).unwrap();
+        assert_eq!(ids(&routes), ["wilds"]);
+        assert_eq!(routes[0].name, "The Wilds");
+        assert!(routes[0].allows("anything"));
+    }
+
+    #[test]
+    fn routes_drop_unknown_species_and_skip_empty_routes() {
+        let routes = sample();
+        assert_eq!(ids(&routes), ["route-1", "mt-moon"]);
+        let start = &routes[0];
+        assert_eq!(
+            start.species,
+            Some(HashSet::from(["pidgey".to_string(), "rattata".to_string()]))
+        );
+        assert!(!start.allows("mew"));
+        assert_eq!(start.map, (4, 2));
+        assert_eq!(start.links, ["viridian-forest"]);
+        assert!(routes[1].allows("zubat"));
+        assert_eq!(routes[1].unlock, 15);
+    }
+
+    #[test]
+    fn the_first_route_is_always_open() {
+        assert_eq!(sample()[0].unlock, 0);
+        assert_eq!(
+            ids(&parse_routes(&json!({ "routes": [] }), &names())),
+            ["wilds"]
+        );
+    }
+
+    #[test]
+    fn routes_are_found_by_number_id_or_name() {
+        let routes = sample();
+        assert_eq!(find(&routes, "2"), Some(1));
+        assert_eq!(find(&routes, "0"), None);
+        assert_eq!(find(&routes, "3"), None);
+        assert_eq!(find(&routes, "route-1"), Some(0));
+        assert_eq!(find(&routes, " Route 1 "), Some(0));
+        assert_eq!(find(&routes, "mt. moon"), Some(1));
+        assert_eq!(find(&routes, "mt moon"), Some(1));
+        assert_eq!(find(&routes, "cerulean"), None);
+    }
+}
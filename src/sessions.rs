@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+
+use tokio::sync::mpsc::UnboundedSender;
+use tokio::sync::watch;
+
+use crate::MirrorFrame;
+
+/// A session's latest frame for spectators; `None` once it stops sharing.
+pub type Mirror = watch::Sender<Option<Arc<MirrorFrame>>>;
+pub type MirrorFeed = watch::Receiver<Option<Arc<MirrorFrame>>>;
+
+/// A caught Pokemon put up for trade.
+#[derive(Clone, Debug)]
+pub struct TradeOffer {
+    pub capture_id: i64,
+    pub species: String,
+    pub level: u16,
+}
+
+/// Messages one live session sends another through the registry.
+#[derive(Clone, Debug)]
+pub enum SessionEvent {
+    TradeRequest {
+        from: String,
+    },
+    TradeAccept {
+        from: String,
+    },
+    TradeOffer {
+        from: String,
+        offer: Option<TradeOffer>,
+    },
+    /// Confirms the pair of captures the sender saw when it confirmed, so a
+    /// confirmation racing a changed offer is ignored.
+    TradeConfirm {
+        from: String,
+        mine: i64,
+        theirs: i64,
+    },
+    TradeCancel {
+        from: String,
+    },
+    TradeComplete {
+        from: String,
+        ok: bool,
+    },
+    LegendaryCaught {
+        trainer: String,
+        species: String,
+    },
+}
+
+struct SessionHandle {
+    id: u64,
+    events: UnboundedSender<SessionEvent>,
+    /// The session's current `screen_label`.
+    activity: &'static str,
+    mirror: Arc<Mirror>,
+    allow_spectators: bool,
+}
+
+fn registry() -> &'static Mutex<HashMap<String, SessionHandle>> {
+    static REGISTRY: OnceLock<Mutex<HashMap<String, SessionHandle>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+pub fn next_session_id() -> u64 {
+    static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+    NEXT_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Registers a trainer's live session. A second login under the same name
+/// takes over from the first.
+pub fn register(
+    trainer: &str,
+    id: u64,
+    events: UnboundedSender<SessionEvent>,
+    mirror: Arc<Mirror>,
+    allow_spectators: bool,
+) {
+    if let Ok(mut sessions) = registry().lock() {
+        sessions.insert(
+            trainer.to_string(),
+            SessionHandle {
+                id,
+                events,
+                activity: "game",
+                mirror,
+                allow_spectators,
+            },
+        );
+    }
+}
+
+pub fn set_activity(trainer: &str, id: u64, activity: &'static str) {
+    if let Ok(mut sessions) = registry().lock()
+        && let Some(handle) = sessions.get_mut(trainer)
+        && handle.id == id
+    {
+        handle.activity = activity;
+    }
+}
+
+/// Removes the session, unless a newer login has already replaced it.
+pub fn unregister(trainer: &str, id: u64) {
+    if let Ok(mut sessions) = registry().lock()
+        && sessions.get(trainer).is_some_and(|handle| handle.id == id)
+    {
+        sessions.remove(trainer);
+    }
+}
+
+pub fn is_online(trainer: &str) -> bool {
+    registry()
+        .lock()
+        .map(|sessions| sessions.contains_key(trainer))
+        .unwrap_or(false)
+}
+
+pub fn set_allow_spectators(trainer: &str, id: u64, allow: bool) {
+    if let Ok(mut sessions) = registry().lock()
+        && let Some(handle) = sessions.get_mut(trainer)
+        && handle.id == id
+    {
+        handle.allow_spectators = allow;
+    }
+}
+
+/// Subscribes to a trainer's frames, unless they are offline or have turned
+/// spectators off.
+pub fn spectate(trainer: &str) -> Option<MirrorFeed> {
+    let sessions = registry().lock().ok()?;
+    let handle = sessions.get(trainer)?;
+    handle.allow_spectators.then(|| handle.mirror.subscribe())
+}
+
+/// Trainers online and what each is doing, sorted by name.
+pub fn online() -> Vec<(String, &'static str)> {
+    let mut list: Vec<(String, &'static str)> = registry()
+        .lock()
+        .map(|sessions| {
+            sessions
+                .iter()
+                .map(|(name, handle)| (name.clone(), handle.activity))
+                .collect()
+        })
+        .unwrap_or_default();
+    list.sort();
+    list
+}
+
+/// Delivers an event to a trainer's session; false when they are offline.
+pub fn send(trainer: &str, event: SessionEvent) -> bool {
+    registry()
+        .lock()
+        .ok()
+        .and_then(|sessions| {
+            sessions
+                .get(trainer)
+                .map(|handle| handle.events.send(event).is_ok())
+        })
+        .unwrap_or(false)
+}
+
+/// Delivers an event to every session except `from`'s.
+pub fn broadcast(from: &str, event: SessionEvent) {
+    if let Ok(sessions) = registry().lock() {
+        for (name, handle) in sessions.iter() {
+            if name != from {
+                let _ = handle.events.send(event.clone());
+            }
+        }
+    }
+}
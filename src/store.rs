@@ -0,0 +1,468 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::io;
+use std::sync::{Mutex, OnceLock};
+
+use rusqlite::{Connection, OptionalExtension, Transaction};
+
+const DEFAULT_REDIS_URL: &str = "redis://127.0.0.1/";
+const REDIS_PREFIX: &str = "pokestream";
+
+/// Where trainer records live. Calls block, so sessions run them inside
+/// `spawn_blocking` the same way they run rusqlite calls.
+///
+/// Only each trainer's Pokedex goes through the store so far; captures,
+/// settings, and the rest still live in the SQLite database.
+pub trait TrainerStore: Send + Sync {
+    /// `sqlite`, `memory`, or `redis`, as named in `POKESTREAM_STORE`.
+    fn kind(&self) -> &'static str;
+    /// Creates tables or checks the connection before the server starts.
+    fn init(&self) -> io::Result<()>;
+    /// A trainer's caught species; empty for trainers never saved.
+    fn load_pokedex(&self, trainer: &str) -> io::Result<HashSet<String>>;
+    fn save_pokedex(&self, trainer: &str, pokedex: &HashSet<String>) -> io::Result<()>;
+    /// `load_pokedex` while `tx` is open on the server's database. Backends
+    /// kept in that database read through it; the rest ignore it.
+    fn load_pokedex_in(&self, _tx: &Transaction, trainer: &str) -> io::Result<HashSet<String>> {
+        self.load_pokedex(trainer)
+    }
+    /// `save_pokedex` while `tx` is open on the server's database. Backends
+    /// kept in that database write through it, so the save commits or rolls
+    /// back with the transaction; the rest save right away.
+    fn save_pokedex_in(
+        &self,
+        _tx: &Transaction,
+        trainer: &str,
+        pokedex: &HashSet<String>,
+    ) -> io::Result<()> {
+        self.save_pokedex(trainer, pokedex)
+    }
+    /// Every saved trainer with their Pokedex.
+    fn trainers(&self) -> io::Result<Vec<(String, HashSet<String>)>>;
+}
+
+/// SQLite database file (`POKESTREAM_DB`, default `pokedex.db`).
+pub fn db_path() -> &'static str {
+    static PATH: OnceLock<String> = OnceLock::new();
+    PATH.get_or_init(|| {
+        env::var("POKESTREAM_DB")
+            .ok()
+            .map(|path| path.trim().to_string())
+            .filter(|path| !path.is_empty())
+            .unwrap_or_else(|| "pokedex.db".to_string())
+    })
+}
+
+/// Picks the backend from `POKESTREAM_STORE` (`sqlite`, `memory`, or `redis`).
+/// SQLite is the default and uses `sqlite_path`; Redis connects to
+/// `POKESTREAM_REDIS_URL`. Only the Pokedex follows this choice: the server
+/// keeps everything else in the SQLite database, so it needs one either way.
+pub fn from_env(sqlite_path: &str) -> io::Result<Box<dyn TrainerStore>> {
+    let kind = env::var("POKESTREAM_STORE")
+        .unwrap_or_default()
+        .trim()
+        .to_lowercase();
+    match kind.as_str() {
+        "" | "sqlite" => Ok(Box::new(SqliteStore::new(sqlite_path))),
+        "memory" => Ok(Box::new(MemoryStore::default())),
+        "redis" => {
+            let url = env::var("POKESTREAM_REDIS_URL")
+                .ok()
+                .map(|url| url.trim().to_string())
+                .filter(|url| !url.is_empty())
+                .unwrap_or_else(|| DEFAULT_REDIS_URL.to_string());
+            Ok(Box::new(RedisStore::open(&url)?))
+        }
+        other => Err(io::Error::other(format!(
+            "unknown POKESTREAM_STORE '{other}' (expected sqlite, memory, or redis)"
+        ))),
+    }
+}
+
+/// Deals with Pokedexes left in the SQLite `trainers` table when another
+/// backend is configured, so they are never silently ignored. With
+/// `POKESTREAM_STORE_IMPORT=1` they are merged into `store` and the table is
+/// emptied; otherwise this fails and the server refuses to start.
+pub fn adopt_sqlite_trainers(sqlite_path: &str, store: &dyn TrainerStore) -> io::Result<()> {
+    if store.kind() == "sqlite" {
+        return Ok(());
+    }
+    let legacy = SqliteStore::new(sqlite_path);
+    let count = legacy.count()?;
+    if count == 0 {
+        return Ok(());
+    }
+    let import = env::var("POKESTREAM_STORE_IMPORT").is_ok_and(|value| value.trim() == "1");
+    if !import || store.kind() == "memory" {
+        return Err(io::Error::other(format!(
+            "{sqlite_path} still has {count} trainer(s) in its trainers table; \
+             set POKESTREAM_STORE_IMPORT=1 to move them into the {} store",
+            store.kind()
+        )));
+    }
+    import_trainers(&legacy, store)
+}
+
+/// Merges every trainer from `legacy` into `store`, then empties `legacy`.
+fn import_trainers(legacy: &SqliteStore, store: &dyn TrainerStore) -> io::Result<()> {
+    for (trainer, pokedex) in legacy.trainers()? {
+        let mut merged = store.load_pokedex(&trainer)?;
+        merged.extend(pokedex);
+        store.save_pokedex(&trainer, &merged)?;
+    }
+    legacy
+        .open()?
+        .execute("DELETE FROM trainers", [])
+        .map_err(io::Error::other)?;
+    Ok(())
+}
+
+/// The `trainers` table, with each Pokedex as a sorted JSON list. The server
+/// opens it on its own database, so trades can save through their transaction.
+pub struct SqliteStore {
+    path: String,
+}
+
+impl SqliteStore {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: path.to_string(),
+        }
+    }
+
+    fn open(&self) -> io::Result<Connection> {
+        Connection::open(&self.path).map_err(io::Error::other)
+    }
+
+    /// Rows in the `trainers` table; 0 if it doesn't exist.
+    fn count(&self) -> io::Result<i64> {
+        let conn = self.open()?;
+        let exists: bool = conn
+            .query_row(
+                "SELECT EXISTS (SELECT 1 FROM sqlite_master WHERE type = 'table' AND name = 'trainers')",
+                [],
+                |row| row.get(0),
+            )
+            .map_err(io::Error::other)?;
+        if !exists {
+            return Ok(0);
+        }
+        conn.query_row("SELECT COUNT(*) FROM trainers", [], |row| row.get(0))
+            .map_err(io::Error::other)
+    }
+}
+
+impl TrainerStore for SqliteStore {
+    fn kind(&self) -> &'static str {
+        "sqlite"
+    }
+
+    fn init(&self) -> io::Result<()> {
+        self.open()?
+            .execute(
+                "CREATE TABLE IF NOT EXISTS trainers (
+                    name TEXT PRIMARY KEY,
+                    pokedex TEXT NOT NULL
+                )",
+                [],
+            )
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn load_pokedex(&self, trainer: &str) -> io::Result<HashSet<String>> {
+        read_pokedex(&self.open()?, trainer)
+    }
+
+    fn save_pokedex(&self, trainer: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+        write_pokedex(&self.open()?, trainer, pokedex)
+    }
+
+    fn load_pokedex_in(&self, tx: &Transaction, trainer: &str) -> io::Result<HashSet<String>> {
+        read_pokedex(tx, trainer)
+    }
+
+    fn save_pokedex_in(
+        &self,
+        tx: &Transaction,
+        trainer: &str,
+        pokedex: &HashSet<String>,
+    ) -> io::Result<()> {
+        write_pokedex(tx, trainer, pokedex)
+    }
+
+    fn trainers(&self) -> io::Result<Vec<(String, HashSet<String>)>> {
+        let conn = self.open()?;
+        let mut stmt = conn
+            .prepare("SELECT name, pokedex FROM trainers")
+            .map_err(io::Error::other)?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })
+            .map_err(io::Error::other)?;
+        let mut trainers = Vec::new();
+        for row in rows {
+            let (name, json) = row.map_err(io::Error::other)?;
+            trainers.push((name, parse_list(&json)?));
+        }
+        Ok(trainers)
+    }
+}
+
+fn read_pokedex(conn: &Connection, trainer: &str) -> io::Result<HashSet<String>> {
+    let json: Option<String> = conn
+        .query_row(
+            "SELECT pokedex FROM trainers WHERE name = ?1",
+            [trainer],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(io::Error::other)?;
+    match json {
+        Some(text) => parse_list(&text),
+        None => Ok(HashSet::new()),
+    }
+}
+
+fn write_pokedex(conn: &Connection, trainer: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+    let mut list: Vec<&String> = pokedex.iter().collect();
+    list.sort();
+    let payload = serde_json::to_string(&list).map_err(io::Error::other)?;
+    conn.execute(
+        "INSERT INTO trainers (name, pokedex)
+         VALUES (?1, ?2)
+         ON CONFLICT(name) DO UPDATE SET pokedex = excluded.pokedex",
+        (trainer, &payload),
+    )
+    .map_err(io::Error::other)?;
+    Ok(())
+}
+
+fn parse_list(json: &str) -> io::Result<HashSet<String>> {
+    let list: Vec<String> = serde_json::from_str(json).map_err(io::Error::other)?;
+    Ok(list.into_iter().collect())
+}
+
+/// Keeps trainers for as long as the server runs. Handy for throwaway
+/// servers and tests; nothing is written anywhere.
+#[derive(Default)]
+pub struct MemoryStore {
+    trainers: Mutex<HashMap<String, HashSet<String>>>,
+}
+
+impl TrainerStore for MemoryStore {
+    fn kind(&self) -> &'static str {
+        "memory"
+    }
+
+    fn init(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn load_pokedex(&self, trainer: &str) -> io::Result<HashSet<String>> {
+        let trainers = self.trainers.lock().map_err(|_| poisoned())?;
+        Ok(trainers.get(trainer).cloned().unwrap_or_default())
+    }
+
+    fn save_pokedex(&self, trainer: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+        let mut trainers = self.trainers.lock().map_err(|_| poisoned())?;
+        trainers.insert(trainer.to_string(), pokedex.clone());
+        Ok(())
+    }
+
+    fn trainers(&self) -> io::Result<Vec<(String, HashSet<String>)>> {
+        let trainers = self.trainers.lock().map_err(|_| poisoned())?;
+        Ok(trainers
+            .iter()
+            .map(|(name, pokedex)| (name.clone(), pokedex.clone()))
+            .collect())
+    }
+}
+
+fn poisoned() -> io::Error {
+    io::Error::other("trainer store lock poisoned")
+}
+
+/// Each Pokedex is a Redis set at `pokestream:trainer:<name>:pokedex`, and
+/// `pokestream:trainers` is the set of trainer names.
+pub struct RedisStore {
+    client: redis::Client,
+}
+
+impl RedisStore {
+    pub fn open(url: &str) -> io::Result<Self> {
+        let client = redis::Client::open(url).map_err(io::Error::other)?;
+        Ok(Self { client })
+    }
+
+    fn connection(&self) -> io::Result<redis::Connection> {
+        self.client.get_connection().map_err(io::Error::other)
+    }
+}
+
+fn pokedex_key(trainer: &str) -> String {
+    format!("{REDIS_PREFIX}:trainer:{trainer}:pokedex")
+}
+
+fn trainers_key() -> String {
+    format!("{REDIS_PREFIX}:trainers")
+}
+
+impl TrainerStore for RedisStore {
+    fn kind(&self) -> &'static str {
+        "redis"
+    }
+
+    fn init(&self) -> io::Result<()> {
+        redis::cmd("PING")
+            .query::<String>(&mut self.connection()?)
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn load_pokedex(&self, trainer: &str) -> io::Result<HashSet<String>> {
+        redis::cmd("SMEMBERS")
+            .arg(pokedex_key(trainer))
+            .query(&mut self.connection()?)
+            .map_err(io::Error::other)
+    }
+
+    /// Replaces the set in one MULTI/EXEC so readers never see it half written.
+    fn save_pokedex(&self, trainer: &str, pokedex: &HashSet<String>) -> io::Result<()> {
+        let key = pokedex_key(trainer);
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("DEL")
+            .arg(&key)
+            .ignore()
+            .cmd("SADD")
+            .arg(trainers_key())
+            .arg(trainer)
+            .ignore();
+        if !pokedex.is_empty() {
+            pipe.cmd("SADD").arg(&key).arg(pokedex).ignore();
+        }
+        pipe.query::<()>(&mut self.connection()?)
+            .map_err(io::Error::other)
+    }
+
+    fn trainers(&self) -> io::Result<Vec<(String, HashSet<String>)>> {
+        let mut conn = self.connection()?;
+        let names: Vec<String> = redis::cmd("SMEMBERS")
+            .arg(trainers_key())
+            .query(&mut conn)
+            .map_err(io::Error::other)?;
+        let mut trainers = Vec::with_capacity(names.len());
+        for name in names {
+            let pokedex: HashSet<String> = redis::cmd("SMEMBERS")
+                .arg(pokedex_key(&name))
+                .query(&mut conn)
+                .map_err(io::Error::other)?;
+            trainers.push((name, pokedex));
+        }
+        Ok(trainers)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dex(names: &[&str]) -> HashSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// A fresh SQLite file under the temp dir, removed when dropped.
+    struct TempDb(String);
+
+    impl TempDb {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("pokestream-{name}-{}.db", std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            Self(path.to_string_lossy().into_owned())
+        }
+    }
+
+    impl Drop for TempDb {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    /// What every backend must do. Trainer names are prefixed so a shared
+    /// Redis can hold other data.
+    fn conformance(store: &dyn TrainerStore) {
+        let ash = "conformance-ash";
+        let misty = "conformance-misty";
+        store.init().unwrap();
+        assert!(store.load_pokedex("conformance-nobody").unwrap().is_empty());
+        store
+            .save_pokedex(ash, &dex(&["pikachu", "bulbasaur"]))
+            .unwrap();
+        assert_eq!(
+            store.load_pokedex(ash).unwrap(),
+            dex(&["pikachu", "bulbasaur"])
+        );
+        store.save_pokedex(ash, &dex(&["pikachu"])).unwrap();
+        assert_eq!(store.load_pokedex(ash).unwrap(), dex(&["pikachu"]));
+        store.save_pokedex(misty, &HashSet::new()).unwrap();
+        assert!(store.load_pokedex(misty).unwrap().is_empty());
+        let trainers: HashMap<String, HashSet<String>> =
+            store.trainers().unwrap().into_iter().collect();
+        assert_eq!(trainers.get(ash), Some(&dex(&["pikachu"])));
+        assert_eq!(trainers.get(misty), Some(&HashSet::new()));
+    }
+
+    #[test]
+    fn memory_store_conforms() {
+        conformance(&MemoryStore::default());
+    }
+
+    #[test]
+    fn sqlite_store_conforms() {
+        let db = TempDb::new("sqlite-store");
+        conformance(&SqliteStore::new(&db.0));
+    }
+
+    /// Runs against a real server only when `POKESTREAM_TEST_REDIS_URL` is set.
+    #[test]
+    fn redis_store_conforms() {
+        let Ok(url) = env::var("POKESTREAM_TEST_REDIS_URL") else {
+            return;
+        };
+        conformance(&RedisStore::open(&url).unwrap());
+    }
+
+    #[test]
+    fn sqlite_trainers_are_merged_into_the_new_store_and_cleared() {
+        let db = TempDb::new("import");
+        let legacy = SqliteStore::new(&db.0);
+        legacy.init().unwrap();
+        legacy.save_pokedex("ash", &dex(&["pikachu"])).unwrap();
+        legacy.save_pokedex("misty", &dex(&["staryu"])).unwrap();
+        assert_eq!(legacy.count().unwrap(), 2);
+        let store = MemoryStore::default();
+        store.save_pokedex("ash", &dex(&["eevee"])).unwrap();
+        import_trainers(&legacy, &store).unwrap();
+        assert_eq!(
+            store.load_pokedex("ash").unwrap(),
+            dex(&["pikachu", "eevee"])
+        );
+        assert_eq!(store.load_pokedex("misty").unwrap(), dex(&["staryu"]));
+        assert_eq!(legacy.count().unwrap(), 0);
+    }
+
+    #[test]
+    fn other_backends_refuse_leftover_sqlite_trainers() {
+        let db = TempDb::new("refuse");
+        let store = MemoryStore::default();
+        adopt_sqlite_trainers(&db.0, &store).unwrap();
+        let legacy = SqliteStore::new(&db.0);
+        legacy.init().unwrap();
+        adopt_sqlite_trainers(&db.0, &store).unwrap();
+        legacy.save_pokedex("ash", &dex(&["pikachu"])).unwrap();
+        assert!(adopt_sqlite_trainers(&db.0, &store).is_err());
+        adopt_sqlite_trainers(&db.0, &legacy).unwrap();
+    }
+}
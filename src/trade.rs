@@ -0,0 +1,140 @@
+use crate::sessions::TradeOffer;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Stage {
+    /// Waiting for the other trainer to accept the request.
+    Requested,
+    Choosing,
+}
+
+/// One side's view of a trade in progress with another live session.
+pub struct Trade {
+    pub partner: String,
+    pub stage: Stage,
+    pub mine: Option<TradeOffer>,
+    pub theirs: Option<TradeOffer>,
+    pub confirmed: bool,
+    pub partner_confirmed: bool,
+    pub notice: Option<String>,
+}
+
+impl Trade {
+    pub fn new(partner: &str, stage: Stage) -> Self {
+        Self {
+            partner: partner.to_string(),
+            stage,
+            mine: None,
+            theirs: None,
+            confirmed: false,
+            partner_confirmed: false,
+            notice: None,
+        }
+    }
+
+    /// Changing either offer withdraws both confirmations.
+    pub fn set_mine(&mut self, offer: Option<TradeOffer>) {
+        self.mine = offer;
+        self.confirmed = false;
+        self.partner_confirmed = false;
+    }
+
+    pub fn set_theirs(&mut self, offer: Option<TradeOffer>) {
+        self.theirs = offer;
+        self.confirmed = false;
+        self.partner_confirmed = false;
+    }
+
+    /// Capture ids on the table, as (mine, theirs).
+    pub fn offer_ids(&self) -> Option<(i64, i64)> {
+        Some((
+            self.mine.as_ref()?.capture_id,
+            self.theirs.as_ref()?.capture_id,
+        ))
+    }
+
+    /// Records the partner's confirmation if it was for the offers currently
+    /// on the table. `mine` and `theirs` are from the partner's point of view.
+    pub fn partner_confirms(&mut self, mine: i64, theirs: i64) -> bool {
+        if self.offer_ids() == Some((theirs, mine)) {
+            self.partner_confirmed = true;
+        }
+        self.partner_confirmed
+    }
+
+    pub fn ready(&self) -> bool {
+        self.stage == Stage::Choosing && self.confirmed && self.partner_confirmed
+    }
+
+    /// Only one side writes the swap so it happens exactly once; the trainer
+    /// whose name sorts first does it.
+    pub fn runs_swap(&self, me: &str) -> bool {
+        me < self.partner.as_str()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn offer(capture_id: i64) -> Option<TradeOffer> {
+        Some(TradeOffer {
+            capture_id,
+            species: "abra".to_string(),
+            level: 5,
+        })
+    }
+
+    fn trade_on_table() -> Trade {
+        let mut trade = Trade::new("misty", Stage::Choosing);
+        trade.set_mine(offer(1));
+        trade.set_theirs(offer(2));
+        trade
+    }
+
+    #[test]
+    fn ready_needs_both_confirmations() {
+        let mut trade = trade_on_table();
+        trade.confirmed = true;
+        assert!(!trade.ready());
+        assert!(trade.partner_confirms(2, 1));
+        assert!(trade.ready());
+    }
+
+    #[test]
+    fn confirmation_for_other_offers_is_ignored() {
+        let mut trade = trade_on_table();
+        assert!(!trade.partner_confirms(1, 2));
+        assert!(!trade.partner_confirms(2, 3));
+        assert!(!trade.partner_confirmed);
+    }
+
+    #[test]
+    fn changing_an_offer_withdraws_confirmations() {
+        let mut trade = trade_on_table();
+        trade.confirmed = true;
+        trade.partner_confirms(2, 1);
+        trade.set_theirs(offer(3));
+        assert!(!trade.confirmed && !trade.partner_confirmed);
+        trade.confirmed = true;
+        trade.partner_confirms(3, 1);
+        trade.set_mine(offer(4));
+        assert!(!trade.ready());
+    }
+
+    #[test]
+    fn requested_trades_are_never_ready() {
+        let mut trade = trade_on_table();
+        trade.stage = Stage::Requested;
+        trade.confirmed = true;
+        trade.partner_confirms(2, 1);
+        assert!(!trade.ready());
+    }
+
+    #[test]
+    fn exactly_one_side_runs_the_swap() {
+        let ash = Trade::new("misty", Stage::Choosing);
+        let misty = Trade::new("ash", Stage::Choosing);
+        assert!(ash.runs_swap("ash"));
+        assert!(!misty.runs_swap("misty"));
+    }
+}
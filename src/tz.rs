@@ -0,0 +1,218 @@
+use std::env;
+use std::io;
+use std::sync::OnceLock;
+
+use jiff::Timestamp;
+use jiff::tz::{Offset, TimeZone, TimeZoneDatabase};
+
+/// A named time zone from the tz database, or a fixed `UTC+h[:mm]` offset.
+/// Offsets are seconds east of UTC.
+#[derive(Clone, Debug)]
+pub struct Zone {
+    pub name: String,
+    tz: TimeZone,
+}
+
+/// `POKESTREAM_ZONEINFO` when set, otherwise the system tz database with the
+/// copy bundled into the binary as a fallback.
+fn database() -> &'static TimeZoneDatabase {
+    static DB: OnceLock<TimeZoneDatabase> = OnceLock::new();
+    DB.get_or_init(|| match env::var("POKESTREAM_ZONEINFO") {
+        Ok(dir) => TimeZoneDatabase::from_dir(dir.trim()).unwrap_or_else(|err| {
+            panic!("invalid POKESTREAM_ZONEINFO '{dir}': {err}");
+        }),
+        Err(_) => jiff::tz::db().clone(),
+    })
+}
+
+impl Zone {
+    pub fn utc() -> Self {
+        Self {
+            name: "UTC".to_string(),
+            tz: TimeZone::UTC,
+        }
+    }
+
+    /// Looks a zone up by IANA name (case-insensitively, e.g. `europe/berlin`)
+    /// or as a fixed offset such as `UTC-5` or `UTC+5:30`.
+    pub fn load(name: &str) -> io::Result<Self> {
+        let name = name.trim();
+        if let Some(offset) = parse_fixed(name) {
+            if offset == 0 {
+                return Ok(Self::utc());
+            }
+            let offset = Offset::from_seconds(offset).map_err(io::Error::other)?;
+            return Ok(Self {
+                name: format!("UTC{}", format_offset(offset.seconds())),
+                tz: TimeZone::fixed(offset),
+            });
+        }
+        let tz = database()
+            .get(name)
+            .map_err(|_| io::Error::new(io::ErrorKind::NotFound, "unknown time zone"))?;
+        Ok(Self {
+            name: tz.iana_name().unwrap_or(name).to_string(),
+            tz,
+        })
+    }
+
+    pub fn offset_at(&self, unix: i64) -> i32 {
+        let at = Timestamp::from_second(unix).unwrap_or(Timestamp::UNIX_EPOCH);
+        self.tz.to_offset(at).seconds()
+    }
+
+    /// Days since the epoch by the local calendar, so the day flips at local
+    /// midnight.
+    pub fn day_index(&self, unix: i64) -> i64 {
+        (unix + self.offset_at(unix) as i64).div_euclid(86_400)
+    }
+
+    /// `Europe/Berlin (UTC+02:00)`.
+    pub fn describe(&self, unix: i64) -> String {
+        let offset = format_offset(self.offset_at(unix));
+        if self.name.starts_with("UTC") {
+            format!("UTC{offset}")
+        } else {
+            format!("{} (UTC{offset})", self.name)
+        }
+    }
+
+    /// `YYYY-MM-DD` by the local calendar for a Unix timestamp.
+    pub fn format_date(&self, unix: i64) -> String {
+        let at = Timestamp::from_second(unix).unwrap_or(Timestamp::UNIX_EPOCH);
+        at.to_zoned(self.tz.clone()).date().to_string()
+    }
+}
+
+fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let minutes = offset.abs() / 60;
+    format!("{sign}{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// `UTC`, `GMT`, `UTC+2`, `UTC-05:00`, `UTC+5:30`.
+fn parse_fixed(name: &str) -> Option<i32> {
+    let upper = name.to_ascii_uppercase();
+    let rest = upper
+        .strip_prefix("UTC")
+        .or_else(|| upper.strip_prefix("GMT"))?;
+    if rest.is_empty() {
+        return Some(0);
+    }
+    let (sign, rest) = match rest.as_bytes()[0] {
+        b'+' => (1, &rest[1..]),
+        b'-' => (-1, &rest[1..]),
+        _ => return None,
+    };
+    let (hours, minutes) = rest.split_once(':').unwrap_or((rest, "00"));
+    let digits = |part: &str, len: std::ops::RangeInclusive<usize>| {
+        len.contains(&part.len()) && part.bytes().all(|b| b.is_ascii_digit())
+    };
+    if !digits(hours, 1..=2) || !digits(minutes, 2..=2) {
+        return None;
+    }
+    let hours: i32 = hours.parse().ok()?;
+    let minutes: i32 = minutes.parse().ok()?;
+    (hours <= 14 && minutes < 60).then_some(sign * (hours * 3600 + minutes * 60))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Unix time for a UTC date and hour.
+    fn utc(date: &str, hour: i8) -> i64 {
+        let date: jiff::civil::Date = date.parse().unwrap();
+        date.at(hour, 0, 0, 0)
+            .to_zoned(TimeZone::UTC)
+            .unwrap()
+            .timestamp()
+            .as_second()
+    }
+
+    #[test]
+    fn new_york_switches_at_2am_local() {
+        let zone = Zone::load("America/New_York").unwrap();
+        // 2026-03-08 02:00 EST is 07:00 UTC; 2026-11-01 02:00 EDT is 06:00 UTC.
+        assert_eq!(zone.offset_at(utc("2026-03-08", 7) - 1), -5 * 3600);
+        assert_eq!(zone.offset_at(utc("2026-03-08", 7)), -4 * 3600);
+        assert_eq!(zone.offset_at(utc("2026-11-01", 6) - 1), -4 * 3600);
+        assert_eq!(zone.offset_at(utc("2026-11-01", 6)), -5 * 3600);
+    }
+
+    #[test]
+    fn berlin_switches_at_1am_utc() {
+        let zone = Zone::load("europe/berlin").unwrap();
+        assert_eq!(zone.name, "Europe/Berlin");
+        assert_eq!(zone.offset_at(utc("2026-03-29", 1) - 1), 3600);
+        assert_eq!(zone.offset_at(utc("2026-03-29", 1)), 7200);
+        assert_eq!(zone.offset_at(utc("2026-10-25", 1) - 1), 7200);
+        assert_eq!(zone.offset_at(utc("2026-10-25", 1)), 3600);
+        assert_eq!(
+            zone.describe(utc("2026-07-01", 0)),
+            "Europe/Berlin (UTC+02:00)"
+        );
+    }
+
+    #[test]
+    fn southern_hemisphere_summer_is_in_january() {
+        let sydney = Zone::load("Australia/Sydney").unwrap();
+        assert_eq!(sydney.offset_at(utc("2026-01-15", 0)), 11 * 3600);
+        assert_eq!(sydney.offset_at(utc("2026-07-15", 0)), 10 * 3600);
+        let santiago = Zone::load("America/Santiago").unwrap();
+        assert!(
+            santiago.offset_at(utc("2026-01-15", 0)) > santiago.offset_at(utc("2026-07-15", 0))
+        );
+    }
+
+    #[test]
+    fn local_day_flips_at_local_midnight() {
+        let zone = Zone::load("UTC-5").unwrap();
+        let midnight = utc("2026-10-18", 5);
+        assert_eq!(zone.day_index(midnight) - zone.day_index(midnight - 1), 1);
+        assert_eq!(zone.describe(midnight), "UTC-05:00");
+    }
+
+    #[test]
+    fn fixed_offsets() {
+        assert_eq!(parse_fixed("UTC"), Some(0));
+        assert_eq!(parse_fixed("gmt"), Some(0));
+        assert_eq!(parse_fixed("UTC+2"), Some(7200));
+        assert_eq!(parse_fixed("UTC-05:00"), Some(-5 * 3600));
+        assert_eq!(parse_fixed("UTC+5:30"), Some(5 * 3600 + 1800));
+        assert_eq!(parse_fixed("UTC+14"), Some(14 * 3600));
+        for bad in [
+            "UTC+-3",
+            "UTC-+3",
+            "UTC+",
+            "UTC+15",
+            "UTC+5:3",
+            "UTC+5:60",
+            "UTC+005",
+            "UTC 5",
+            "UTC+5:",
+            "UTC++5",
+            "Mars/Olympus",
+        ] {
+            assert_eq!(parse_fixed(bad), None, "{bad}");
+        }
+        assert!(Zone::load("UTC+-3").is_err());
+    }
+
+    #[test]
+    fn dates_follow_the_local_calendar() {
+        assert_eq!(Zone::utc().format_date(0), "1970-01-01");
+        let late = utc("2026-10-18", 23);
+        assert_eq!(Zone::utc().format_date(late), "2026-10-18");
+        assert_eq!(
+            Zone::load("Europe/Berlin").unwrap().format_date(late),
+            "2026-10-19"
+        );
+        assert_eq!(
+            Zone::load("UTC-5")
+                .unwrap()
+                .format_date(utc("2026-10-18", 3)),
+            "2026-10-17"
+        );
+    }
+}